@@ -0,0 +1,184 @@
+use crate::ParseError;
+use std::fmt;
+use std::str::FromStr;
+
+/// Separator of the account name segments.
+pub const ACCOUNT_SEPARATOR: char = ':';
+
+///
+/// Account name made of `:` separated segments, e.g. `Assets:Bank:Checking`.
+///
+/// The name is not empty and none of its segments is empty
+/// (so it does not start or end with `:` and does not contain `::`).
+///
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "String", into = "String")
+)]
+pub struct AccountName(String);
+
+impl AccountName {
+    pub fn new(name: impl Into<String>) -> Result<Self, ParseError> {
+        let name = name.into();
+        validate(&name)?;
+        Ok(AccountName(name))
+    }
+
+    /// Wraps a name that has already been validated by the parser.
+    pub(crate) fn from_validated(name: String) -> Self {
+        AccountName(name)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn segments(&self) -> impl DoubleEndedIterator<Item = &str> + '_ {
+        self.0.split(ACCOUNT_SEPARATOR)
+    }
+
+    /// Number of segments, `1` for top-level accounts.
+    pub fn depth(&self) -> usize {
+        self.segments().count()
+    }
+
+    /// Last segment of the name.
+    pub fn leaf(&self) -> &str {
+        self.0
+            .rsplit_once(ACCOUNT_SEPARATOR)
+            .map_or(self.as_str(), |(_, leaf)| leaf)
+    }
+
+    /// Name without the last segment, `None` for top-level accounts.
+    pub fn parent(&self) -> Option<AccountName> {
+        self.0
+            .rsplit_once(ACCOUNT_SEPARATOR)
+            .map(|(parent, _)| AccountName(parent.to_owned()))
+    }
+
+    /// Iterates over the parents, starting with the top-level one.
+    pub fn ancestors(&self) -> impl Iterator<Item = &str> + '_ {
+        self.0
+            .match_indices(ACCOUNT_SEPARATOR)
+            .map(move |(pos, _)| &self.0[..pos])
+    }
+
+    /// Returns `true` if the account is a (direct or indirect) sub-account of `other`.
+    pub fn is_descendant_of(&self, other: &AccountName) -> bool {
+        self.0
+            .strip_prefix(other.as_str())
+            .is_some_and(|rest| rest.starts_with(ACCOUNT_SEPARATOR))
+    }
+
+    /// Returns `true` if the account is `other` or one of its sub-accounts.
+    pub fn starts_with(&self, other: &AccountName) -> bool {
+        self == other || self.is_descendant_of(other)
+    }
+
+    /// Builds the name of a sub-account. `child` may contain several segments.
+    pub fn join(&self, child: &str) -> Result<AccountName, ParseError> {
+        AccountName::new(format!("{}{}{}", self.0, ACCOUNT_SEPARATOR, child))
+    }
+}
+
+pub(crate) fn validate(name: &str) -> Result<(), ParseError> {
+    if name.is_empty() {
+        return Err(ParseError::String("account name is empty".to_owned()));
+    }
+    if name.split(ACCOUNT_SEPARATOR).any(str::is_empty) {
+        return Err(ParseError::String(format!(
+            "account name '{}' has an empty segment",
+            name
+        )));
+    }
+    Ok(())
+}
+
+impl FromStr for AccountName {
+    type Err = ParseError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        AccountName::new(name)
+    }
+}
+
+impl TryFrom<String> for AccountName {
+    type Error = ParseError;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        AccountName::new(name)
+    }
+}
+
+impl From<AccountName> for String {
+    fn from(name: AccountName) -> Self {
+        name.0
+    }
+}
+
+impl AsRef<str> for AccountName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for AccountName {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for AccountName {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl fmt::Display for AccountName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hierarchy() {
+        let name: AccountName = "Assets:Bank 1:Checking".parse().unwrap();
+        assert_eq!(
+            name.segments().collect::<Vec<_>>(),
+            vec!["Assets", "Bank 1", "Checking"]
+        );
+        assert_eq!(name.depth(), 3);
+        assert_eq!(name.leaf(), "Checking");
+        assert_eq!(name.parent().unwrap(), "Assets:Bank 1");
+        assert_eq!(
+            name.ancestors().collect::<Vec<_>>(),
+            vec!["Assets", "Assets:Bank 1"]
+        );
+
+        let assets: AccountName = "Assets".parse().unwrap();
+        assert_eq!(assets.parent(), None);
+        assert_eq!(assets.leaf(), "Assets");
+        assert!(name.is_descendant_of(&assets));
+        assert!(!assets.is_descendant_of(&assets));
+        assert!(assets.starts_with(&assets));
+        assert!(!name.is_descendant_of(&"Assets:Bank".parse().unwrap()));
+        assert_eq!(assets.join("Bank 1:Checking").unwrap(), name);
+    }
+
+    #[test]
+    fn validation() {
+        for name in ["", ":Assets", "Assets:", "Assets::Bank", ":"] {
+            assert!(AccountName::new(name).is_err(), "{:?}", name);
+        }
+        assert!("Assets".parse::<AccountName>().unwrap().join("").is_err());
+        assert!("2018-10-01 Payee\n  Assets::Bank  $1\n  Equity\n"
+            .parse::<crate::Ledger>()
+            .is_err());
+    }
+}
@@ -0,0 +1,502 @@
+use crate::model::*;
+use crate::serializer::*;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum AmountError {
+    /// Amounts in different commodities cannot be added or subtracted.
+    CommodityMismatch(String, String),
+    /// Result does not fit in a `Decimal`.
+    Overflow,
+    DivisionByZero,
+}
+
+impl fmt::Display for AmountError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AmountError::CommodityMismatch(left, right) => {
+                write!(f, "commodities differ: '{}' and '{}'", left, right)
+            }
+            AmountError::Overflow => write!(f, "arithmetic overflow"),
+            AmountError::DivisionByZero => write!(f, "division by zero"),
+        }
+    }
+}
+
+impl std::error::Error for AmountError {}
+
+impl Amount {
+    pub fn new(quantity: Decimal, commodity: Commodity) -> Self {
+        Amount {
+            quantity,
+            commodity,
+            span: None,
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.quantity.is_zero()
+    }
+
+    /// Multiplies the quantity, returning an error instead of overflowing.
+    pub fn checked_mul(&self, factor: Decimal) -> Result<Amount, AmountError> {
+        let quantity = self
+            .quantity
+            .checked_mul(factor)
+            .ok_or(AmountError::Overflow)?;
+        Ok(Amount::new(quantity, self.commodity.clone()))
+    }
+
+    /// Divides the quantity, returning an error instead of overflowing or dividing by zero.
+    pub fn checked_div(&self, divisor: Decimal) -> Result<Amount, AmountError> {
+        if divisor.is_zero() {
+            return Err(AmountError::DivisionByZero);
+        }
+        let quantity = self
+            .quantity
+            .checked_div(divisor)
+            .ok_or(AmountError::Overflow)?;
+        Ok(Amount::new(quantity, self.commodity.clone()))
+    }
+
+    pub(crate) fn check_commodity(&self, other: &Amount) -> Result<(), AmountError> {
+        if self.commodity.name == other.commodity.name {
+            Ok(())
+        } else {
+            Err(AmountError::CommodityMismatch(
+                self.commodity.name.clone(),
+                other.commodity.name.clone(),
+            ))
+        }
+    }
+}
+
+impl PostingAmount {
+    /// Value of the amount in the commodity it was exchanged for: the `@`/`@@` price if
+    /// given, otherwise the `{}`/`{{}}` lot price, otherwise the amount itself.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cost overflows (see [`checked_cost`](PostingAmount::checked_cost)).
+    pub fn cost(&self) -> Amount {
+        self.checked_cost().expect("cost overflowed")
+    }
+
+    /// Same as [`cost`](PostingAmount::cost), returning an error instead of overflowing.
+    pub fn checked_cost(&self) -> Result<Amount, AmountError> {
+        match self.price.as_ref().or(self.lot_price.as_ref()) {
+            Some(Price::Unit(price)) => price.checked_mul(self.amount.quantity),
+            Some(Price::Total(price)) if self.amount.quantity.is_sign_negative() => Ok(-price),
+            Some(Price::Total(price)) => Ok(Amount::new(price.quantity, price.commodity.clone())),
+            None => Ok(Amount::new(
+                self.amount.quantity,
+                self.amount.commodity.clone(),
+            )),
+        }
+    }
+}
+
+// Results of the arithmetic keep the commodity (and its style) of the left operand
+// and have no span. Operators returning an `Amount` or a `MixedAmount` panic on overflow,
+// the `checked_*` methods return `AmountError::Overflow` instead.
+
+impl Add for &Amount {
+    type Output = Result<Amount, AmountError>;
+
+    fn add(self, other: &Amount) -> Self::Output {
+        self.check_commodity(other)?;
+        let quantity = self
+            .quantity
+            .checked_add(other.quantity)
+            .ok_or(AmountError::Overflow)?;
+        Ok(Amount::new(quantity, self.commodity.clone()))
+    }
+}
+
+impl Add for Amount {
+    type Output = Result<Amount, AmountError>;
+
+    fn add(self, other: Amount) -> Self::Output {
+        &self + &other
+    }
+}
+
+impl Sub for &Amount {
+    type Output = Result<Amount, AmountError>;
+
+    fn sub(self, other: &Amount) -> Self::Output {
+        self + &-other
+    }
+}
+
+impl Sub for Amount {
+    type Output = Result<Amount, AmountError>;
+
+    fn sub(self, other: Amount) -> Self::Output {
+        &self - &other
+    }
+}
+
+impl Neg for &Amount {
+    type Output = Amount;
+
+    fn neg(self) -> Amount {
+        Amount::new(-self.quantity, self.commodity.clone())
+    }
+}
+
+impl Neg for Amount {
+    type Output = Amount;
+
+    fn neg(self) -> Amount {
+        -&self
+    }
+}
+
+impl Mul<Decimal> for &Amount {
+    type Output = Amount;
+
+    fn mul(self, factor: Decimal) -> Amount {
+        Amount::new(self.quantity * factor, self.commodity.clone())
+    }
+}
+
+impl Mul<Decimal> for Amount {
+    type Output = Amount;
+
+    fn mul(self, factor: Decimal) -> Amount {
+        &self * factor
+    }
+}
+
+impl Div<Decimal> for &Amount {
+    type Output = Amount;
+
+    /// # Panics
+    ///
+    /// Panics if `divisor` is zero.
+    fn div(self, divisor: Decimal) -> Amount {
+        Amount::new(self.quantity / divisor, self.commodity.clone())
+    }
+}
+
+impl Div<Decimal> for Amount {
+    type Output = Amount;
+
+    fn div(self, divisor: Decimal) -> Amount {
+        &self / divisor
+    }
+}
+
+///
+/// Sum of amounts in any number of commodities, e.g. a balance of an account.
+///
+/// Commodities whose total is exactly zero are dropped.
+///
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct MixedAmount {
+    amounts: BTreeMap<String, Amount>,
+}
+
+impl MixedAmount {
+    pub fn new() -> Self {
+        MixedAmount::default()
+    }
+
+    /// Amounts sorted by commodity name.
+    pub fn amounts(&self) -> impl Iterator<Item = &Amount> {
+        self.amounts.values()
+    }
+
+    pub fn get(&self, commodity_name: &str) -> Option<&Amount> {
+        self.amounts.get(commodity_name)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.amounts.is_empty()
+    }
+
+    /// Returns `true` if no commodity differs from zero by more than `tolerance`.
+    pub fn is_zero_within(&self, tolerance: Decimal) -> bool {
+        self.amounts
+            .values()
+            .all(|amount| amount.quantity.abs() <= tolerance)
+    }
+
+    /// Returns the only amount, or `None` if there are none or several commodities.
+    pub fn single(&self) -> Option<&Amount> {
+        match self.amounts.len() {
+            1 => self.amounts.values().next(),
+            _ => None,
+        }
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the total overflows
+    /// (see [`checked_add_amount`](MixedAmount::checked_add_amount)).
+    pub fn add_amount(&mut self, amount: &Amount) {
+        self.checked_add_amount(amount).expect("total overflowed");
+    }
+
+    /// Adds the amount, returning an error (and leaving the total unchanged) instead of
+    /// overflowing.
+    pub fn checked_add_amount(&mut self, amount: &Amount) -> Result<(), AmountError> {
+        match self.amounts.get_mut(&amount.commodity.name) {
+            Some(total) => {
+                total.quantity = total
+                    .quantity
+                    .checked_add(amount.quantity)
+                    .ok_or(AmountError::Overflow)?;
+                if total.is_zero() {
+                    self.amounts.remove(&amount.commodity.name);
+                }
+            }
+            None if amount.is_zero() => {}
+            None => {
+                self.amounts.insert(
+                    amount.commodity.name.clone(),
+                    Amount::new(amount.quantity, amount.commodity.clone()),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Sum of the two amounts, or an error if it overflows.
+    pub fn checked_add(&self, other: &MixedAmount) -> Result<MixedAmount, AmountError> {
+        let mut total = self.clone();
+        for amount in other.amounts() {
+            total.checked_add_amount(amount)?;
+        }
+        Ok(total)
+    }
+
+    /// Difference of the two amounts, or an error if it overflows.
+    pub fn checked_sub(&self, other: &MixedAmount) -> Result<MixedAmount, AmountError> {
+        self.checked_add(&-other.clone())
+    }
+}
+
+impl From<Amount> for MixedAmount {
+    fn from(amount: Amount) -> Self {
+        let mut mixed = MixedAmount::new();
+        mixed.add_amount(&amount);
+        mixed
+    }
+}
+
+impl AddAssign<&Amount> for MixedAmount {
+    fn add_assign(&mut self, amount: &Amount) {
+        self.add_amount(amount);
+    }
+}
+
+impl SubAssign<&Amount> for MixedAmount {
+    fn sub_assign(&mut self, amount: &Amount) {
+        self.add_amount(&-amount);
+    }
+}
+
+impl AddAssign<&MixedAmount> for MixedAmount {
+    fn add_assign(&mut self, other: &MixedAmount) {
+        for amount in other.amounts() {
+            self.add_amount(amount);
+        }
+    }
+}
+
+impl SubAssign<&MixedAmount> for MixedAmount {
+    fn sub_assign(&mut self, other: &MixedAmount) {
+        for amount in other.amounts() {
+            self.add_amount(&-amount);
+        }
+    }
+}
+
+impl Add for MixedAmount {
+    type Output = MixedAmount;
+
+    fn add(mut self, other: MixedAmount) -> MixedAmount {
+        self += &other;
+        self
+    }
+}
+
+impl Sub for MixedAmount {
+    type Output = MixedAmount;
+
+    fn sub(mut self, other: MixedAmount) -> MixedAmount {
+        self -= &other;
+        self
+    }
+}
+
+impl Neg for MixedAmount {
+    type Output = MixedAmount;
+
+    fn neg(mut self) -> MixedAmount {
+        for amount in self.amounts.values_mut() {
+            amount.quantity = -amount.quantity;
+        }
+        self
+    }
+}
+
+impl<'a> Sum<&'a Amount> for MixedAmount {
+    fn sum<I: Iterator<Item = &'a Amount>>(iter: I) -> Self {
+        let mut total = MixedAmount::new();
+        for amount in iter {
+            total.add_amount(amount);
+        }
+        total
+    }
+}
+
+impl Sum<Amount> for MixedAmount {
+    fn sum<I: Iterator<Item = Amount>>(iter: I) -> Self {
+        let mut total = MixedAmount::new();
+        for amount in iter {
+            total.add_amount(&amount);
+        }
+        total
+    }
+}
+
+impl<'a> Sum<&'a MixedAmount> for MixedAmount {
+    fn sum<I: Iterator<Item = &'a MixedAmount>>(iter: I) -> Self {
+        let mut total = MixedAmount::new();
+        for amount in iter {
+            total += amount;
+        }
+        total
+    }
+}
+
+impl FromIterator<Amount> for MixedAmount {
+    fn from_iter<I: IntoIterator<Item = Amount>>(iter: I) -> Self {
+        iter.into_iter().sum()
+    }
+}
+
+/// Amounts are separated with `, `, a zero amount is written as `0`.
+impl fmt::Display for MixedAmount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        let settings = SerializerSettings::default();
+        for (i, amount) in self.amounts().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", amount.to_string_pretty(&settings))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn amount(quantity: i64, scale: u32, name: &str, position: CommodityPosition) -> Amount {
+        Amount::new(
+            Decimal::new(quantity, scale),
+            Commodity {
+                name: name.to_owned(),
+                position,
+            },
+        )
+    }
+
+    #[test]
+    fn amount_operators() {
+        let usd = amount(120, 2, "$", CommodityPosition::Left);
+        let more_usd = amount(5, 1, "$", CommodityPosition::Left);
+        let pln = amount(5, 0, "PLN", CommodityPosition::Right);
+
+        assert_eq!(
+            &usd + &more_usd,
+            Ok(amount(170, 2, "$", CommodityPosition::Left))
+        );
+        assert_eq!(
+            usd.clone() - more_usd,
+            Ok(amount(70, 2, "$", CommodityPosition::Left))
+        );
+        assert_eq!(
+            &usd + &pln,
+            Err(AmountError::CommodityMismatch(
+                "$".to_owned(),
+                "PLN".to_owned()
+            ))
+        );
+        assert_eq!(-&pln, amount(-5, 0, "PLN", CommodityPosition::Right));
+        assert_eq!(
+            &pln * Decimal::new(3, 0),
+            amount(15, 0, "PLN", CommodityPosition::Right)
+        );
+        assert_eq!(
+            pln.clone() / Decimal::new(2, 0),
+            amount(25, 1, "PLN", CommodityPosition::Right)
+        );
+
+        let max = Amount::new(Decimal::MAX, pln.commodity.clone());
+        assert_eq!(&max + &pln, Err(AmountError::Overflow));
+        assert_eq!(max.checked_mul(Decimal::TWO), Err(AmountError::Overflow));
+        assert_eq!(
+            pln.checked_div(Decimal::ZERO),
+            Err(AmountError::DivisionByZero)
+        );
+        assert_eq!(
+            pln.checked_div(Decimal::TWO),
+            Ok(amount(25, 1, "PLN", CommodityPosition::Right))
+        );
+    }
+
+    #[test]
+    fn mixed_amount() {
+        let usd = amount(120, 2, "$", CommodityPosition::Left);
+        let pln = amount(5, 0, "PLN", CommodityPosition::Right);
+
+        let mut total: MixedAmount = [usd.clone(), pln.clone(), usd.clone()]
+            .into_iter()
+            .collect();
+        assert_eq!(total.to_string(), "$2.40, 5 PLN");
+        assert_eq!(
+            total.get("$"),
+            Some(&amount(240, 2, "$", CommodityPosition::Left))
+        );
+        assert_eq!(total.single(), None);
+
+        total -= &MixedAmount::from(pln.clone());
+        assert_eq!(
+            total.single(),
+            Some(&amount(240, 2, "$", CommodityPosition::Left))
+        );
+        assert_eq!((-total.clone()).to_string(), "$-2.40");
+
+        total -= &amount(2395, 3, "$", CommodityPosition::Left);
+        assert!(!total.is_zero());
+        assert!(total.is_zero_within(Decimal::new(5, 3)));
+        assert!(!total.is_zero_within(Decimal::new(4, 3)));
+
+        let zero = MixedAmount::from(pln.clone()) + MixedAmount::from(-pln.clone());
+        assert!(zero.is_zero());
+        assert_eq!(zero.to_string(), "0");
+
+        let max = MixedAmount::from(Amount::new(Decimal::MAX, pln.commodity.clone()));
+        let mut sum = max.clone();
+        assert_eq!(sum.checked_add_amount(&pln), Err(AmountError::Overflow));
+        assert_eq!(sum, max);
+        assert_eq!(max.checked_sub(&-max.clone()), Err(AmountError::Overflow));
+        assert_eq!(
+            max.checked_sub(&max).map(|difference| difference.is_zero()),
+            Ok(true)
+        );
+    }
+}
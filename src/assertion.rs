@@ -0,0 +1,358 @@
+use crate::model::*;
+use crate::register::posting_date;
+use crate::BalanceError;
+use crate::{AccountName, MixedAmount};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+///
+/// Failed balance assertion (or a balance assignment which could not be made).
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BalanceAssertionError {
+    /// Index of the transaction in `Ledger::items`.
+    pub item: usize,
+    /// Index of the posting in the transaction.
+    pub posting: usize,
+    pub account: AccountName,
+    pub date: NaiveDate,
+    pub kind: BalanceAssertionErrorKind,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum BalanceAssertionErrorKind {
+    /// Balance of the account after the posting is not the asserted one.
+    Mismatch {
+        expected: Balance,
+        /// Balance of the account after the posting.
+        actual: MixedAmount,
+    },
+    /// Posting has no amount and its transaction cannot be balanced,
+    /// so the balance of the account is not known.
+    Inference(BalanceError),
+    /// Balance assignment `= 0` on an account holding several commodities, which
+    /// cannot be zeroed by a single amount.
+    MultiCommodityAssignment {
+        /// Balance of the account before the posting.
+        balance: MixedAmount,
+    },
+}
+
+impl fmt::Display for BalanceAssertionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            BalanceAssertionErrorKind::Mismatch {
+                ref expected,
+                ref actual,
+            } => write!(
+                f,
+                "balance assertion failed for {} on {}: expected {}, actual {}",
+                self.account, self.date, expected, actual
+            ),
+            BalanceAssertionErrorKind::Inference(ref error) => write!(
+                f,
+                "cannot infer the amount posted to {} on {}: {}",
+                self.account, self.date, error
+            ),
+            BalanceAssertionErrorKind::MultiCommodityAssignment { ref balance } => write!(
+                f,
+                "cannot assign a zero balance to {} on {}: it holds several commodities ({})",
+                self.account, self.date, balance
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BalanceAssertionError {}
+
+impl Ledger {
+    /// Goes through the postings in date order (using posting dates where given),
+    /// keeping a running balance of every account, and checks the balance assertions
+    /// (`ACCOUNT  AMOUNT = BALANCE`).
+    ///
+    /// Postings with a balance but no amount are balance assignments: their amount
+    /// is set to the difference between the balance and the running balance
+    /// (`= 0` on an empty account gives an amount of `0`, on an account holding several
+    /// commodities it is an error). Other postings without an amount are counted with their inferred amount
+    /// (see [`Transaction::infer_amounts`]), but left unchanged. They are visited after
+    /// the rest of their transaction, so the amounts of its balance assignments are known.
+    /// Transactions whose amounts cannot be inferred are reported as errors.
+    ///
+    /// Balances are compared for the commodity of the asserted amount only
+    /// (or all commodities for `= 0`), and do not include sub-accounts.
+    pub fn verify_balance_assertions(&mut self) -> Result<(), Vec<BalanceAssertionError>> {
+        let mut postings = Vec::new();
+        for (item, ledger_item) in self.items.iter().enumerate() {
+            if let LedgerItem::Transaction(transaction) = ledger_item {
+                for (index, posting) in transaction.postings.iter().enumerate() {
+                    let date = posting_date(transaction, posting, false);
+                    let elided = posting.amount.is_none() && posting.balance.is_none();
+                    postings.push((date, item, elided, index));
+                }
+            }
+        }
+        // Postings with the same date stay in file order, except for the elided amounts
+        // going after the rest of their transaction.
+        postings.sort();
+
+        let mut balances: HashMap<AccountName, MixedAmount> = HashMap::new();
+        let mut errors = Vec::new();
+        // Transactions with a failed balance assignment, their other postings without
+        // an amount cannot be inferred either.
+        let mut failed_assignments = HashSet::new();
+        for (date, item, _, index) in postings {
+            let LedgerItem::Transaction(ref mut transaction) = self.items[item] else {
+                unreachable!()
+            };
+            let balance = balances
+                .entry(transaction.postings[index].account.clone())
+                .or_default();
+
+            let posting = &transaction.postings[index];
+            let error = |kind| BalanceAssertionError {
+                item,
+                posting: index,
+                account: posting.account.clone(),
+                date,
+                kind,
+            };
+            let mismatch = |actual: &MixedAmount, expected: &Balance| {
+                error(BalanceAssertionErrorKind::Mismatch {
+                    expected: expected.clone(),
+                    actual: actual.clone(),
+                })
+            };
+
+            match (&posting.amount, &posting.balance) {
+                (Some(amount), _) => *balance += &amount.amount,
+                (None, Some(expected)) => {
+                    let assigned = match expected {
+                        Balance::Amount(expected) => {
+                            let current = quantity(balance, &expected.commodity.name);
+                            Amount::new(expected.quantity - current, expected.commodity.clone())
+                        }
+                        Balance::Zero if balance.is_zero() => Amount::new(
+                            Decimal::ZERO,
+                            Commodity {
+                                name: String::new(),
+                                position: CommodityPosition::Left,
+                            },
+                        ),
+                        Balance::Zero => match balance.single() {
+                            Some(current) => -current,
+                            None => {
+                                errors.push(error(
+                                    BalanceAssertionErrorKind::MultiCommodityAssignment {
+                                        balance: balance.clone(),
+                                    },
+                                ));
+                                failed_assignments.insert(item);
+                                continue;
+                            }
+                        },
+                    };
+                    *balance += &assigned;
+                    transaction.postings[index].amount = Some(PostingAmount {
+                        amount: assigned,
+                        lot_price: None,
+                        price: None,
+                        span: None,
+                    });
+                    continue;
+                }
+                (None, None) if failed_assignments.contains(&item) => continue,
+                (None, None) => match transaction.inferred_amount(index) {
+                    Ok(inferred) => *balance += &inferred,
+                    Err(inference) => {
+                        errors.push(error(BalanceAssertionErrorKind::Inference(inference)));
+                        continue;
+                    }
+                },
+            }
+
+            let posting = &transaction.postings[index];
+            let failed = match posting.balance {
+                Some(Balance::Zero) => !balance.is_zero(),
+                Some(Balance::Amount(ref expected)) => {
+                    quantity(balance, &expected.commodity.name) != expected.quantity
+                }
+                None => false,
+            };
+            if failed {
+                errors.push(mismatch(balance, posting.balance.as_ref().unwrap()));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+fn quantity(balance: &MixedAmount, commodity_name: &str) -> Decimal {
+    balance
+        .get(commodity_name)
+        .map_or(Decimal::ZERO, |amount| amount.quantity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assertions_in_date_order() {
+        let mut ledger: Ledger = r#"2018-10-02 Payee
+  Assets:Bank  $10 = $15
+  Income
+
+2018-10-01 Payee
+  Assets:Bank  $5 = $5
+  Assets:Cash  2 PLN
+  Income
+
+2018-10-03 Payee
+  Assets:Bank  $-15 = 0
+  Assets:Cash  $1 = $2
+  ; [2018-10-04]
+  Expenses
+"#
+        .parse()
+        .unwrap();
+
+        let errors = ledger.verify_balance_assertions().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].item, 4);
+        assert_eq!(errors[0].posting, 1);
+        assert_eq!(
+            errors[0].date,
+            NaiveDate::from_ymd_opt(2018, 10, 4).unwrap()
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "balance assertion failed for Assets:Cash on 2018-10-04: expected $2, actual $1, 2 PLN"
+        );
+    }
+
+    #[test]
+    fn balance_assignments() {
+        let mut ledger: Ledger = r#"2018-10-01 Payee
+  Assets:Bank  $5.50
+  Income
+
+2018-10-02 Payee
+  Assets:Bank  = $20.00
+  Income
+
+2018-10-03 Payee
+  Assets:Bank  = 0
+  Expenses
+"#
+        .parse()
+        .unwrap();
+
+        ledger.verify_balance_assertions().unwrap();
+        let amounts = ledger
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                LedgerItem::Transaction(transaction) => {
+                    Some(transaction.postings[0].amount.as_ref().unwrap().to_string())
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(amounts, vec!["$5.50", "$14.50", "$-20.00"]);
+
+        ledger.infer_amounts().unwrap();
+        for item in &ledger.items {
+            if let LedgerItem::Transaction(transaction) = item {
+                assert_eq!(transaction.check_balance(), Ok(()));
+            }
+        }
+    }
+
+    #[test]
+    fn assignments_before_inference() {
+        let mut ledger: Ledger = r#"2018-10-01 Payee
+  Income
+  Assets:Bank  = $20
+
+2018-10-02 Payee
+  Income  $0 = $-20
+  Assets:Bank  $0
+
+2018-10-03 Payee
+  Assets:Bank  $1
+  Income
+  Expenses
+"#
+        .parse()
+        .unwrap();
+
+        let errors = ledger.verify_balance_assertions().unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!((errors[0].item, errors[0].posting), (4, 1));
+        assert!(matches!(
+            errors[0].kind,
+            BalanceAssertionErrorKind::Inference(BalanceError::MultipleNullAmounts { .. })
+        ));
+        assert_eq!(
+            errors[0].to_string(),
+            "cannot infer the amount posted to Income on 2018-10-03: \
+             only one real posting may have no amount, found [1, 2]"
+        );
+        assert_eq!((errors[1].item, errors[1].posting), (4, 2));
+
+        let LedgerItem::Transaction(ref transaction) = ledger.items[0] else {
+            panic!("expected transaction");
+        };
+        assert_eq!(
+            transaction.postings[1].amount.as_ref().unwrap().to_string(),
+            "$20"
+        );
+    }
+
+    #[test]
+    fn zero_balance_assignments() {
+        let mut ledger: Ledger = r#"2018-10-01 A
+  Assets:Bank  = 0
+  Expenses  $5
+  Income
+
+2018-10-02 B
+  Assets:Cash  $5
+  Assets:Cash  3 EUR
+  Income
+
+2018-10-03 C
+  Assets:Cash  = 0
+  Expenses
+"#
+        .parse()
+        .unwrap();
+
+        let errors = ledger.verify_balance_assertions().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].item, errors[0].posting), (4, 0));
+        assert_eq!(
+            errors[0].to_string(),
+            "cannot assign a zero balance to Assets:Cash on 2018-10-03: \
+             it holds several commodities ($5, 3 EUR)"
+        );
+
+        let LedgerItem::Transaction(ref transaction) = ledger.items[0] else {
+            panic!("expected transaction");
+        };
+        let amount = transaction.postings[0].amount.as_ref().unwrap();
+        assert!(amount.amount.is_zero());
+        assert_eq!(
+            transaction.to_string().lines().nth(1),
+            Some("  Assets:Bank  0 = 0")
+        );
+        assert_eq!(transaction.check_balance(), Ok(()));
+    }
+}
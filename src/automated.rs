@@ -0,0 +1,286 @@
+use crate::model::*;
+use crate::{Expr, ExprError, ParseError, Query};
+use std::fmt;
+
+///
+/// Predicate of an automated transaction which is not a valid query or value
+/// expression, or which could not be evaluated, or a matched posting whose amount
+/// could not be determined (e.g. an elided amount which cannot be inferred).
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AutomatedTransactionError {
+    /// Index of the automated transaction in `Ledger::items`.
+    pub item: usize,
+    pub predicate: String,
+    pub message: String,
+}
+
+impl fmt::Display for AutomatedTransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid automated transaction predicate {:?}: {}",
+            self.predicate, self.message
+        )
+    }
+}
+
+impl std::error::Error for AutomatedTransactionError {}
+
+impl Ledger {
+    /// Returns a copy of the ledger with the postings of the automated transactions
+    /// (`= PREDICATE`) added to the transactions which follow them.
+    ///
+    /// The predicate is a report query (see [`Query`]), e.g. `/^Expenses:Food/ or
+    /// payee Shop`, or `expr` followed by a value expression (see [`Expr`]). For every
+    /// posting of a transaction matching the predicate, the postings of the automated
+    /// transaction are appended with `generated` set. Amounts without
+    /// a commodity multiply the amount of the matched posting (its inferred amount,
+    /// if elided), other amounts are used as they are.
+    ///
+    /// Only the postings written in the transaction are matched, not the generated ones.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let ledger: ledger_parser::Ledger = "= Food\n  (Budget:Food)  -1\n\n2018-10-01 Shop\n  Expenses:Food  $10\n  Assets:Bank\n"
+    ///     .parse()
+    ///     .unwrap();
+    /// let expanded = ledger.apply_automated_transactions().unwrap();
+    /// let ledger_parser::LedgerItem::Transaction(ref transaction) = expanded.items[2] else {
+    ///     unreachable!()
+    /// };
+    /// assert_eq!(transaction.postings[2].to_string(), "(Budget:Food)  $-10");
+    /// assert!(transaction.postings[2].generated);
+    /// ```
+    pub fn apply_automated_transactions(&self) -> Result<Ledger, AutomatedTransactionError> {
+        let mut rules: Vec<(usize, Predicate, &AutomatedTransaction)> = Vec::new();
+        let mut items = Vec::with_capacity(self.items.len());
+        for (item, ledger_item) in self.items.iter().enumerate() {
+            match ledger_item {
+                LedgerItem::AutomatedTransaction(automated) => {
+                    let predicate = Predicate::parse(&automated.predicate).map_err(|message| {
+                        AutomatedTransactionError {
+                            item,
+                            predicate: automated.predicate.clone(),
+                            message,
+                        }
+                    })?;
+                    rules.push((item, predicate, automated));
+                    items.push(ledger_item.clone());
+                }
+                LedgerItem::Transaction(transaction) if !rules.is_empty() => {
+                    items.push(LedgerItem::Transaction(apply(item, transaction, &rules)?));
+                }
+                _ => items.push(ledger_item.clone()),
+            }
+        }
+        Ok(Ledger { items })
+    }
+}
+
+enum Predicate {
+    Query(Query),
+    Expr(Expr),
+}
+
+impl Predicate {
+    fn parse(predicate: &str) -> Result<Self, String> {
+        if let Some(expr) = predicate.strip_prefix("expr ") {
+            return expr
+                .parse()
+                .map(Predicate::Expr)
+                .map_err(|error: ExprError| error.to_string());
+        }
+        predicate
+            .parse()
+            .map(Predicate::Query)
+            .map_err(|error: ParseError| error.to_string())
+    }
+
+    fn matches(&self, transaction: &Transaction, index: usize) -> Result<bool, ExprError> {
+        match self {
+            Predicate::Query(query) => Ok(query.matches(transaction, index)),
+            Predicate::Expr(expr) => expr.matches(transaction, Some(index)),
+        }
+    }
+}
+
+fn apply(
+    transaction_item: usize,
+    transaction: &Transaction,
+    rules: &[(usize, Predicate, &AutomatedTransaction)],
+) -> Result<Transaction, AutomatedTransactionError> {
+    let mut expanded = transaction.clone();
+    for index in 0..transaction.postings.len() {
+        for (item, predicate, automated) in rules {
+            let error = |message: String| AutomatedTransactionError {
+                item: *item,
+                predicate: automated.predicate.clone(),
+                message,
+            };
+            let matches = predicate
+                .matches(transaction, index)
+                .map_err(|e| error(e.to_string()))?;
+            if !matches {
+                continue;
+            }
+
+            let matched = transaction
+                .posting_amount(transaction_item, index)
+                .map_err(|e| error(e.to_string()))?;
+            for automated_posting in &automated.postings {
+                let amounts = match automated_posting.amount {
+                    AutomatedAmount::Multiplier(multiplier) => matched
+                        .amounts()
+                        .map(|amount| amount.checked_mul(multiplier))
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|e| {
+                            error(format!(
+                                "item {} posting {}: {}",
+                                transaction_item, index, e
+                            ))
+                        })?,
+                    AutomatedAmount::Amount(ref amount) => vec![amount.clone()],
+                };
+                for amount in amounts {
+                    expanded.postings.push(Posting {
+                        account: automated_posting.account.clone(),
+                        reality: automated_posting.reality,
+                        amount: Some(PostingAmount {
+                            amount,
+                            lot_price: None,
+                            price: None,
+                            span: None,
+                        }),
+                        balance: None,
+                        status: None,
+                        comment: automated_posting.comment.clone(),
+                        metadata: automated_posting.metadata.clone(),
+                        generated: true,
+                        span: None,
+                    });
+                }
+            }
+        }
+    }
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"2018-10-01 Before
+  Expenses:Food  $5
+  Assets:Bank
+
+= /^expenses:food/ or Dining
+  ; budget
+  (Budget:Food)  -1
+  Liabilities:Tax  0.1  ; tax
+  Assets:Bank  $-1
+
+2018-10-02 Shop
+  Expenses:Food  $10
+  Expenses:Dining  20 EUR
+  Assets:Bank
+"#;
+
+    #[test]
+    fn parse_and_serialize() {
+        let ledger: Ledger = SOURCE.parse().unwrap();
+        let LedgerItem::AutomatedTransaction(ref automated) = ledger.items[2] else {
+            panic!("expected automated transaction");
+        };
+        assert_eq!(automated.predicate, "/^expenses:food/ or Dining");
+        assert_eq!(automated.comment.as_deref(), Some("budget"));
+        assert_eq!(automated.postings.len(), 3);
+        assert_eq!(automated.postings[0].reality, Reality::UnbalancedVirtual);
+        assert_eq!(
+            automated.postings[1].amount,
+            AutomatedAmount::Multiplier(rust_decimal::Decimal::new(1, 1))
+        );
+        assert_eq!(automated.postings[1].comment.as_deref(), Some("tax"));
+        assert!(matches!(
+            automated.postings[2].amount,
+            AutomatedAmount::Amount(ref amount) if amount.to_string() == "$-1"
+        ));
+        assert_eq!(automated.span.as_ref().unwrap().lines, 5..10);
+
+        let reparsed: Ledger = ledger.to_string().parse().unwrap();
+        assert_eq!(reparsed, ledger);
+    }
+
+    #[test]
+    fn generated_postings() {
+        let ledger: Ledger = SOURCE.parse().unwrap();
+        let expanded = ledger.apply_automated_transactions().unwrap();
+        assert_eq!(expanded.items[0], ledger.items[0]);
+
+        let LedgerItem::Transaction(ref transaction) = expanded.items[4] else {
+            panic!("expected transaction");
+        };
+        let generated = transaction
+            .postings
+            .iter()
+            .filter(|posting| posting.generated)
+            .map(|posting| format!("{} {}", posting.account, posting.amount.as_ref().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            generated,
+            vec![
+                "Budget:Food $-10",
+                "Liabilities:Tax $1.0",
+                "Assets:Bank $-1",
+                "Budget:Food -20 EUR",
+                "Liabilities:Tax 2.0 EUR",
+                "Assets:Bank $-1",
+            ]
+        );
+        assert_eq!(transaction.postings.len(), 9);
+        assert!(!transaction.postings[2].generated);
+        assert_eq!(transaction.postings[4].comment.as_deref(), Some("tax"));
+
+        let ledger: Ledger = "= (\n  A  1\n".parse().unwrap();
+        let error = ledger.apply_automated_transactions().unwrap_err();
+        assert_eq!((error.item, error.predicate.as_str()), (0, "("));
+    }
+
+    #[test]
+    fn value_expression_predicates() {
+        let source = SOURCE.replace(
+            "/^expenses:food/ or Dining",
+            "expr account =~ /^expenses/ and commodity == \"$\" and amount > {$5}",
+        );
+        let ledger: Ledger = source.parse().unwrap();
+        let expanded = ledger.apply_automated_transactions().unwrap();
+        let LedgerItem::Transaction(ref transaction) = expanded.items[4] else {
+            panic!("expected transaction");
+        };
+        // Only `Expenses:Food  $10` matches.
+        assert_eq!(transaction.postings.len(), 6);
+        assert_eq!(
+            transaction.postings[3].amount.as_ref().unwrap().to_string(),
+            "$-10"
+        );
+
+        let ledger: Ledger = "= expr price > 1\n  A  1\n".parse().unwrap();
+        let error = ledger.apply_automated_transactions().unwrap_err();
+        assert_eq!(error.message, "unknown variable 'price'");
+    }
+
+    #[test]
+    fn uninferable_matched_amounts() {
+        let ledger: Ledger = "= Food\n  (Budget:Food)  -1\n\n2018-10-01 Shop\n  Expenses:Food\n  Assets:Bank\n  Income\n"
+            .parse()
+            .unwrap();
+        let error = ledger.apply_automated_transactions().unwrap_err();
+        assert_eq!(error.item, 0);
+        assert_eq!(
+            error.message,
+            "item 2 posting 0: cannot infer the amount: \
+             only one real posting may have no amount, found [0, 1, 2]"
+        );
+    }
+}
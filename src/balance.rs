@@ -0,0 +1,549 @@
+use crate::model::*;
+use crate::{AmountError, MixedAmount, PeriodicTransactionError};
+use rust_decimal::RoundingStrategy;
+use std::collections::HashMap;
+use std::fmt;
+
+///
+/// Reason why a transaction does not balance.
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum BalanceError {
+    /// Postings of the given reality do not sum to zero.
+    Unbalanced(Vec<Imbalance>),
+    /// More than one posting of the given reality has no amount,
+    /// so the missing amounts cannot be determined.
+    MultipleNullAmounts {
+        reality: Reality,
+        /// Indices of the postings without an amount.
+        postings: Vec<usize>,
+    },
+    /// Cost of a posting or the sum of the postings does not fit in a `Decimal`.
+    Amount(AmountError),
+}
+
+///
+/// Non-zero sum of the postings of one reality (real or balanced virtual).
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Imbalance {
+    pub reality: Reality,
+    /// Sum of the postings after rounding to the commodity precisions.
+    pub amount: MixedAmount,
+    /// Indices of the postings included in the sum.
+    pub postings: Vec<usize>,
+}
+
+impl fmt::Display for BalanceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BalanceError::Unbalanced(imbalances) => {
+                write!(f, "transaction does not balance")?;
+                for imbalance in imbalances {
+                    write!(
+                        f,
+                        "; {} postings {:?} sum to {}",
+                        reality_name(imbalance.reality),
+                        imbalance.postings,
+                        imbalance.amount
+                    )?;
+                }
+                Ok(())
+            }
+            BalanceError::MultipleNullAmounts { reality, postings } => write!(
+                f,
+                "only one {} posting may have no amount, found {:?}",
+                reality_name(*reality),
+                postings
+            ),
+            BalanceError::Amount(error) => write!(f, "cannot sum the postings: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for BalanceError {}
+
+///
+/// Reason why a report over the postings of a ledger could not be made.
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ReportError {
+    /// Posting has no amount and its transaction cannot be balanced,
+    /// so the amount cannot be inferred.
+    Inference {
+        /// Index of the transaction in `Ledger::items`.
+        item: usize,
+        /// Index of the posting in the transaction.
+        posting: usize,
+        error: BalanceError,
+    },
+    /// Sums of the amounts overflow.
+    Amount(AmountError),
+    /// Period expression of a periodic transaction is not supported.
+    Period(PeriodicTransactionError),
+}
+
+impl fmt::Display for ReportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReportError::Inference {
+                item,
+                posting,
+                error,
+            } => write!(
+                f,
+                "item {} posting {}: cannot infer the amount: {}",
+                item, posting, error
+            ),
+            ReportError::Amount(error) => error.fmt(f),
+            ReportError::Period(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ReportError {}
+
+impl From<AmountError> for ReportError {
+    fn from(error: AmountError) -> Self {
+        ReportError::Amount(error)
+    }
+}
+
+impl From<PeriodicTransactionError> for ReportError {
+    fn from(error: PeriodicTransactionError) -> Self {
+        ReportError::Period(error)
+    }
+}
+
+fn reality_name(reality: Reality) -> &'static str {
+    match reality {
+        Reality::Real => "real",
+        Reality::BalancedVirtual => "balanced virtual",
+        Reality::UnbalancedVirtual => "unbalanced virtual",
+    }
+}
+
+impl Transaction {
+    /// Checks that the postings sum to zero, like ledger does.
+    ///
+    /// Real and `[balanced virtual]` postings must balance separately,
+    /// `(unbalanced virtual)` postings are ignored. Amounts with a price (or a lot price)
+    /// are counted at their cost. Sums are rounded to the precision of each commodity,
+    /// which is the largest number of decimal places of its posting amounts in this
+    /// transaction. Use [`Ledger::check_balances`] to take the precisions from the
+    /// whole ledger, like ledger does.
+    /// A single posting without an amount balances the rest of its group.
+    pub fn check_balance(&self) -> Result<(), BalanceError> {
+        self.check_balance_with(&commodity_precisions([self]))
+    }
+
+    /// Same as [`check_balance`](Transaction::check_balance), with the sums rounded to
+    /// the given number of decimal places of each commodity.
+    pub fn check_balance_with(
+        &self,
+        precisions: &HashMap<String, u32>,
+    ) -> Result<(), BalanceError> {
+        let mut imbalances = Vec::new();
+
+        for reality in [Reality::Real, Reality::BalancedVirtual] {
+            let group = self.group(reality)?;
+            if group.null_amount.is_some() {
+                continue;
+            }
+            let sum = round(&group.sum, precisions);
+            if !sum.is_zero() {
+                imbalances.push(Imbalance {
+                    reality,
+                    amount: sum,
+                    postings: group.postings,
+                });
+            }
+        }
+
+        if imbalances.is_empty() {
+            Ok(())
+        } else {
+            Err(BalanceError::Unbalanced(imbalances))
+        }
+    }
+
+    /// Fills in the amount of the posting left without one, so that the transaction
+    /// balances (rounded to the commodity precision). If the rest of the postings
+    /// sum to several commodities, the posting is replaced by one posting per commodity.
+    /// Its balance assertion, comment and tags are kept on the first of them only,
+    /// its posting dates on all of them.
+    ///
+    /// Real and `[balanced virtual]` postings are completed separately.
+    /// Returns an error if a group has more than one posting without an amount.
+    pub fn infer_amounts(&mut self) -> Result<(), BalanceError> {
+        let precisions = commodity_precisions([&*self]);
+        self.infer_amounts_with(&precisions)
+    }
+
+    /// Same as [`infer_amounts`](Transaction::infer_amounts), with the sums rounded to
+    /// the given number of decimal places of each commodity.
+    pub fn infer_amounts_with(
+        &mut self,
+        precisions: &HashMap<String, u32>,
+    ) -> Result<(), BalanceError> {
+        let mut inferred = Vec::new();
+        for reality in [Reality::Real, Reality::BalancedVirtual] {
+            let group = self.group(reality)?;
+            if let Some(index) = group.null_amount {
+                inferred.push((index, -round(&group.sum, precisions)));
+            }
+        }
+        // Replace from the back, so the indices of the remaining postings do not change.
+        inferred.sort_by_key(|(index, _)| std::cmp::Reverse(*index));
+
+        for (index, amounts) in inferred {
+            let posting = self.postings[index].clone();
+            let postings = amounts
+                .amounts()
+                .enumerate()
+                .map(|(split, amount)| {
+                    let mut split_posting = posting.clone();
+                    split_posting.amount = Some(PostingAmount {
+                        amount: amount.clone(),
+                        lot_price: None,
+                        price: None,
+                        span: None,
+                    });
+                    if split > 0 {
+                        split_posting.balance = None;
+                        split_posting.comment = None;
+                        split_posting.metadata.tags.clear();
+                    }
+                    split_posting
+                })
+                .collect::<Vec<_>>();
+            // Nothing to balance, the posting is kept without an amount.
+            if !postings.is_empty() {
+                self.postings.splice(index..index + 1, postings);
+            }
+        }
+        Ok(())
+    }
+
+    /// Amount of the posting at `index` of this transaction (at `item` in
+    /// `Ledger::items`), as counted by the reports: its own amount, or the one
+    /// `infer_amounts` would give it.
+    pub(crate) fn posting_amount(
+        &self,
+        item: usize,
+        index: usize,
+    ) -> Result<MixedAmount, ReportError> {
+        match self.postings[index].amount {
+            Some(ref amount) => Ok(MixedAmount::from(amount.amount.clone())),
+            None => self
+                .inferred_amount(index)
+                .map_err(|error| ReportError::Inference {
+                    item,
+                    posting: index,
+                    error,
+                }),
+        }
+    }
+
+    /// Amount that `infer_amounts` would give to the posting at `index`, which has none.
+    pub(crate) fn inferred_amount(&self, index: usize) -> Result<MixedAmount, BalanceError> {
+        let group = self.group(self.postings[index].reality)?;
+        Ok(-round(&group.sum, &commodity_precisions([self])))
+    }
+
+    /// Sums the postings of one reality.
+    fn group(&self, reality: Reality) -> Result<PostingGroup, BalanceError> {
+        let mut group = PostingGroup {
+            sum: MixedAmount::new(),
+            postings: Vec::new(),
+            null_amount: None,
+        };
+        let mut null_amounts = Vec::new();
+        for (index, posting) in self.postings.iter().enumerate() {
+            if posting.reality != reality {
+                continue;
+            }
+            match posting.amount {
+                Some(ref amount) => {
+                    let cost = amount.checked_cost().map_err(BalanceError::Amount)?;
+                    group
+                        .sum
+                        .checked_add_amount(&cost)
+                        .map_err(BalanceError::Amount)?;
+                    group.postings.push(index);
+                }
+                None => null_amounts.push(index),
+            }
+        }
+
+        if null_amounts.len() > 1 {
+            return Err(BalanceError::MultipleNullAmounts {
+                reality,
+                postings: null_amounts,
+            });
+        }
+        group.null_amount = null_amounts.pop();
+        Ok(group)
+    }
+}
+
+/// Number of decimal places of each commodity, taken from the posting amounts.
+/// Commodities used only in prices get the precision of the prices.
+fn commodity_precisions<'a>(
+    transactions: impl IntoIterator<Item = &'a Transaction>,
+) -> HashMap<String, u32> {
+    fn update(precisions: &mut HashMap<String, u32>, amount: &Amount) {
+        let precision = precisions.entry(amount.commodity.name.clone()).or_default();
+        *precision = (*precision).max(amount.quantity.scale());
+    }
+
+    let amounts = transactions
+        .into_iter()
+        .flat_map(|transaction| &transaction.postings)
+        .filter_map(|posting| posting.amount.as_ref());
+    let mut precisions = HashMap::new();
+    let mut price_precisions = HashMap::new();
+    for amount in amounts {
+        update(&mut precisions, &amount.amount);
+        for price in amount.price.iter().chain(amount.lot_price.iter()) {
+            match price {
+                Price::Unit(price) | Price::Total(price) => update(&mut price_precisions, price),
+            }
+        }
+    }
+    for (name, precision) in price_precisions {
+        precisions.entry(name).or_insert(precision);
+    }
+    precisions
+}
+
+struct PostingGroup {
+    sum: MixedAmount,
+    /// Indices of the postings with an amount.
+    postings: Vec<usize>,
+    /// Index of the posting without an amount.
+    null_amount: Option<usize>,
+}
+
+fn round(sum: &MixedAmount, precisions: &HashMap<String, u32>) -> MixedAmount {
+    sum.amounts()
+        .map(|amount| {
+            let mut amount = amount.clone();
+            if let Some(&precision) = precisions.get(amount.commodity.name.as_str()) {
+                amount.quantity = amount
+                    .quantity
+                    .round_dp_with_strategy(precision, RoundingStrategy::MidpointAwayFromZero);
+            }
+            amount
+        })
+        .collect()
+}
+
+impl Ledger {
+    /// Number of decimal places of each commodity: the largest of all the posting
+    /// amounts in the ledger, like ledger's display precision.
+    /// Commodities used only in prices get the precision of the prices.
+    pub fn commodity_precisions(&self) -> HashMap<String, u32> {
+        commodity_precisions(self.transactions())
+    }
+
+    /// Checks that all transactions balance (see [`Transaction::check_balance`]),
+    /// with the sums rounded to the precisions of the whole ledger.
+    /// Item indices of the unbalanced transactions are returned with the errors.
+    pub fn check_balances(&self) -> Result<(), Vec<(usize, BalanceError)>> {
+        let precisions = self.commodity_precisions();
+        let errors = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(index, item)| match item {
+                LedgerItem::Transaction(transaction) => transaction
+                    .check_balance_with(&precisions)
+                    .err()
+                    .map(|error| (index, error)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Fills in the elided posting amounts of all transactions
+    /// (see [`Transaction::infer_amounts`]), with the sums rounded to the precisions
+    /// of the whole ledger.
+    ///
+    /// Transactions which cannot be completed are left unchanged,
+    /// their item indices are returned with the errors.
+    pub fn infer_amounts(&mut self) -> Result<(), Vec<(usize, BalanceError)>> {
+        let precisions = self.commodity_precisions();
+        let errors = self
+            .items
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, item)| match item {
+                LedgerItem::Transaction(transaction) => transaction
+                    .infer_amounts_with(&precisions)
+                    .err()
+                    .map(|error| (index, error)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transaction(postings: &str) -> Transaction {
+        let ledger: Ledger = format!("2018-10-01 Payee\n{}", postings).parse().unwrap();
+        match ledger.items.into_iter().next() {
+            Some(LedgerItem::Transaction(transaction)) => transaction,
+            _ => panic!("expected transaction"),
+        }
+    }
+
+    #[test]
+    fn balanced_transactions() {
+        for postings in [
+            "  A  $1.20\n  B  $-1.20\n",
+            "  A  $1.20\n  B\n",
+            "  A  10 AAPL @ $1.333\n  B  $-13.33\n",
+            "  A  -10 AAPL @@ $13.33\n  B  $13.33\n",
+            "  A  10 AAPL {$1.50}\n  B  $-15.00\n",
+            "  A  10 AAPL {$1.50} @ $2\n  B  $-20.00\n",
+            "  A  $1\n  B  $-1\n  (C)  $5\n  [D]  $2\n  [E]  $-2\n",
+            "  A  $1\n  B  2 PLN\n  C\n",
+        ] {
+            assert_eq!(
+                transaction(postings).check_balance(),
+                Ok(()),
+                "{}",
+                postings
+            );
+        }
+    }
+
+    #[test]
+    fn unbalanced_transactions() {
+        let error = transaction("  A  $1.20\n  B  $-1.10\n  [C]  $2\n  [D]  $-1\n")
+            .check_balance()
+            .unwrap_err();
+        let BalanceError::Unbalanced(ref imbalances) = error else {
+            panic!("expected imbalance");
+        };
+        assert_eq!(imbalances.len(), 2);
+        assert_eq!(imbalances[0].reality, Reality::Real);
+        assert_eq!(imbalances[0].amount.to_string(), "$0.10");
+        assert_eq!(imbalances[0].postings, vec![0, 1]);
+        assert_eq!(imbalances[1].amount.to_string(), "$1");
+        assert_eq!(
+            error.to_string(),
+            "transaction does not balance; real postings [0, 1] sum to $0.10; \
+             balanced virtual postings [2, 3] sum to $1"
+        );
+
+        assert_eq!(
+            transaction("  A  $1\n  B\n  C\n").check_balance(),
+            Err(BalanceError::MultipleNullAmounts {
+                reality: Reality::Real,
+                postings: vec![1, 2]
+            })
+        );
+        assert!(transaction("  A  10 AAPL @ $1.335\n  B  $-13.30\n")
+            .check_balance()
+            .is_err());
+        assert_eq!(
+            transaction("  A  2 AAPL @ $79228162514264337593543950335\n  B\n").check_balance(),
+            Err(BalanceError::Amount(AmountError::Overflow))
+        );
+    }
+
+    #[test]
+    fn infer_elided_amounts() {
+        let mut transaction = self::transaction("  A  10 AAPL @ $1.25\n  B\n  [C]  2 PLN\n  [D]\n");
+        transaction.infer_amounts().unwrap();
+        let amounts = transaction
+            .postings
+            .iter()
+            .map(|posting| posting.amount.as_ref().unwrap().amount.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(amounts, vec!["10 AAPL", "$-12.50", "2 PLN", "-2 PLN"]);
+        assert_eq!(transaction.check_balance(), Ok(()));
+
+        let mut transaction = self::transaction("  A  $1\n  B  2 PLN\n  C\n  ; comment\n");
+        transaction.infer_amounts().unwrap();
+        assert_eq!(transaction.postings.len(), 4);
+        for (posting, amount) in transaction.postings[2..].iter().zip(["$-1", "-2 PLN"]) {
+            assert_eq!(posting.account, "C");
+            assert_eq!(posting.amount.as_ref().unwrap().amount.to_string(), amount);
+        }
+        assert_eq!(transaction.postings[2].comment.as_deref(), Some("comment"));
+        assert_eq!(transaction.postings[3].comment, None);
+
+        // A single balance assertion (and the tags) stay on the first split posting.
+        let mut transaction =
+            self::transaction("  A  $1\n  B  2 PLN\n  C  = $-1  ; :tag:\n  ; [=2018-10-05]\n");
+        transaction.infer_amounts().unwrap();
+        let (first, second) = (&transaction.postings[2], &transaction.postings[3]);
+        assert_eq!(first.balance.as_ref().unwrap().to_string(), "$-1");
+        assert_eq!(first.metadata.tags.len(), 1);
+        assert_eq!(second.balance, None);
+        assert!(second.metadata.tags.is_empty());
+        assert_eq!(
+            second.metadata.effective_date,
+            first.metadata.effective_date
+        );
+        assert!(second.metadata.effective_date.is_some());
+
+        let mut ledger: Ledger =
+            "2018-10-01 Payee\n  A  $1\n  B\n  C\n\n2018-10-02 Payee\n  A  $1\n  B\n"
+                .parse()
+                .unwrap();
+        let errors = ledger.infer_amounts().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, 0);
+        assert!(matches!(
+            errors[0].1,
+            BalanceError::MultipleNullAmounts { .. }
+        ));
+    }
+
+    #[test]
+    fn ledger_wide_precisions() {
+        let source = "2018-10-01 Payee\n  A  $1.50\n  B\n\n\
+            2018-10-02 Payee\n  A  3 AAPL @ $0.335\n  B  $-1\n\n\
+            2018-10-03 Payee\n  A  3 AAPL @ $0.335\n  B\n";
+        let mut ledger: Ledger = source.parse().unwrap();
+        assert_eq!(ledger.commodity_precisions()["$"], 2);
+        assert_eq!(ledger.commodity_precisions()["AAPL"], 0);
+
+        // The second transaction balances on its own dollar precision only.
+        let LedgerItem::Transaction(ref transaction) = ledger.items[2] else {
+            panic!("expected transaction");
+        };
+        assert_eq!(transaction.check_balance(), Ok(()));
+        let errors = ledger.check_balances().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, 2);
+        let BalanceError::Unbalanced(ref imbalances) = errors[0].1 else {
+            panic!("expected imbalance");
+        };
+        assert_eq!(imbalances[0].amount.to_string(), "$0.01");
+
+        ledger.infer_amounts().unwrap();
+        let LedgerItem::Transaction(ref transaction) = ledger.items[4] else {
+            panic!("expected transaction");
+        };
+        let amount = transaction.postings[1].amount.as_ref().unwrap();
+        assert_eq!(amount.amount.to_string(), "$-1.01");
+    }
+}
@@ -0,0 +1,550 @@
+//! Borrowed variant of the model.
+//!
+//! Text fields point into the parsed input instead of being copied. Fields that may need
+//! to be built from several pieces of the input (multi-line comments, quoted commodity
+//! names with escapes) are `Cow`s. Use `into_owned()` to convert to the owned model.
+
+use crate::model::{self, CommodityPosition, Reality, Span, TransactionStatus};
+use crate::AccountName;
+use chrono::{NaiveDate, NaiveDateTime};
+use ordered_float::NotNan;
+use rust_decimal::Decimal;
+use std::borrow::Cow;
+
+///
+/// Main document. Contains transactions and/or commodity prices.
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Ledger<'a> {
+    pub items: Vec<LedgerItem<'a>>,
+}
+
+impl Ledger<'_> {
+    pub fn into_owned(self) -> model::Ledger {
+        model::Ledger {
+            items: self.items.into_iter().map(LedgerItem::into_owned).collect(),
+        }
+    }
+}
+
+#[non_exhaustive]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum LedgerItem<'a> {
+    EmptyLine,
+    LineComment(LineComment<'a>),
+    Transaction(Transaction<'a>),
+    CommodityPrice(CommodityPrice<'a>),
+    Include(Include<'a>),
+    AutomatedTransaction(AutomatedTransaction<'a>),
+    PeriodicTransaction(PeriodicTransaction<'a>),
+}
+
+impl LedgerItem<'_> {
+    pub fn into_owned(self) -> model::LedgerItem {
+        match self {
+            LedgerItem::EmptyLine => model::LedgerItem::EmptyLine,
+            LedgerItem::LineComment(comment) => {
+                model::LedgerItem::LineComment(comment.into_owned())
+            }
+            LedgerItem::Transaction(transaction) => {
+                model::LedgerItem::Transaction(transaction.into_owned())
+            }
+            LedgerItem::CommodityPrice(commodity_price) => {
+                model::LedgerItem::CommodityPrice(commodity_price.into_owned())
+            }
+            LedgerItem::Include(include) => model::LedgerItem::Include(include.into_owned()),
+            LedgerItem::AutomatedTransaction(automated) => {
+                model::LedgerItem::AutomatedTransaction(automated.into_owned())
+            }
+            LedgerItem::PeriodicTransaction(periodic) => {
+                model::LedgerItem::PeriodicTransaction(periodic.into_owned())
+            }
+        }
+    }
+}
+
+///
+/// Comment on its own line (`; text`), outside of transactions.
+///
+#[derive(Debug, Eq, Clone)]
+pub struct LineComment<'a> {
+    pub comment: &'a str,
+    pub span: Option<Span>,
+}
+
+impl PartialEq for LineComment<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.comment == other.comment
+    }
+}
+
+impl LineComment<'_> {
+    pub fn into_owned(self) -> model::LineComment {
+        model::LineComment {
+            comment: self.comment.to_owned(),
+            span: self.span,
+        }
+    }
+}
+
+///
+/// `include` directive.
+///
+#[derive(Debug, Eq, Clone)]
+pub struct Include<'a> {
+    pub file: &'a str,
+    pub span: Option<Span>,
+}
+
+impl PartialEq for Include<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.file == other.file
+    }
+}
+
+impl Include<'_> {
+    pub fn into_owned(self) -> model::Include {
+        model::Include {
+            file: self.file.to_owned(),
+            span: self.span,
+        }
+    }
+}
+
+///
+/// Transaction.
+///
+#[derive(Debug, Eq, Clone)]
+pub struct Transaction<'a> {
+    pub status: Option<TransactionStatus>,
+    pub code: Option<&'a str>,
+    pub description: Option<&'a str>,
+    pub comment: Option<Cow<'a, str>>,
+    pub date: NaiveDate,
+    pub effective_date: Option<NaiveDate>,
+    pub posting_metadata: PostingMetadata<'a>,
+    pub postings: Vec<Posting<'a>>,
+    pub forecast: bool,
+    pub span: Option<Span>,
+}
+
+impl PartialEq for Transaction<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.status == other.status
+            && self.code == other.code
+            && self.description == other.description
+            && self.comment == other.comment
+            && self.date == other.date
+            && self.effective_date == other.effective_date
+            && self.posting_metadata == other.posting_metadata
+            && self.postings == other.postings
+            && self.forecast == other.forecast
+    }
+}
+
+impl Transaction<'_> {
+    pub fn into_owned(self) -> model::Transaction {
+        model::Transaction {
+            status: self.status,
+            code: self.code.map(str::to_owned),
+            description: self.description.map(str::to_owned),
+            comment: self.comment.map(Cow::into_owned),
+            date: self.date,
+            effective_date: self.effective_date,
+            posting_metadata: self.posting_metadata.into_owned(),
+            postings: self.postings.into_iter().map(Posting::into_owned).collect(),
+            forecast: self.forecast,
+            span: self.span,
+        }
+    }
+}
+
+#[derive(Debug, Eq, Clone)]
+pub struct Posting<'a> {
+    /// Account name, already validated by the parser.
+    pub account: &'a str,
+    pub reality: Reality,
+    pub amount: Option<PostingAmount<'a>>,
+    pub balance: Option<Balance<'a>>,
+    pub status: Option<TransactionStatus>,
+    pub comment: Option<Cow<'a, str>>,
+    pub metadata: PostingMetadata<'a>,
+    pub generated: bool,
+    pub span: Option<Span>,
+}
+
+impl PartialEq for Posting<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.account == other.account
+            && self.reality == other.reality
+            && self.amount == other.amount
+            && self.balance == other.balance
+            && self.status == other.status
+            && self.comment == other.comment
+            && self.metadata == other.metadata
+            && self.generated == other.generated
+    }
+}
+
+impl Posting<'_> {
+    pub fn into_owned(self) -> model::Posting {
+        model::Posting {
+            account: AccountName::from_validated(self.account.to_owned()),
+            reality: self.reality,
+            amount: self.amount.map(PostingAmount::into_owned),
+            balance: self.balance.map(Balance::into_owned),
+            status: self.status,
+            comment: self.comment.map(Cow::into_owned),
+            metadata: self.metadata.into_owned(),
+            generated: self.generated,
+            span: self.span,
+        }
+    }
+}
+
+#[derive(Debug, Eq, Clone)]
+pub struct PostingAmount<'a> {
+    pub amount: Amount<'a>,
+    pub lot_price: Option<Price<'a>>,
+    pub price: Option<Price<'a>>,
+    pub span: Option<Span>,
+}
+
+impl PartialEq for PostingAmount<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.amount == other.amount
+            && self.lot_price == other.lot_price
+            && self.price == other.price
+    }
+}
+
+impl PostingAmount<'_> {
+    pub fn into_owned(self) -> model::PostingAmount {
+        model::PostingAmount {
+            amount: self.amount.into_owned(),
+            lot_price: self.lot_price.map(Price::into_owned),
+            price: self.price.map(Price::into_owned),
+            span: self.span,
+        }
+    }
+}
+
+#[derive(Debug, Eq, Clone)]
+pub struct Amount<'a> {
+    pub quantity: Decimal,
+    pub commodity: Commodity<'a>,
+    pub span: Option<Span>,
+}
+
+impl PartialEq for Amount<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.quantity == other.quantity && self.commodity == other.commodity
+    }
+}
+
+impl Amount<'_> {
+    pub fn into_owned(self) -> model::Amount {
+        model::Amount {
+            quantity: self.quantity,
+            commodity: self.commodity.into_owned(),
+            span: self.span,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Commodity<'a> {
+    pub name: Cow<'a, str>,
+    pub position: CommodityPosition,
+}
+
+impl Commodity<'_> {
+    pub fn into_owned(self) -> model::Commodity {
+        model::Commodity {
+            name: self.name.into_owned(),
+            position: self.position,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Price<'a> {
+    Unit(Amount<'a>),
+    Total(Amount<'a>),
+}
+
+impl Price<'_> {
+    pub fn into_owned(self) -> model::Price {
+        match self {
+            Price::Unit(amount) => model::Price::Unit(amount.into_owned()),
+            Price::Total(amount) => model::Price::Total(amount.into_owned()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Balance<'a> {
+    Zero,
+    Amount(Amount<'a>),
+}
+
+impl Balance<'_> {
+    pub fn into_owned(self) -> model::Balance {
+        match self {
+            Balance::Zero => model::Balance::Zero,
+            Balance::Amount(amount) => model::Balance::Amount(amount.into_owned()),
+        }
+    }
+}
+
+///
+/// Commodity price.
+///
+#[derive(Debug, Eq, Clone)]
+pub struct CommodityPrice<'a> {
+    pub datetime: NaiveDateTime,
+    pub commodity_name: Cow<'a, str>,
+    pub amount: Amount<'a>,
+    pub span: Option<Span>,
+}
+
+impl PartialEq for CommodityPrice<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.datetime == other.datetime
+            && self.commodity_name == other.commodity_name
+            && self.amount == other.amount
+    }
+}
+
+impl CommodityPrice<'_> {
+    pub fn into_owned(self) -> model::CommodityPrice {
+        model::CommodityPrice {
+            datetime: self.datetime,
+            commodity_name: self.commodity_name.into_owned(),
+            amount: self.amount.into_owned(),
+            span: self.span,
+        }
+    }
+}
+
+///
+/// Automated transaction.
+///
+#[derive(Debug, Eq, Clone)]
+pub struct AutomatedTransaction<'a> {
+    pub predicate: &'a str,
+    pub comment: Option<Cow<'a, str>>,
+    pub posting_metadata: PostingMetadata<'a>,
+    pub postings: Vec<AutomatedPosting<'a>>,
+    pub span: Option<Span>,
+}
+
+impl PartialEq for AutomatedTransaction<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.predicate == other.predicate
+            && self.comment == other.comment
+            && self.posting_metadata == other.posting_metadata
+            && self.postings == other.postings
+    }
+}
+
+impl AutomatedTransaction<'_> {
+    pub fn into_owned(self) -> model::AutomatedTransaction {
+        model::AutomatedTransaction {
+            predicate: self.predicate.to_owned(),
+            comment: self.comment.map(Cow::into_owned),
+            posting_metadata: self.posting_metadata.into_owned(),
+            postings: self
+                .postings
+                .into_iter()
+                .map(AutomatedPosting::into_owned)
+                .collect(),
+            span: self.span,
+        }
+    }
+}
+
+#[derive(Debug, Eq, Clone)]
+pub struct AutomatedPosting<'a> {
+    /// Account name, already validated by the parser.
+    pub account: &'a str,
+    pub reality: Reality,
+    pub amount: AutomatedAmount<'a>,
+    pub comment: Option<Cow<'a, str>>,
+    pub metadata: PostingMetadata<'a>,
+    pub span: Option<Span>,
+}
+
+impl PartialEq for AutomatedPosting<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.account == other.account
+            && self.reality == other.reality
+            && self.amount == other.amount
+            && self.comment == other.comment
+            && self.metadata == other.metadata
+    }
+}
+
+impl AutomatedPosting<'_> {
+    pub fn into_owned(self) -> model::AutomatedPosting {
+        model::AutomatedPosting {
+            account: AccountName::from_validated(self.account.to_owned()),
+            reality: self.reality,
+            amount: self.amount.into_owned(),
+            comment: self.comment.map(Cow::into_owned),
+            metadata: self.metadata.into_owned(),
+            span: self.span,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum AutomatedAmount<'a> {
+    Multiplier(Decimal),
+    Amount(Amount<'a>),
+}
+
+impl AutomatedAmount<'_> {
+    pub fn into_owned(self) -> model::AutomatedAmount {
+        match self {
+            AutomatedAmount::Multiplier(multiplier) => {
+                model::AutomatedAmount::Multiplier(multiplier)
+            }
+            AutomatedAmount::Amount(amount) => model::AutomatedAmount::Amount(amount.into_owned()),
+        }
+    }
+}
+
+///
+/// Periodic transaction.
+///
+#[derive(Debug, Eq, Clone)]
+pub struct PeriodicTransaction<'a> {
+    pub period: &'a str,
+    pub comment: Option<Cow<'a, str>>,
+    pub posting_metadata: PostingMetadata<'a>,
+    pub postings: Vec<Posting<'a>>,
+    pub span: Option<Span>,
+}
+
+impl PartialEq for PeriodicTransaction<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.period == other.period
+            && self.comment == other.comment
+            && self.posting_metadata == other.posting_metadata
+            && self.postings == other.postings
+    }
+}
+
+impl PeriodicTransaction<'_> {
+    pub fn into_owned(self) -> model::PeriodicTransaction {
+        model::PeriodicTransaction {
+            period: self.period.to_owned(),
+            comment: self.comment.map(Cow::into_owned),
+            posting_metadata: self.posting_metadata.into_owned(),
+            postings: self.postings.into_iter().map(Posting::into_owned).collect(),
+            span: self.span,
+        }
+    }
+}
+
+///
+/// Posting metadata. Also appears on Transaction
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PostingMetadata<'a> {
+    pub date: Option<NaiveDate>,
+    pub effective_date: Option<NaiveDate>,
+    pub tags: Vec<Tag<'a>>,
+}
+
+impl PostingMetadata<'_> {
+    pub fn into_owned(self) -> model::PostingMetadata {
+        model::PostingMetadata {
+            date: self.date,
+            effective_date: self.effective_date,
+            tags: self.tags.into_iter().map(Tag::into_owned).collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq)]
+pub struct Tag<'a> {
+    pub name: &'a str,
+    pub value: Option<TagValue<'a>>,
+    pub span: Option<Span>,
+}
+
+impl PartialEq for Tag<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.value == other.value
+    }
+}
+
+impl Tag<'_> {
+    pub fn into_owned(self) -> model::Tag {
+        model::Tag {
+            name: self.name.to_owned(),
+            value: self.value.map(TagValue::into_owned),
+            span: self.span,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TagValue<'a> {
+    String(&'a str),
+    Integer(i64),
+    Float(NotNan<f64>),
+    Date(NaiveDate),
+}
+
+impl TagValue<'_> {
+    pub fn into_owned(self) -> model::TagValue {
+        match self {
+            TagValue::String(v) => model::TagValue::String(v.to_owned()),
+            TagValue::Integer(v) => model::TagValue::Integer(v),
+            TagValue::Float(v) => model::TagValue::Float(v),
+            TagValue::Date(v) => model::TagValue::Date(v),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_borrows_from_input() {
+        let input = r#"2018-10-01 (123) Payee  ; single line
+  TEST:ABC 123  "ABC 1" 1.20  ; Tag: value
+  ; first
+  ; second
+  TEST:DEF 123
+"#;
+        let ledger = crate::parse_borrowed(input).unwrap();
+        let LedgerItem::Transaction(ref transaction) = ledger.items[0] else {
+            panic!("expected transaction");
+        };
+        assert!(matches!(
+            transaction.comment,
+            Some(Cow::Borrowed("single line"))
+        ));
+        assert_eq!(transaction.code, Some("123"));
+        assert_eq!(transaction.description, Some("Payee"));
+
+        let posting = &transaction.postings[0];
+        assert_eq!(posting.account, "TEST:ABC 123");
+        assert!(matches!(
+            posting.amount.as_ref().unwrap().amount.commodity.name,
+            Cow::Borrowed("ABC 1")
+        ));
+        assert_eq!(
+            posting.metadata.tags[0].value,
+            Some(TagValue::String("value"))
+        );
+        assert!(matches!(posting.comment, Some(Cow::Owned(ref c)) if c == "first\nsecond"));
+
+        assert_eq!(ledger.into_owned(), input.parse::<model::Ledger>().unwrap());
+    }
+}
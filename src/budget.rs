@@ -0,0 +1,279 @@
+use crate::model::*;
+use crate::period::periodic_transactions;
+use crate::register::posting_date;
+use crate::{AccountName, MixedAmount, ReportError};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+
+///
+/// Budgeted and actual amounts of an account in a single period.
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BudgetRow {
+    pub account: AccountName,
+    /// First day of the period.
+    pub begin: NaiveDate,
+    /// Day after the last day of the period.
+    pub end: NaiveDate,
+    pub budget: MixedAmount,
+    /// Sum of the postings to the account and its sub-accounts in the period.
+    pub actual: MixedAmount,
+    /// `actual - budget`.
+    pub difference: MixedAmount,
+    /// `actual` as a percentage of `budget` (rounded to two decimal places), if both are
+    /// in the same single commodity, the budget is not zero and the percentage does
+    /// not overflow.
+    pub percentage: Option<Decimal>,
+}
+
+/// Compares the periodic transactions (`~ PERIOD`) of the ledger with the actual postings.
+///
+/// Each periodic transaction is expanded into the periods of its period expression
+/// which start on or after `begin` and before `end`. For every posting of it and every
+/// period, the budgeted amount (inferred, if elided) is compared with the sum of the
+/// postings of the transactions in the period to the account and its sub-accounts.
+/// Overlapping periods of the same account (e.g. of a monthly and a quarterly budget)
+/// are merged into one row, so every posting is counted once per account.
+/// Rows are sorted by account and period. Relative dates in the period expressions
+/// (e.g. `this year`) are resolved against `begin`.
+///
+/// Returns an error if a period expression is not supported, an elided amount
+/// cannot be inferred or the sums overflow.
+///
+/// # Examples
+///
+/// ```
+/// use chrono::NaiveDate;
+///
+/// let ledger: ledger_parser::Ledger = "~ Monthly\n  Expenses:Food  $100\n  Assets\n\n2018-10-03 Shop\n  Expenses:Food  $25\n  Assets\n"
+///     .parse()
+///     .unwrap();
+/// let rows = ledger_parser::budget(
+///     &ledger,
+///     NaiveDate::from_ymd_opt(2018, 10, 1).unwrap(),
+///     NaiveDate::from_ymd_opt(2018, 11, 1).unwrap(),
+/// )
+/// .unwrap();
+/// assert_eq!(rows[1].account, "Expenses:Food");
+/// assert_eq!(rows[1].percentage, Some(25.into()));
+/// ```
+pub fn budget(
+    ledger: &Ledger,
+    begin: NaiveDate,
+    end: NaiveDate,
+) -> Result<Vec<BudgetRow>, ReportError> {
+    let mut budgets: BTreeMap<AccountName, Vec<(NaiveDate, NaiveDate, MixedAmount)>> =
+        BTreeMap::new();
+    for (item, periodic, period) in periodic_transactions(ledger, begin)? {
+        for (first, next) in period.periods(begin, end) {
+            let transaction = periodic.transaction_on(first);
+            for (index, posting) in transaction.postings.iter().enumerate() {
+                let amount = transaction.posting_amount(item, index)?;
+                budgets
+                    .entry(posting.account.clone())
+                    .or_default()
+                    .push((first, next, amount));
+            }
+        }
+    }
+
+    let mut rows: Vec<BudgetRow> = Vec::new();
+    for (account, mut periods) in budgets {
+        periods.sort_by_key(|(first, next, _)| (*first, *next));
+        let account_rows = rows.len();
+        for (first, next, amount) in periods {
+            match rows[account_rows..].last_mut() {
+                Some(row) if first < row.end => {
+                    row.end = row.end.max(next);
+                    row.budget = row.budget.checked_add(&amount)?;
+                }
+                _ => rows.push(BudgetRow {
+                    account: account.clone(),
+                    begin: first,
+                    end: next,
+                    budget: amount,
+                    actual: MixedAmount::new(),
+                    difference: MixedAmount::new(),
+                    percentage: None,
+                }),
+            }
+        }
+    }
+
+    for (item, ledger_item) in ledger.items.iter().enumerate() {
+        let LedgerItem::Transaction(transaction) = ledger_item else {
+            continue;
+        };
+        for (index, posting) in transaction.postings.iter().enumerate() {
+            let date = posting_date(transaction, posting, false);
+            let mut matching = rows
+                .iter_mut()
+                .filter(|row| {
+                    row.begin <= date
+                        && date < row.end
+                        && (posting.account == row.account
+                            || posting.account.is_descendant_of(&row.account))
+                })
+                .peekable();
+            if matching.peek().is_none() {
+                continue;
+            }
+            let amount = transaction.posting_amount(item, index)?;
+            for row in matching {
+                row.actual = row.actual.checked_add(&amount)?;
+            }
+        }
+    }
+
+    for row in &mut rows {
+        row.difference = row.actual.checked_sub(&row.budget)?;
+        row.percentage = match (row.actual.single(), row.budget.single()) {
+            (Some(actual), Some(budget)) if actual.commodity.name == budget.commodity.name => {
+                actual
+                    .quantity
+                    .checked_mul(Decimal::ONE_HUNDRED)
+                    .and_then(|quantity| quantity.checked_div(budget.quantity))
+                    .map(|percentage| percentage.round_dp(2))
+            }
+            (None, Some(_)) if row.actual.is_zero() => Some(Decimal::ZERO),
+            _ => None,
+        };
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BalanceError, PeriodicTransactionError};
+
+    const SOURCE: &str = r#"~ Monthly
+  Expenses:Food  $500
+  Expenses:Rent  $1,000
+  Assets:Bank
+
+~ every 2 months from 2018-10-01
+  Expenses:Travel  200 EUR
+  Assets:Bank
+
+2018-10-03 Shop
+  Expenses:Food:Groceries  $120
+  Assets:Bank
+
+2018-10-20 Shop
+  Expenses:Food  $30
+  Expenses:Travel  $50
+  Assets:Bank
+
+2018-11-01 Landlord
+  Expenses:Rent  $1,000
+  Assets:Bank
+"#;
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2018, month, day).unwrap()
+    }
+
+    #[test]
+    fn budget_vs_actual() {
+        let ledger: Ledger = SOURCE.parse().unwrap();
+        assert_eq!(ledger.to_string().parse::<Ledger>().unwrap(), ledger);
+        let rows = budget(&ledger, date(10, 1), date(12, 1)).unwrap();
+        let summary = rows
+            .iter()
+            .map(|row| {
+                format!(
+                    "{} {} {} / {} = {} ({:?})",
+                    row.begin,
+                    row.account,
+                    row.actual,
+                    row.budget,
+                    row.difference,
+                    row.percentage.map(|percentage| percentage.to_string())
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                "2018-10-01 Assets:Bank $-1200 / $-3000, -200 EUR = $1800, 200 EUR (None)",
+                "2018-10-01 Expenses:Food $150 / $500 = $-350 (Some(\"30\"))",
+                "2018-11-01 Expenses:Food 0 / $500 = $-500 (Some(\"0\"))",
+                "2018-10-01 Expenses:Rent 0 / $1000 = $-1000 (Some(\"0\"))",
+                "2018-11-01 Expenses:Rent $1000 / $1000 = 0 (Some(\"100\"))",
+                "2018-10-01 Expenses:Travel $50 / 200 EUR = $50, -200 EUR (None)",
+            ]
+        );
+        // The monthly and the two-monthly budget of the bank account are merged, so its
+        // postings are counted once.
+        assert_eq!((rows[0].begin, rows[0].end), (date(10, 1), date(12, 1)));
+        assert_eq!(rows[1].end, date(11, 1));
+    }
+
+    #[test]
+    fn unsupported_period() {
+        let ledger: Ledger = "~ Fortnightly\n  Expenses  $1\n  Assets\n".parse().unwrap();
+        assert_eq!(
+            budget(&ledger, date(10, 1), date(12, 1)),
+            Err(ReportError::Period(PeriodicTransactionError {
+                item: 0,
+                period: "Fortnightly".to_owned(),
+                message:
+                    "unknown interval or date \"fortnightly\" in period expression \"Fortnightly\""
+                        .to_owned()
+            }))
+        );
+
+        let ledger: Ledger = "~ in 2018\n  Expenses  $1\n  Assets\n".parse().unwrap();
+        let error = budget(&ledger, date(10, 1), date(12, 1)).unwrap_err();
+        assert!(matches!(error, ReportError::Period(error) if error.message == "missing interval"));
+
+        // An interval too long to add to a date gives no periods.
+        let ledger: Ledger = "~ every 400000000 years\n  Expenses  $1\n  Assets\n"
+            .parse()
+            .unwrap();
+        assert_eq!(budget(&ledger, date(10, 1), date(12, 1)), Ok(vec![]));
+        let settings = crate::ForecastSettings::new(date(12, 1)).with_begin(date(10, 1));
+        assert_eq!(ledger.forecast(&settings), Ok(vec![]));
+    }
+
+    #[test]
+    fn uninferable_amounts() {
+        let ledger: Ledger = "~ Monthly\n  Expenses  $1\n  Assets\n  Income\n"
+            .parse()
+            .unwrap();
+        assert!(matches!(
+            budget(&ledger, date(10, 1), date(12, 1)),
+            Err(ReportError::Inference {
+                item: 0,
+                posting: 1,
+                error: BalanceError::MultipleNullAmounts { .. }
+            })
+        ));
+
+        let source = format!(
+            "{}\n2018-10-05 Shop\n  Expenses:Food  $1\n  Assets:Bank\n  Income\n",
+            SOURCE
+        );
+        let ledger: Ledger = source.parse().unwrap();
+        assert!(matches!(
+            budget(&ledger, date(10, 1), date(12, 1)),
+            Err(ReportError::Inference {
+                item: 10,
+                posting: 1,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn overflowing_percentage() {
+        let ledger: Ledger = "~ Monthly\n  Expenses  $0.0000000000000000000000000001\n  Assets\n\n2018-10-05 Shop\n  Expenses  $79228162514264337593543950335\n  Assets\n"
+            .parse()
+            .unwrap();
+        let rows = budget(&ledger, date(10, 1), date(11, 1)).unwrap();
+        assert_eq!(rows[1].account, "Expenses");
+        assert_eq!(rows[1].percentage, None);
+    }
+}
@@ -0,0 +1,358 @@
+use crate::model::*;
+use crate::parser;
+use crate::serializer::*;
+use crate::ParseError;
+use nom::{error::convert_error, Finish};
+use std::fmt;
+use std::str::FromStr;
+
+///
+/// Lossless syntax tree. Keeps the exact source text of every item next to its AST,
+/// so the document can be edited and written back with all untouched bytes preserved.
+///
+/// Spans of the items are relative to their own source text.
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SyntaxTree {
+    items: Vec<SyntaxItem>,
+}
+
+///
+/// Single top-level item with the source text it was parsed from (including its line ending).
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SyntaxItem {
+    text: String,
+    item: LedgerItem,
+}
+
+impl SyntaxItem {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn item(&self) -> &LedgerItem {
+        &self.item
+    }
+}
+
+impl SyntaxTree {
+    pub fn items(&self) -> &[SyntaxItem] {
+        &self.items
+    }
+
+    /// Builds the AST of the whole document.
+    pub fn to_ledger(&self) -> Ledger {
+        Ledger {
+            items: self.items.iter().map(|item| item.item.clone()).collect(),
+        }
+    }
+
+    /// Replaces the item at `index` with the serialized form of `item`.
+    /// The line ending of the replaced item is kept.
+    pub fn replace_item(
+        &mut self,
+        index: usize,
+        item: &LedgerItem,
+        settings: &SerializerSettings,
+    ) -> Result<(), ParseError> {
+        let mut text = item.to_string_pretty(settings);
+        if !ends_with_eol(&self.item_at(index)?.text) {
+            text.truncate(text.trim_end_matches(['\r', '\n']).len());
+        }
+        self.items[index] = parse_single_item(&text)?;
+        Ok(())
+    }
+
+    /// Inserts the serialized form of `item` before the item at `index`.
+    pub fn insert_item(
+        &mut self,
+        index: usize,
+        item: &LedgerItem,
+        settings: &SerializerSettings,
+    ) -> Result<(), ParseError> {
+        if index > self.items.len() {
+            return Err(ParseError::String(format!(
+                "item index {} out of range for {} items",
+                index,
+                self.items.len()
+            )));
+        }
+        if index > 0 && !ends_with_eol(&self.items[index - 1].text) {
+            let previous = &mut self.items[index - 1].text;
+            previous.push_str(&settings.eol);
+        }
+        let item = parse_single_item(&item.to_string_pretty(settings))?;
+        self.items.insert(index, item);
+        Ok(())
+    }
+
+    /// Removes the item at `index` with its source text, and returns its AST.
+    pub fn remove_item(&mut self, index: usize) -> Result<LedgerItem, ParseError> {
+        self.item_at(index)?;
+        Ok(self.items.remove(index).item)
+    }
+
+    /// Replaces a single posting of the transaction at `index`. Only the text of that
+    /// posting (and its comments) changes, the rest of the transaction is kept as is.
+    pub fn replace_posting(
+        &mut self,
+        index: usize,
+        posting_index: usize,
+        posting: &Posting,
+        settings: &SerializerSettings,
+    ) -> Result<(), ParseError> {
+        let syntax_item = self.item_at(index)?;
+        let span = match syntax_item.item {
+            LedgerItem::Transaction(ref transaction) => transaction
+                .postings
+                .get(posting_index)
+                .ok_or_else(|| {
+                    ParseError::String(format!(
+                        "posting index {} out of range for {} postings",
+                        posting_index,
+                        transaction.postings.len()
+                    ))
+                })?
+                .span
+                .clone()
+                .ok_or_else(|| ParseError::String("posting has no span".to_owned()))?,
+            _ => {
+                return Err(ParseError::String(format!(
+                    "item {} is not a transaction",
+                    index
+                )))
+            }
+        };
+
+        let mut text = syntax_item.text.clone();
+        text.replace_range(span.bytes, &posting.to_string_pretty(settings));
+        self.items[index] = parse_single_item(&text)?;
+        Ok(())
+    }
+
+    /// Replaces the header of the transaction at `index` (its date, effective date,
+    /// status, code and description) with the one of `transaction`. Only that part of
+    /// the first line changes, its comment, tags and postings are kept as they are.
+    pub fn replace_header(
+        &mut self,
+        index: usize,
+        transaction: &Transaction,
+        settings: &SerializerSettings,
+    ) -> Result<(), ParseError> {
+        let syntax_item = self.item_at(index)?;
+        let LedgerItem::Transaction(ref old) = syntax_item.item else {
+            return Err(ParseError::String(format!(
+                "item {} is not a transaction",
+                index
+            )));
+        };
+        let start = old
+            .span
+            .as_ref()
+            .ok_or_else(|| ParseError::String("transaction has no span".to_owned()))?
+            .bytes
+            .start;
+        let end = start
+            + parser::transaction_header_len(&syntax_item.text[start..]).ok_or_else(|| {
+                ParseError::String("transaction header cannot be parsed".to_owned())
+            })?;
+
+        let header = Transaction {
+            comment: None,
+            posting_metadata: PostingMetadata {
+                date: None,
+                effective_date: None,
+                tags: Vec::new(),
+            },
+            postings: Vec::new(),
+            span: None,
+            ..transaction.clone()
+        };
+        let mut text = syntax_item.text.clone();
+        text.replace_range(start..end, &header.to_string_pretty(settings));
+        self.items[index] = parse_single_item(&text)?;
+        Ok(())
+    }
+
+    fn item_at(&self, index: usize) -> Result<&SyntaxItem, ParseError> {
+        self.items.get(index).ok_or_else(|| {
+            ParseError::String(format!(
+                "item index {} out of range for {} items",
+                index,
+                self.items.len()
+            ))
+        })
+    }
+}
+
+fn ends_with_eol(text: &str) -> bool {
+    text.ends_with('\n')
+}
+
+fn parse_items(input: &str) -> Result<Vec<SyntaxItem>, ParseError> {
+    match parser::parse_ledger_items(input).finish() {
+        Ok((_, items)) => Ok(items
+            .into_iter()
+            .map(|(item, text)| SyntaxItem {
+                text: text.to_owned(),
+                item: item.into_owned(),
+            })
+            .collect()),
+        Err(error) => Err(ParseError::String(convert_error(input, error))),
+    }
+}
+
+fn parse_single_item(input: &str) -> Result<SyntaxItem, ParseError> {
+    let mut items = parse_items(input)?;
+    if items.len() != 1 {
+        return Err(ParseError::String(format!(
+            "expected a single item, found {}",
+            items.len()
+        )));
+    }
+    Ok(items.remove(0))
+}
+
+impl FromStr for SyntaxTree {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        Ok(SyntaxTree {
+            items: parse_items(input)?,
+        })
+    }
+}
+
+impl fmt::Display for SyntaxTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for item in &self.items {
+            f.write_str(&item.text)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+
+    const SOURCE: &str = "; Header   comment\r\n\
+        \r\n\
+        2018/10/01   *  Payee 123    ; note\r\n\
+        \tTEST:ABC 123        $1.20\r\n\
+        \tTEST:DEF 123      ; posting comment\r\n\
+        \r\n\
+        P 2017-11-12 12:00:00 mBH 5.00 PLN   ; price\r\n\
+        2018/10/02 Other\r\n  A   1,000.00 PLN\r\n  Bank";
+
+    #[test]
+    fn round_trip_is_byte_exact() {
+        let tree: SyntaxTree = SOURCE.parse().unwrap();
+        assert_eq!(tree.items().len(), 6);
+        assert_eq!(tree.to_string(), SOURCE);
+        assert_eq!(tree.to_ledger(), SOURCE.parse::<Ledger>().unwrap());
+    }
+
+    #[test]
+    fn replace_posting_keeps_other_bytes() {
+        let mut tree: SyntaxTree = SOURCE.parse().unwrap();
+        let LedgerItem::Transaction(ref transaction) = *tree.items()[2].item() else {
+            panic!("expected transaction");
+        };
+        let mut posting = transaction.postings[0].clone();
+        posting.amount.as_mut().unwrap().amount.quantity = Decimal::new(250, 2);
+
+        tree.replace_posting(2, 0, &posting, &SerializerSettings::default())
+            .unwrap();
+        assert_eq!(
+            tree.to_string(),
+            SOURCE.replace("TEST:ABC 123        $1.20", "TEST:ABC 123  $2.50")
+        );
+    }
+
+    #[test]
+    fn replace_header_keeps_other_bytes() {
+        let mut tree: SyntaxTree = SOURCE.parse().unwrap();
+        let LedgerItem::Transaction(ref transaction) = *tree.items()[2].item() else {
+            panic!("expected transaction");
+        };
+        let mut transaction = transaction.clone();
+        transaction.date = NaiveDate::from_ymd_opt(2018, 10, 3).unwrap();
+        transaction.code = Some("42".to_owned());
+        transaction.description = Some("New payee".to_owned());
+        // Only the header is written, not the comment.
+        transaction.comment = Some("ignored".to_owned());
+
+        tree.replace_header(2, &transaction, &SerializerSettings::default())
+            .unwrap();
+        assert_eq!(
+            tree.to_string(),
+            SOURCE.replace(
+                "2018/10/01   *  Payee 123    ; note",
+                "2018-10-03 * (42) New payee    ; note"
+            )
+        );
+        let LedgerItem::Transaction(ref replaced) = *tree.items()[2].item() else {
+            panic!("expected transaction");
+        };
+        assert_eq!(replaced.comment.as_deref(), Some("note"));
+        assert_eq!(replaced.description.as_deref(), Some("New payee"));
+
+        assert_eq!(
+            tree.replace_header(0, &transaction, &SerializerSettings::default()),
+            Err(ParseError::String("item 0 is not a transaction".to_owned()))
+        );
+    }
+
+    #[test]
+    fn replace_insert_and_remove_items() {
+        let mut tree: SyntaxTree = SOURCE.parse().unwrap();
+        let settings = SerializerSettings::default().with_eol("\r\n");
+
+        let comment = LedgerItem::LineComment(LineComment::new("Replaced"));
+        tree.replace_item(0, &comment, &settings).unwrap();
+        assert!(tree
+            .to_string()
+            .starts_with("; Replaced\r\n\r\n2018/10/01   *  Payee"));
+
+        tree.insert_item(6, &comment, &settings).unwrap();
+        assert!(tree.to_string().ends_with("  Bank\r\n; Replaced\r\n"));
+
+        assert_eq!(tree.remove_item(6), Ok(comment));
+        tree.remove_item(0).unwrap();
+        assert_eq!(
+            tree.to_string(),
+            SOURCE.replacen("; Header   comment\r\n", "", 1) + "\r\n"
+        );
+    }
+
+    #[test]
+    fn invalid_indices() {
+        let mut tree: SyntaxTree = SOURCE.parse().unwrap();
+        let settings = SerializerSettings::default();
+        let LedgerItem::Transaction(ref transaction) = *tree.items()[2].item() else {
+            panic!("expected transaction");
+        };
+        let posting = transaction.postings[0].clone();
+        let comment = LedgerItem::LineComment(LineComment::new("Comment"));
+
+        assert!(tree.replace_item(6, &comment, &settings).is_err());
+        assert!(tree.insert_item(7, &comment, &settings).is_err());
+        assert!(tree.remove_item(6).is_err());
+        assert!(tree.replace_posting(6, 0, &posting, &settings).is_err());
+        assert_eq!(
+            tree.replace_posting(2, 2, &posting, &settings),
+            Err(ParseError::String(
+                "posting index 2 out of range for 2 postings".to_owned()
+            ))
+        );
+        assert_eq!(
+            tree.replace_posting(0, 0, &posting, &settings),
+            Err(ParseError::String("item 0 is not a transaction".to_owned()))
+        );
+        assert_eq!(tree.to_string(), SOURCE);
+    }
+}
@@ -0,0 +1,269 @@
+use crate::model::*;
+use crate::AccountName;
+use rust_decimal::Decimal;
+use std::collections::{HashMap, HashSet};
+
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateSettings {
+    /// Largest number of days between the dates of fuzzy duplicates. Defaults to 3.
+    pub max_days: u64,
+    /// Smallest similarity (from 0 to 1) of the descriptions of fuzzy duplicates.
+    /// Defaults to 0.5.
+    pub min_similarity: f64,
+}
+
+impl Default for DuplicateSettings {
+    fn default() -> Self {
+        DuplicateSettings {
+            max_days: 3,
+            min_similarity: 0.5,
+        }
+    }
+}
+
+impl DuplicateSettings {
+    pub fn with_max_days(mut self, max_days: u64) -> Self {
+        self.max_days = max_days;
+        self
+    }
+
+    pub fn with_min_similarity(mut self, min_similarity: f64) -> Self {
+        self.min_similarity = min_similarity;
+        self
+    }
+}
+
+///
+/// Transaction of a duplicate pair.
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TransactionLocation {
+    /// Index of the transaction in `Ledger::items`.
+    pub item: usize,
+    pub span: Option<Span>,
+}
+
+///
+/// Pair of transactions which are likely the same.
+///
+#[derive(Debug, PartialEq, Clone)]
+pub struct Duplicate {
+    /// The transaction coming first in the file.
+    pub first: TransactionLocation,
+    pub second: TransactionLocation,
+    /// Same date, status, code, description and postings.
+    pub exact: bool,
+    /// From 0 to 1, 1 for exact duplicates.
+    pub confidence: f64,
+}
+
+impl Ledger {
+    /// Finds pairs of transactions with the same accounts and amounts (elided amounts
+    /// inferred, regardless of the order of the postings), whose dates are at most
+    /// `max_days` apart and whose descriptions are similar.
+    ///
+    /// The similarity of the descriptions is the share of the pairs of adjacent letters
+    /// and digits they have in common, ignoring case. The confidence of a fuzzy duplicate
+    /// is the similarity, lowered for every day between the dates. Pairs are sorted by
+    /// decreasing confidence, then in file order. Transactions with elided amounts which
+    /// cannot be inferred are skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let ledger: ledger_parser::Ledger = "2018-10-01 AMAZON MKTPLACE\n  Expenses  $10\n  Assets\n\n2018-10-02 Amazon Marketplace\n  Assets  $-10\n  Expenses\n"
+    ///     .parse()
+    ///     .unwrap();
+    /// let duplicates = ledger.find_duplicates(&Default::default());
+    /// assert_eq!(duplicates.len(), 1);
+    /// assert_eq!((duplicates[0].first.item, duplicates[0].second.item), (0, 2));
+    /// assert!(!duplicates[0].exact);
+    /// ```
+    pub fn find_duplicates(&self, settings: &DuplicateSettings) -> Vec<Duplicate> {
+        let mut groups: HashMap<Vec<(AccountName, String, Decimal)>, Vec<usize>> = HashMap::new();
+        for (item, ledger_item) in self.items.iter().enumerate() {
+            if let LedgerItem::Transaction(transaction) = ledger_item {
+                if let Some(key) = postings_key(transaction) {
+                    groups.entry(key).or_default().push(item);
+                }
+            }
+        }
+
+        let mut duplicates = Vec::new();
+        for items in groups.values() {
+            for (index, &first) in items.iter().enumerate() {
+                for &second in &items[index + 1..] {
+                    let (LedgerItem::Transaction(a), LedgerItem::Transaction(b)) =
+                        (&self.items[first], &self.items[second])
+                    else {
+                        unreachable!("only transactions are grouped");
+                    };
+                    let days = (a.date - b.date).num_days().unsigned_abs();
+                    if days > settings.max_days {
+                        continue;
+                    }
+                    let exact = days == 0
+                        && a.status == b.status
+                        && a.code == b.code
+                        && a.description == b.description;
+                    let confidence = if exact {
+                        1.0
+                    } else {
+                        let similarity =
+                            similarity(a.description.as_deref(), b.description.as_deref());
+                        if similarity < settings.min_similarity {
+                            continue;
+                        }
+                        similarity * (1.0 - days as f64 / (settings.max_days + 1) as f64)
+                    };
+                    duplicates.push(Duplicate {
+                        first: TransactionLocation {
+                            item: first,
+                            span: a.span.clone(),
+                        },
+                        second: TransactionLocation {
+                            item: second,
+                            span: b.span.clone(),
+                        },
+                        exact,
+                        confidence,
+                    });
+                }
+            }
+        }
+        duplicates.sort_by(|a, b| {
+            b.confidence
+                .total_cmp(&a.confidence)
+                .then(a.first.item.cmp(&b.first.item))
+                .then(a.second.item.cmp(&b.second.item))
+        });
+        duplicates
+    }
+}
+
+/// Accounts and amounts of the postings, sorted, or `None` if an elided amount cannot
+/// be inferred.
+fn postings_key(transaction: &Transaction) -> Option<Vec<(AccountName, String, Decimal)>> {
+    let mut key = Vec::new();
+    for (index, posting) in transaction.postings.iter().enumerate() {
+        let amounts = match posting.amount {
+            Some(ref amount) => vec![amount.amount.clone()],
+            None => transaction
+                .inferred_amount(index)
+                .ok()?
+                .amounts()
+                .cloned()
+                .collect(),
+        };
+        for amount in amounts {
+            key.push((
+                posting.account.clone(),
+                amount.commodity.name,
+                amount.quantity.normalize(),
+            ));
+        }
+    }
+    key.sort();
+    Some(key)
+}
+
+/// Dice coefficient of the sets of adjacent letter and digit pairs, ignoring case.
+fn similarity(a: Option<&str>, b: Option<&str>) -> f64 {
+    fn bigrams(text: &str) -> HashSet<(char, char)> {
+        let chars = text
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect::<Vec<_>>();
+        chars.windows(2).map(|pair| (pair[0], pair[1])).collect()
+    }
+
+    let (a, b) = (
+        bigrams(a.unwrap_or_default()),
+        bigrams(b.unwrap_or_default()),
+    );
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    2.0 * a.intersection(&b).count() as f64 / (a.len() + b.len()) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"2018-10-01 * Coffee Shop
+  Expenses:Coffee  $4.50
+  Assets:Bank
+
+2018-10-01 * Coffee Shop
+  Assets:Bank  $-4.5
+  Expenses:Coffee
+
+2018-10-03 COFFEE SHOP #123
+  Expenses:Coffee  $4.50
+  Assets:Bank
+
+2018-10-02 Bookstore
+  Expenses:Coffee  $4.50
+  Assets:Bank
+
+2018-10-08 Coffee Shop
+  Expenses:Coffee  $4.50
+  Assets:Bank
+
+2018-10-01 Coffee Shop
+  Expenses:Coffee  $5
+  Assets:Bank
+"#;
+
+    #[test]
+    fn exact_and_fuzzy_duplicates() {
+        let ledger: Ledger = SOURCE.parse().unwrap();
+        let duplicates = ledger.find_duplicates(&DuplicateSettings::default());
+        let pairs = duplicates
+            .iter()
+            .map(|duplicate| (duplicate.first.item, duplicate.second.item, duplicate.exact))
+            .collect::<Vec<_>>();
+        assert_eq!(pairs, vec![(0, 2, true), (0, 4, false), (2, 4, false)]);
+
+        assert_eq!(duplicates[0].confidence, 1.0);
+        assert_eq!(duplicates[0].first.span.as_ref().unwrap().lines, 1..4);
+        assert_eq!(duplicates[0].second.span.as_ref().unwrap().lines, 5..8);
+        // Two days apart, with a longer description.
+        let confidence = duplicates[1].confidence;
+        assert!(confidence > 0.3 && confidence < 0.5, "{}", confidence);
+
+        let settings = DuplicateSettings::default()
+            .with_max_days(7)
+            .with_min_similarity(0.9);
+        let pairs = ledger
+            .find_duplicates(&settings)
+            .iter()
+            .map(|duplicate| (duplicate.first.item, duplicate.second.item))
+            .collect::<Vec<_>>();
+        assert_eq!(pairs, vec![(0, 2), (0, 8), (2, 8)]);
+    }
+
+    #[test]
+    fn uninferable_amounts() {
+        // Both would have the same key if the elided amounts were counted as nothing.
+        let ledger: Ledger = "2018-10-01 Shop\n  Expenses  $5\n  Assets\n  Income\n\n2018-10-01 Shop\n  Expenses  $5\n  Assets\n  Income\n"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            ledger.find_duplicates(&DuplicateSettings::default()),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn description_similarity() {
+        assert_eq!(similarity(Some("Coffee Shop"), Some("coffee-shop")), 1.0);
+        assert_eq!(similarity(None, None), 1.0);
+        assert_eq!(similarity(Some("Coffee"), None), 0.0);
+        assert!(similarity(Some("AMAZON MKTPLACE"), Some("Amazon Marketplace")) > 0.6);
+        assert!(similarity(Some("Coffee Shop"), Some("Bookstore")) < 0.2);
+    }
+}
@@ -0,0 +1,884 @@
+use crate::model::*;
+use crate::parser::{parse_amount, parse_date, LedgerParseResult};
+use crate::register::posting_date;
+use crate::AmountError;
+use chrono::NaiveDate;
+use nom::{
+    branch::alt,
+    bytes::complete::{escaped, is_not, tag, tag_no_case},
+    character::complete::{char, digit1, multispace0, none_of, one_of, satisfy},
+    combinator::{all_consuming, cut, map_res, not, opt, peek, recognize, value, verify},
+    error::convert_error,
+    multi::{many0, separated_list0},
+    sequence::{delimited, pair, preceded, terminated},
+    Finish, Parser,
+};
+use regex::{Regex, RegexBuilder};
+use rust_decimal::Decimal;
+use std::fmt;
+use std::str::FromStr;
+
+///
+/// Result of evaluating a value expression.
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Value {
+    /// Missing value, e.g. the amount of a transaction or a tag which is not set.
+    Null,
+    Bool(bool),
+    Number(Decimal),
+    Amount(Amount),
+    String(String),
+    Date(NaiveDate),
+}
+
+impl Value {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Bool(_) => "boolean",
+            Value::Number(_) => "number",
+            Value::Amount(_) => "amount",
+            Value::String(_) => "string",
+            Value::Date(_) => "date",
+        }
+    }
+
+    /// `false` for null, `false`, zero numbers and amounts and empty strings.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Null => false,
+            Value::Bool(value) => *value,
+            Value::Number(value) => !value.is_zero(),
+            Value::Amount(value) => !value.is_zero(),
+            Value::String(value) => !value.is_empty(),
+            Value::Date(_) => true,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(value) => value.fmt(f),
+            Value::Number(value) => value.fmt(f),
+            Value::Amount(value) => value.fmt(f),
+            Value::String(value) => write!(f, "{:?}", value),
+            Value::Date(value) => write!(f, "[{}]", value),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ExprError {
+    /// The expression could not be parsed.
+    Syntax(String),
+    UnknownVariable(String),
+    UnsupportedFunction(String),
+    WrongArgumentCount {
+        function: String,
+        expected: usize,
+        found: usize,
+    },
+    /// Operator or function applied to values of the wrong types.
+    Type {
+        operation: String,
+        operands: Vec<&'static str>,
+    },
+    Amount(AmountError),
+    DivisionByZero,
+    /// Result of an arithmetic operation out of the range of decimals.
+    Overflow,
+    /// Posting index out of range for the postings of the transaction.
+    PostingIndex {
+        index: usize,
+        postings: usize,
+    },
+}
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExprError::Syntax(message) => write!(f, "invalid value expression: {}", message),
+            ExprError::UnknownVariable(name) => write!(f, "unknown variable '{}'", name),
+            ExprError::UnsupportedFunction(name) => write!(f, "unsupported function '{}'", name),
+            ExprError::WrongArgumentCount {
+                function,
+                expected,
+                found,
+            } => write!(
+                f,
+                "function '{}' takes {} argument(s), {} given",
+                function, expected, found
+            ),
+            ExprError::Type {
+                operation,
+                operands,
+            } => write!(
+                f,
+                "cannot apply '{}' to {}",
+                operation,
+                operands.join(" and ")
+            ),
+            ExprError::Amount(error) => error.fmt(f),
+            ExprError::DivisionByZero => write!(f, "division by zero"),
+            ExprError::Overflow => write!(f, "arithmetic overflow"),
+            ExprError::PostingIndex { index, postings } => write!(
+                f,
+                "posting index {} out of range for {} postings",
+                index, postings
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ExprError {}
+
+impl From<AmountError> for ExprError {
+    fn from(error: AmountError) -> Self {
+        ExprError::Amount(error)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BinaryOperator {
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+impl BinaryOperator {
+    fn symbol(self) -> &'static str {
+        match self {
+            BinaryOperator::Or => "or",
+            BinaryOperator::And => "and",
+            BinaryOperator::Equal => "==",
+            BinaryOperator::NotEqual => "!=",
+            BinaryOperator::Less => "<",
+            BinaryOperator::LessOrEqual => "<=",
+            BinaryOperator::Greater => ">",
+            BinaryOperator::GreaterOrEqual => ">=",
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+        }
+    }
+}
+
+const VARIABLES: &[&str] = &[
+    "account",
+    "payee",
+    "note",
+    "code",
+    "date",
+    "amount",
+    "commodity",
+    "cleared",
+    "pending",
+    "real",
+    "virtual",
+];
+
+const FUNCTIONS: &[(&str, usize)] = &[("tag", 1), ("has_tag", 1), ("abs", 1), ("quantity", 1)];
+
+///
+/// Ledger value expression, e.g. `account =~ /^Expenses/ and amount > {$100}`.
+///
+/// Literals are numbers, amounts (`{10 EUR}`, or `$10` for symbol commodities), strings
+/// (`"text"`), dates (`[2018-10-01]`) and regular expressions (`/re/`, only after `=~`
+/// and `!~`). Operators, from the lowest precedence: `or` (`||`), `and` (`&&`),
+/// `not` (`!`), comparisons (`==`, `!=`, `<`, `<=`, `>`, `>=`, `=~`, `!~`), `+` and `-`,
+/// `*` and `/`, unary `-`.
+///
+/// Variables describe the posting (or transaction) the expression is evaluated for:
+/// `account`, `payee`, `note`, `code`, `date`, `amount`, `commodity`, `cleared`,
+/// `pending`, `real` and `virtual`. Functions are `tag("NAME")`, `has_tag("NAME")`,
+/// `abs(x)` and `quantity(x)`.
+///
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Value(Value),
+    Variable(String),
+    Call(String, Vec<Expr>),
+    Not(Box<Expr>),
+    Negate(Box<Expr>),
+    Binary(BinaryOperator, Box<Expr>, Box<Expr>),
+    /// Regular expression match (`=~`), ignoring case.
+    Match(Box<Expr>, Regex),
+}
+
+impl FromStr for Expr {
+    type Err = ExprError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let expr = match all_consuming(parse_expr)(input).finish() {
+            Ok((_, expr)) => expr,
+            Err(error) => return Err(ExprError::Syntax(convert_error(input, error))),
+        };
+        expr.check()?;
+        Ok(expr)
+    }
+}
+
+impl Expr {
+    /// Checks the names of the variables and functions and the numbers of arguments.
+    fn check(&self) -> Result<(), ExprError> {
+        match self {
+            Expr::Value(_) => Ok(()),
+            Expr::Variable(name) if VARIABLES.contains(&name.as_str()) => Ok(()),
+            Expr::Variable(name) => Err(ExprError::UnknownVariable(name.clone())),
+            Expr::Call(name, args) => {
+                let Some(&(_, expected)) = FUNCTIONS.iter().find(|(function, _)| function == name)
+                else {
+                    return Err(ExprError::UnsupportedFunction(name.clone()));
+                };
+                if args.len() != expected {
+                    return Err(ExprError::WrongArgumentCount {
+                        function: name.clone(),
+                        expected,
+                        found: args.len(),
+                    });
+                }
+                args.iter().try_for_each(Expr::check)
+            }
+            Expr::Not(expr) | Expr::Negate(expr) | Expr::Match(expr, _) => expr.check(),
+            Expr::Binary(_, left, right) => {
+                left.check()?;
+                right.check()
+            }
+        }
+    }
+
+    /// Evaluates the expression for the posting at index `posting` of `transaction`,
+    /// or for the transaction itself if `posting` is `None` (posting variables are null).
+    pub fn evaluate(
+        &self,
+        transaction: &Transaction,
+        posting: Option<usize>,
+    ) -> Result<Value, ExprError> {
+        let posting = match posting {
+            Some(index) => Some((
+                index,
+                transaction
+                    .postings
+                    .get(index)
+                    .ok_or(ExprError::PostingIndex {
+                        index,
+                        postings: transaction.postings.len(),
+                    })?,
+            )),
+            None => None,
+        };
+        let context = Context {
+            transaction,
+            posting,
+        };
+        context.evaluate(self)
+    }
+
+    /// Evaluates the expression and checks if the result [is truthy](Value::is_truthy).
+    pub fn matches(
+        &self,
+        transaction: &Transaction,
+        posting: Option<usize>,
+    ) -> Result<bool, ExprError> {
+        self.evaluate(transaction, posting)
+            .map(|value| value.is_truthy())
+    }
+}
+
+struct Context<'a> {
+    transaction: &'a Transaction,
+    posting: Option<(usize, &'a Posting)>,
+}
+
+impl Context<'_> {
+    fn evaluate(&self, expr: &Expr) -> Result<Value, ExprError> {
+        match expr {
+            Expr::Value(value) => Ok(value.clone()),
+            Expr::Variable(name) => self.variable(name),
+            Expr::Call(name, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.evaluate(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.call(name, args)
+            }
+            Expr::Not(expr) => Ok(Value::Bool(!self.evaluate(expr)?.is_truthy())),
+            Expr::Negate(expr) => match self.evaluate(expr)? {
+                Value::Number(value) => Ok(Value::Number(-value)),
+                Value::Amount(value) => Ok(Value::Amount(-value)),
+                value => Err(type_error("-", &[&value])),
+            },
+            Expr::Binary(BinaryOperator::Or, left, right) => {
+                let left = self.evaluate(left)?;
+                if left.is_truthy() {
+                    Ok(left)
+                } else {
+                    self.evaluate(right)
+                }
+            }
+            Expr::Binary(BinaryOperator::And, left, right) => {
+                let left = self.evaluate(left)?;
+                if left.is_truthy() {
+                    self.evaluate(right)
+                } else {
+                    Ok(left)
+                }
+            }
+            Expr::Binary(operator, left, right) => {
+                binary(*operator, self.evaluate(left)?, self.evaluate(right)?)
+            }
+            Expr::Match(expr, regex) => match self.evaluate(expr)? {
+                Value::String(value) => Ok(Value::Bool(regex.is_match(&value))),
+                Value::Null => Ok(Value::Bool(false)),
+                value => Err(type_error("=~", &[&value])),
+            },
+        }
+    }
+
+    fn variable(&self, name: &str) -> Result<Value, ExprError> {
+        let transaction = self.transaction;
+        let posting = self.posting.map(|(_, posting)| posting);
+        let string = |value: Option<&String>| value.cloned().map_or(Value::Null, Value::String);
+        let status = |status| {
+            Value::Bool(
+                posting
+                    .and_then(|posting| posting.status)
+                    .or(transaction.status)
+                    == Some(status),
+            )
+        };
+        Ok(match name {
+            "account" => posting.map_or(Value::Null, |posting| {
+                Value::String(posting.account.to_string())
+            }),
+            "payee" => string(transaction.description.as_ref()),
+            "note" => string(
+                posting
+                    .and_then(|posting| posting.comment.as_ref())
+                    .or(transaction.comment.as_ref()),
+            ),
+            "code" => string(transaction.code.as_ref()),
+            "date" => Value::Date(match posting {
+                Some(posting) => posting_date(transaction, posting, false),
+                None => transaction.date,
+            }),
+            "amount" => self.amount().map_or(Value::Null, Value::Amount),
+            "commodity" => self
+                .amount()
+                .map_or(Value::Null, |amount| Value::String(amount.commodity.name)),
+            "cleared" => status(TransactionStatus::Cleared),
+            "pending" => status(TransactionStatus::Pending),
+            "real" => Value::Bool(posting.is_some_and(|posting| posting.reality == Reality::Real)),
+            "virtual" => {
+                Value::Bool(posting.is_some_and(|posting| posting.reality != Reality::Real))
+            }
+            _ => return Err(ExprError::UnknownVariable(name.to_owned())),
+        })
+    }
+
+    /// Amount of the posting, inferred if elided (and in a single commodity).
+    fn amount(&self) -> Option<Amount> {
+        let (index, posting) = self.posting?;
+        match posting.amount {
+            Some(ref amount) => Some(amount.amount.clone()),
+            None => self
+                .transaction
+                .inferred_amount(index)
+                .ok()?
+                .single()
+                .cloned(),
+        }
+    }
+
+    fn call(&self, name: &str, args: Vec<Value>) -> Result<Value, ExprError> {
+        let mut args = args.into_iter();
+        let arg = args.next().unwrap_or(Value::Null);
+        match name {
+            "tag" | "has_tag" => {
+                let Value::String(tag_name) = arg else {
+                    return Err(type_error(name, &[&arg]));
+                };
+                let tag = self
+                    .posting
+                    .into_iter()
+                    .flat_map(|(_, posting)| &posting.metadata.tags)
+                    .chain(&self.transaction.posting_metadata.tags)
+                    .find(|tag| tag.name == tag_name);
+                if name == "has_tag" {
+                    return Ok(Value::Bool(tag.is_some()));
+                }
+                Ok(match tag.and_then(|tag| tag.value.as_ref()) {
+                    None => Value::Null,
+                    Some(TagValue::String(value)) => Value::String(value.clone()),
+                    Some(TagValue::Integer(value)) => Value::Number((*value).into()),
+                    Some(TagValue::Float(value)) => {
+                        Decimal::try_from(value.into_inner()).map_or(Value::Null, Value::Number)
+                    }
+                    Some(TagValue::Date(value)) => Value::Date(*value),
+                })
+            }
+            "abs" => match arg {
+                Value::Number(value) => Ok(Value::Number(value.abs())),
+                Value::Amount(value) => Ok(Value::Amount(Amount::new(
+                    value.quantity.abs(),
+                    value.commodity,
+                ))),
+                value => Err(type_error(name, &[&value])),
+            },
+            "quantity" => match arg {
+                Value::Number(value) => Ok(Value::Number(value)),
+                Value::Amount(value) => Ok(Value::Number(value.quantity)),
+                value => Err(type_error(name, &[&value])),
+            },
+            _ => Err(ExprError::UnsupportedFunction(name.to_owned())),
+        }
+    }
+}
+
+fn type_error(operation: &str, operands: &[&Value]) -> ExprError {
+    ExprError::Type {
+        operation: operation.to_owned(),
+        operands: operands.iter().map(|value| value.type_name()).collect(),
+    }
+}
+
+fn binary(operator: BinaryOperator, left: Value, right: Value) -> Result<Value, ExprError> {
+    use BinaryOperator::*;
+
+    let error = || type_error(operator.symbol(), &[&left, &right]);
+    match operator {
+        Equal | NotEqual => {
+            let equal = match (&left, &right) {
+                (Value::Amount(amount), Value::Number(number))
+                | (Value::Number(number), Value::Amount(amount)) => amount.quantity == *number,
+                (left, right) => left == right,
+            };
+            Ok(Value::Bool(equal == (operator == Equal)))
+        }
+        Less | LessOrEqual | Greater | GreaterOrEqual => {
+            let ordering = match (&left, &right) {
+                (Value::Null, _) | (_, Value::Null) => return Ok(Value::Bool(false)),
+                (Value::Number(left), Value::Number(right)) => left.cmp(right),
+                (Value::Amount(left), Value::Number(right)) => left.quantity.cmp(right),
+                (Value::Number(left), Value::Amount(right)) => left.cmp(&right.quantity),
+                (Value::Amount(left), Value::Amount(right)) => {
+                    left.check_commodity(right)?;
+                    left.quantity.cmp(&right.quantity)
+                }
+                (Value::String(left), Value::String(right)) => left.cmp(right),
+                (Value::Date(left), Value::Date(right)) => left.cmp(right),
+                _ => return Err(error()),
+            };
+            Ok(Value::Bool(match operator {
+                Less => ordering.is_lt(),
+                LessOrEqual => ordering.is_le(),
+                Greater => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            }))
+        }
+        Add | Subtract => {
+            let apply = |left: Decimal, right: Decimal| {
+                if operator == Add {
+                    left.checked_add(right)
+                } else {
+                    left.checked_sub(right)
+                }
+                .ok_or(ExprError::Overflow)
+            };
+            match (&left, &right) {
+                (Value::Number(left), Value::Number(right)) => {
+                    Ok(Value::Number(apply(*left, *right)?))
+                }
+                (Value::Amount(left), Value::Amount(right)) => {
+                    left.check_commodity(right)?;
+                    Ok(Value::Amount(Amount::new(
+                        apply(left.quantity, right.quantity)?,
+                        left.commodity.clone(),
+                    )))
+                }
+                _ => Err(error()),
+            }
+        }
+        Multiply => {
+            let multiply =
+                |left: Decimal, right: Decimal| left.checked_mul(right).ok_or(ExprError::Overflow);
+            match (&left, &right) {
+                (Value::Number(left), Value::Number(right)) => {
+                    Ok(Value::Number(multiply(*left, *right)?))
+                }
+                (Value::Amount(amount), Value::Number(number))
+                | (Value::Number(number), Value::Amount(amount)) => Ok(Value::Amount(Amount::new(
+                    multiply(amount.quantity, *number)?,
+                    amount.commodity.clone(),
+                ))),
+                _ => Err(error()),
+            }
+        }
+        Divide => {
+            let divide =
+                |left: Decimal, right: Decimal| left.checked_div(right).ok_or(ExprError::Overflow);
+            match (&left, &right) {
+                (_, Value::Number(right)) if right.is_zero() => Err(ExprError::DivisionByZero),
+                (Value::Number(left), Value::Number(right)) => {
+                    Ok(Value::Number(divide(*left, *right)?))
+                }
+                (Value::Amount(left), Value::Number(right)) => Ok(Value::Amount(Amount::new(
+                    divide(left.quantity, *right)?,
+                    left.commodity.clone(),
+                ))),
+                _ => Err(error()),
+            }
+        }
+        Or | And => unreachable!("evaluated lazily"),
+    }
+}
+
+fn ws<'a, O, F>(parser: F) -> impl FnMut(&'a str) -> LedgerParseResult<'a, O>
+where
+    F: Parser<&'a str, O, nom::error::VerboseError<&'a str>>,
+{
+    delimited(multispace0, parser, multispace0)
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn keyword<'a>(name: &'static str) -> impl FnMut(&'a str) -> LedgerParseResult<'a, &'a str> {
+    terminated(tag_no_case(name), not(peek(satisfy(is_identifier_char))))
+}
+
+fn identifier(input: &str) -> LedgerParseResult<'_, &str> {
+    verify(
+        recognize(pair(
+            satisfy(|c| c.is_ascii_alphabetic() || c == '_'),
+            many0(satisfy(is_identifier_char)),
+        )),
+        |name: &str| !["and", "or", "not"].contains(&name),
+    )(input)
+}
+
+fn string_literal(input: &str) -> LedgerParseResult<'_, String> {
+    alt((
+        delimited(char('"'), opt(is_not("\"")), char('"')),
+        delimited(char('\''), opt(is_not("'")), char('\'')),
+    ))
+    .map(|value: Option<&str>| value.unwrap_or_default().to_owned())
+    .parse(input)
+}
+
+fn regex_literal(input: &str) -> LedgerParseResult<'_, Regex> {
+    map_res(
+        delimited(
+            char('/'),
+            opt(escaped(
+                none_of("\\/"),
+                '\\',
+                one_of("\\/dDwWsSbB.*+?()[]{}|^$-"),
+            )),
+            char('/'),
+        ),
+        |pattern: Option<&str>| {
+            RegexBuilder::new(&pattern.unwrap_or_default().replace("\\/", "/"))
+                .case_insensitive(true)
+                .build()
+        },
+    )(input)
+}
+
+fn parse_primary(input: &str) -> LedgerParseResult<'_, Expr> {
+    ws(alt((
+        delimited(char('('), parse_expr, cut(char(')'))),
+        map_res(
+            recognize(pair(digit1, opt(pair(char('.'), digit1)))),
+            |number| Decimal::from_str(number).map(|number| Expr::Value(Value::Number(number))),
+        ),
+        delimited(char('{'), ws(parse_amount), cut(char('}')))
+            .map(|amount| Expr::Value(Value::Amount(amount.into_owned()))),
+        preceded(peek(satisfy(|c| c == '$' || !c.is_ascii())), parse_amount)
+            .map(|amount| Expr::Value(Value::Amount(amount.into_owned()))),
+        delimited(char('['), parse_date, cut(char(']'))).map(|date| Expr::Value(Value::Date(date))),
+        string_literal.map(|value| Expr::Value(Value::String(value))),
+        value(Expr::Value(Value::Bool(true)), keyword("true")),
+        value(Expr::Value(Value::Bool(false)), keyword("false")),
+        pair(
+            identifier,
+            opt(delimited(
+                ws(char('(')),
+                separated_list0(char(','), parse_expr),
+                cut(char(')')),
+            )),
+        )
+        .map(|(name, args)| match args {
+            Some(args) => Expr::Call(name.to_owned(), args),
+            None => Expr::Variable(name.to_owned()),
+        }),
+    )))(input)
+}
+
+fn parse_unary(input: &str) -> LedgerParseResult<'_, Expr> {
+    alt((
+        preceded(ws(char('-')), parse_unary).map(|expr| Expr::Negate(Box::new(expr))),
+        parse_primary,
+    ))(input)
+}
+
+/// Parses `operand (operator operand)*`, associating to the left.
+fn binary_chain<'a>(
+    input: &'a str,
+    operand: fn(&'a str) -> LedgerParseResult<'a, Expr>,
+    operator: impl Parser<&'a str, BinaryOperator, nom::error::VerboseError<&'a str>>,
+) -> LedgerParseResult<'a, Expr> {
+    let (input, first) = operand(input)?;
+    let (input, rest) = many0(pair(ws(operator), operand))(input)?;
+    Ok((
+        input,
+        rest.into_iter().fold(first, |left, (operator, right)| {
+            Expr::Binary(operator, Box::new(left), Box::new(right))
+        }),
+    ))
+}
+
+fn parse_term(input: &str) -> LedgerParseResult<'_, Expr> {
+    binary_chain(
+        input,
+        parse_unary,
+        alt((
+            value(BinaryOperator::Multiply, char('*')),
+            value(BinaryOperator::Divide, char('/')),
+        )),
+    )
+}
+
+fn parse_sum(input: &str) -> LedgerParseResult<'_, Expr> {
+    binary_chain(
+        input,
+        parse_term,
+        alt((
+            value(BinaryOperator::Add, char('+')),
+            value(BinaryOperator::Subtract, char('-')),
+        )),
+    )
+}
+
+fn parse_comparison(input: &str) -> LedgerParseResult<'_, Expr> {
+    enum Suffix {
+        Match(bool, Regex),
+        Compare(BinaryOperator, Expr),
+    }
+
+    let (input, left) = parse_sum(input)?;
+    let (input, suffix) = opt(alt((
+        pair(
+            ws(alt((value(true, tag("=~")), value(false, tag("!~"))))),
+            cut(ws(regex_literal)),
+        )
+        .map(|(matches, regex)| Suffix::Match(matches, regex)),
+        pair(
+            ws(alt((
+                value(BinaryOperator::Equal, tag("==")),
+                value(BinaryOperator::NotEqual, tag("!=")),
+                value(BinaryOperator::LessOrEqual, tag("<=")),
+                value(BinaryOperator::GreaterOrEqual, tag(">=")),
+                value(BinaryOperator::Less, char('<')),
+                value(BinaryOperator::Greater, char('>')),
+            ))),
+            parse_sum,
+        )
+        .map(|(operator, right)| Suffix::Compare(operator, right)),
+    )))(input)?;
+
+    let expr = match suffix {
+        None => left,
+        Some(Suffix::Match(true, regex)) => Expr::Match(Box::new(left), regex),
+        Some(Suffix::Match(false, regex)) => {
+            Expr::Not(Box::new(Expr::Match(Box::new(left), regex)))
+        }
+        Some(Suffix::Compare(operator, right)) => {
+            Expr::Binary(operator, Box::new(left), Box::new(right))
+        }
+    };
+    Ok((input, expr))
+}
+
+fn parse_not(input: &str) -> LedgerParseResult<'_, Expr> {
+    alt((
+        preceded(
+            ws(alt((
+                keyword("not"),
+                terminated(tag("!"), not(one_of("=~"))),
+            ))),
+            parse_not,
+        )
+        .map(|expr| Expr::Not(Box::new(expr))),
+        parse_comparison,
+    ))(input)
+}
+
+fn parse_and(input: &str) -> LedgerParseResult<'_, Expr> {
+    binary_chain(
+        input,
+        parse_not,
+        value(BinaryOperator::And, alt((keyword("and"), tag("&&")))),
+    )
+}
+
+fn parse_expr(input: &str) -> LedgerParseResult<'_, Expr> {
+    binary_chain(
+        input,
+        parse_and,
+        value(BinaryOperator::Or, alt((keyword("or"), tag("||")))),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"2018-10-01 * Shop  ; :receipt:
+  Expenses:Food  $12.50  ; Category: groceries
+  Expenses:Travel  20 EUR
+  Assets:Bank  $-12.50
+  Assets:Cash
+"#;
+
+    fn evaluate(expr: &str, posting: Option<usize>) -> Result<Value, ExprError> {
+        let ledger: Ledger = SOURCE.parse().unwrap();
+        let LedgerItem::Transaction(ref transaction) = ledger.items[0] else {
+            unreachable!()
+        };
+        expr.parse::<Expr>()?.evaluate(transaction, posting)
+    }
+
+    fn bool(expr: &str, posting: usize) -> bool {
+        match evaluate(expr, Some(posting)) {
+            Ok(Value::Bool(value)) => value,
+            result => panic!("{}: {:?}", expr, result),
+        }
+    }
+
+    #[test]
+    fn variables_and_functions() {
+        assert_eq!(
+            evaluate("account", Some(0)),
+            Ok(Value::String("Expenses:Food".to_owned()))
+        );
+        assert_eq!(evaluate("account", None), Ok(Value::Null));
+        assert_eq!(
+            evaluate("payee", None),
+            Ok(Value::String("Shop".to_owned()))
+        );
+        assert_eq!(evaluate("tag(\"category\")", Some(0)), Ok(Value::Null));
+        assert_eq!(
+            evaluate("tag('Category')", Some(0)),
+            Ok(Value::String("groceries".to_owned()))
+        );
+        assert_eq!(
+            evaluate("amount * 2 + {$1}", Some(0)).map(|value| value.to_string()),
+            Ok("$26.00".to_owned())
+        );
+        // The elided amount is inferred.
+        assert_eq!(
+            evaluate("-amount", Some(3)).map(|value| value.to_string()),
+            Ok("20 EUR".to_owned())
+        );
+        assert_eq!(
+            evaluate("quantity(abs({$-3}))", None),
+            Ok(Value::Number(3.into()))
+        );
+    }
+
+    #[test]
+    fn predicates() {
+        assert!(bool("account =~ /^expenses:/ and amount > 10", 0));
+        assert!(bool("account =~ /^expenses:/ and amount > 10", 1));
+        assert!(!bool("account =~ /^expenses:/ and amount > $20", 0));
+        assert!(bool("commodity == \"EUR\" || account !~ /food/", 1));
+        assert!(bool("not has_tag(\"receipt\") or cleared", 2));
+        assert!(bool("!(date < [2018-10-01]) && real && !virtual", 3));
+        assert!(bool("payee =~ /sh.p/ and date == [2018/10/01]", 0));
+        assert!(bool("amount / 2 == {$6.25}", 0));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            evaluate("account =~ /x/ and market(amount)", Some(0)),
+            Err(ExprError::UnsupportedFunction("market".to_owned()))
+        );
+        assert_eq!(
+            evaluate("acount", Some(0)),
+            Err(ExprError::UnknownVariable("acount".to_owned()))
+        );
+        assert_eq!(
+            evaluate("tag()", Some(0)),
+            Err(ExprError::WrongArgumentCount {
+                function: "tag".to_owned(),
+                expected: 1,
+                found: 0
+            })
+        );
+        assert_eq!(
+            evaluate("amount > {$1}", Some(1)),
+            Err(ExprError::Amount(AmountError::CommodityMismatch(
+                "EUR".to_owned(),
+                "$".to_owned()
+            )))
+        );
+        assert_eq!(
+            evaluate("payee + 1", None),
+            Err(ExprError::Type {
+                operation: "+".to_owned(),
+                operands: vec!["string", "number"]
+            })
+        );
+        assert_eq!(
+            evaluate("amount / 0", Some(0)),
+            Err(ExprError::DivisionByZero)
+        );
+        assert_eq!(
+            evaluate("79228162514264337593543950335 * 2", None),
+            Err(ExprError::Overflow)
+        );
+        assert_eq!(
+            evaluate("amount + {$79228162514264337593543950335}", Some(0)),
+            Err(ExprError::Overflow)
+        );
+        assert_eq!(
+            evaluate("{$79228162514264337593543950335} * 2", None),
+            Err(ExprError::Overflow)
+        );
+        assert_eq!(
+            evaluate("{$79228162514264337593543950335} / 0.5", None),
+            Err(ExprError::Overflow)
+        );
+        assert_eq!(
+            evaluate("account", Some(4)),
+            Err(ExprError::PostingIndex {
+                index: 4,
+                postings: 4
+            })
+        );
+        assert!(matches!(
+            evaluate("account =~", Some(0)),
+            Err(ExprError::Syntax(_))
+        ));
+        assert!(matches!(
+            evaluate("(payee", None),
+            Err(ExprError::Syntax(_))
+        ));
+    }
+}
@@ -0,0 +1,151 @@
+use crate::model::*;
+use crate::period::{periodic_transactions, PeriodicTransactionError};
+use chrono::{Days, NaiveDate};
+
+#[non_exhaustive]
+#[derive(Debug, Clone)]
+pub struct ForecastSettings {
+    /// First day of the forecast. Defaults to the day after the latest transaction.
+    pub begin: Option<NaiveDate>,
+    /// Transactions are forecast up to (not including) this day.
+    pub end: NaiveDate,
+}
+
+impl ForecastSettings {
+    pub fn new(end: NaiveDate) -> Self {
+        ForecastSettings { begin: None, end }
+    }
+
+    pub fn with_begin(mut self, begin: NaiveDate) -> Self {
+        self.begin = Some(begin);
+        self
+    }
+}
+
+impl Ledger {
+    /// Transactions of the periodic transactions (`~ PERIOD`) on the first days of their
+    /// periods between the beginning and the end of the forecast, in date order.
+    /// They have `forecast` set.
+    ///
+    /// Without a given beginning, the forecast starts after the latest transaction
+    /// (or is empty if there are no transactions). Relative dates in the period
+    /// expressions (e.g. `this year`) are resolved against the beginning.
+    pub fn forecast(
+        &self,
+        settings: &ForecastSettings,
+    ) -> Result<Vec<Transaction>, PeriodicTransactionError> {
+        let latest = self
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                LedgerItem::Transaction(transaction) => Some(transaction.date),
+                _ => None,
+            })
+            .max();
+        let Some(begin) = settings
+            .begin
+            .or_else(|| latest.and_then(|latest| latest.checked_add_days(Days::new(1))))
+        else {
+            return Ok(Vec::new());
+        };
+
+        let mut transactions = Vec::new();
+        for (_, periodic, period) in periodic_transactions(self, begin)? {
+            for (first, _) in period.periods(begin, settings.end) {
+                transactions.push(periodic.transaction_on(first));
+            }
+        }
+        // The sort is stable, so transactions with the same date stay in file order.
+        transactions.sort_by_key(|transaction| transaction.date);
+        Ok(transactions)
+    }
+
+    /// Returns a copy of the ledger with the [`forecast`](Ledger::forecast) transactions
+    /// added at the end, e.g. to see projected balances with [`AccountTree`](crate::AccountTree)
+    /// or [`register`](crate::register()).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use ledger_parser::{register, ForecastSettings, Ledger, RegisterSettings};
+    ///
+    /// let ledger: Ledger = "~ Monthly\n  Assets:Bank  $100\n  Income\n\n2018-10-15 Employer\n  Assets:Bank  $100\n  Income\n"
+    ///     .parse()
+    ///     .unwrap();
+    /// let settings = ForecastSettings::new(NaiveDate::from_ymd_opt(2019, 1, 1).unwrap());
+    /// let projected = ledger.with_forecast(&settings).unwrap();
+    /// let rows = register(&projected, &RegisterSettings::default().with_query("bank".parse().unwrap()))
+    ///     .unwrap();
+    /// assert_eq!(rows.last().unwrap().total.to_string(), "$300");
+    /// ```
+    pub fn with_forecast(
+        &self,
+        settings: &ForecastSettings,
+    ) -> Result<Ledger, PeriodicTransactionError> {
+        let mut ledger = self.clone();
+        ledger.items.extend(
+            self.forecast(settings)?
+                .into_iter()
+                .map(LedgerItem::Transaction),
+        );
+        Ok(ledger)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"~ Monthly
+  Expenses:Rent  $1,000
+  Assets:Bank
+
+~ Every 2 weeks from 2018-10-05
+  Assets:Bank  $1,500
+  Income:Salary
+
+2018-10-05 Employer
+  Assets:Bank  $1,500
+  Income:Salary
+"#;
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2018, month, day).unwrap()
+    }
+
+    #[test]
+    fn forecast_after_latest_transaction() {
+        let ledger: Ledger = SOURCE.parse().unwrap();
+        let transactions = ledger
+            .forecast(&ForecastSettings::new(date(11, 10)))
+            .unwrap();
+        let summary = transactions
+            .iter()
+            .map(|transaction| format!("{} {}", transaction.date, transaction.postings[0].account))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                "2018-10-19 Assets:Bank",
+                "2018-11-01 Expenses:Rent",
+                "2018-11-02 Assets:Bank",
+            ]
+        );
+        assert!(transactions.iter().all(|transaction| transaction.forecast));
+
+        let settings = ForecastSettings::new(date(11, 10)).with_begin(date(10, 1));
+        assert_eq!(ledger.forecast(&settings).unwrap().len(), 5);
+
+        let projected = ledger.with_forecast(&settings).unwrap();
+        assert_eq!(projected.items.len(), ledger.items.len() + 5);
+        let tree = crate::AccountTree::new(&projected, &Default::default()).unwrap();
+        assert_eq!(
+            tree.get(&"Assets:Bank".parse().unwrap())
+                .unwrap()
+                .total()
+                .to_string(),
+            "$4000"
+        );
+    }
+}
@@ -0,0 +1,539 @@
+use crate::model::*;
+use crate::register::{posting_date, register, RegisterRow, RegisterSettings};
+use crate::{AccountName, Interval, MixedAmount, Query, ReportError};
+use chrono::{Datelike, NaiveDate, Weekday};
+use std::collections::BTreeMap;
+use std::fmt;
+
+///
+/// Length of the periods reports are grouped by.
+///
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Grouping {
+    Daily,
+    Weekly,
+    Monthly,
+    Quarterly,
+    Yearly,
+}
+
+#[non_exhaustive]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct GroupingSettings {
+    pub grouping: Grouping,
+    /// First day of the weeks. Defaults to Monday.
+    pub week_start: Weekday,
+    /// First month (1 to 12) of the years and quarters. Defaults to January.
+    pub fiscal_year_start: u32,
+}
+
+impl GroupingSettings {
+    pub fn new(grouping: Grouping) -> Self {
+        GroupingSettings {
+            grouping,
+            week_start: Weekday::Mon,
+            fiscal_year_start: 1,
+        }
+    }
+
+    pub fn with_week_start(mut self, week_start: Weekday) -> Self {
+        self.week_start = week_start;
+        self
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `month` is not between 1 and 12.
+    pub fn with_fiscal_year_start(mut self, month: u32) -> Self {
+        assert!((1..=12).contains(&month), "invalid month {}", month);
+        self.fiscal_year_start = month;
+        self
+    }
+
+    /// Length of the periods.
+    pub fn interval(&self) -> Interval {
+        match self.grouping {
+            Grouping::Daily => Interval::Days(1),
+            Grouping::Weekly => Interval::Weeks(1),
+            Grouping::Monthly => Interval::Months(1),
+            Grouping::Quarterly => Interval::Months(3),
+            Grouping::Yearly => Interval::Years(1),
+        }
+    }
+
+    /// First day of the period containing `date`.
+    pub fn period_start(&self, date: NaiveDate) -> NaiveDate {
+        self.interval()
+            .align_with(date, self.week_start, self.fiscal_year_start)
+    }
+
+    /// First day of the period after the one starting on `start`.
+    pub fn period_end(&self, start: NaiveDate) -> NaiveDate {
+        self.interval().nth(start, 1).unwrap_or(NaiveDate::MAX)
+    }
+
+    /// Periods (start and exclusive end) overlapping the days from `begin` up to
+    /// (not including) `end`.
+    pub fn periods(&self, begin: NaiveDate, end: NaiveDate) -> Vec<(NaiveDate, NaiveDate)> {
+        let mut periods = Vec::new();
+        let mut start = self.period_start(begin);
+        while start < end {
+            let next = self.period_end(start);
+            periods.push((start, next));
+            start = next;
+        }
+        periods
+    }
+
+    /// Name of the period starting on `start`, e.g. `2018-10` for a month or `2018Q4`
+    /// for a quarter. Quarters and years of fiscal years not starting in January are
+    /// named by their first month.
+    pub fn label(&self, start: NaiveDate) -> String {
+        match self.grouping {
+            Grouping::Daily | Grouping::Weekly => start.to_string(),
+            Grouping::Quarterly if self.fiscal_year_start == 1 => {
+                format!("{}Q{}", start.year(), start.month0() / 3 + 1)
+            }
+            Grouping::Yearly if self.fiscal_year_start == 1 => start.year().to_string(),
+            Grouping::Monthly | Grouping::Quarterly | Grouping::Yearly => {
+                start.format("%Y-%m").to_string()
+            }
+        }
+    }
+}
+
+///
+/// Postings of a single period.
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PeriodGroup {
+    pub begin: NaiveDate,
+    /// Day after the last day of the period.
+    pub end: NaiveDate,
+    /// Register rows of the postings in the period, with totals running from the
+    /// beginning of the period.
+    pub rows: Vec<RegisterRow>,
+}
+
+/// Groups the postings of the [`register`] report by period. Only periods with
+/// postings are listed.
+pub fn group_postings(
+    ledger: &Ledger,
+    register_settings: &RegisterSettings,
+    settings: &GroupingSettings,
+) -> Result<Vec<PeriodGroup>, ReportError> {
+    let mut groups: Vec<PeriodGroup> = Vec::new();
+    for mut row in register(ledger, register_settings)? {
+        let begin = settings.period_start(row.date);
+        let group = match groups.last_mut() {
+            Some(group) if group.begin == begin => group,
+            _ => {
+                groups.push(PeriodGroup {
+                    begin,
+                    end: settings.period_end(begin),
+                    rows: Vec::new(),
+                });
+                groups.last_mut().unwrap()
+            }
+        };
+        row.total = match group.rows.last() {
+            Some(previous) => previous.total.checked_add(&row.amount)?,
+            None => row.amount.clone(),
+        };
+        group.rows.push(row);
+    }
+    Ok(groups)
+}
+
+#[non_exhaustive]
+#[derive(Debug, Clone)]
+pub struct BalanceOverTimeSettings {
+    pub grouping: GroupingSettings,
+    /// Postings before this date are skipped. Defaults to the start of the period of the
+    /// first matching posting.
+    pub begin: Option<NaiveDate>,
+    /// Postings on or after this date are skipped. Defaults to the end of the period of
+    /// the last matching posting.
+    pub end: Option<NaiveDate>,
+    /// Sub-accounts deeper than this are summed into their ancestors.
+    pub depth: Option<usize>,
+    /// Only postings matching the query are summed.
+    pub query: Option<Query>,
+}
+
+impl BalanceOverTimeSettings {
+    pub fn new(grouping: GroupingSettings) -> Self {
+        BalanceOverTimeSettings {
+            grouping,
+            begin: None,
+            end: None,
+            depth: None,
+            query: None,
+        }
+    }
+
+    pub fn with_begin(mut self, begin: NaiveDate) -> Self {
+        self.begin = Some(begin);
+        self
+    }
+
+    pub fn with_end(mut self, end: NaiveDate) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    pub fn with_depth(mut self, depth: usize) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    pub fn with_query(mut self, query: Query) -> Self {
+        self.query = Some(query);
+        self
+    }
+}
+
+///
+/// Column of the balance over time report.
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ReportColumn {
+    pub begin: NaiveDate,
+    /// Day after the last day of the period.
+    pub end: NaiveDate,
+    pub label: String,
+}
+
+///
+/// Account row of the balance over time report.
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BalanceOverTimeRow {
+    pub account: AccountName,
+    /// Sum of the postings to the account in each period, one per column.
+    pub amounts: Vec<MixedAmount>,
+    /// Sum of `amounts`.
+    pub total: MixedAmount,
+}
+
+///
+/// Balance changes of the accounts per period, like `hledger balance --monthly`.
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BalanceOverTime {
+    pub columns: Vec<ReportColumn>,
+    /// Accounts with postings, sorted by name.
+    pub rows: Vec<BalanceOverTimeRow>,
+    /// Sum of all the rows in each period.
+    pub totals: Vec<MixedAmount>,
+}
+
+impl BalanceOverTime {
+    /// Sums the postings of the transactions in the ledger per account and period.
+    /// Elided amounts are counted as inferred by [`Transaction::infer_amounts`],
+    /// posting dates are used where given. Returns an error if an elided amount cannot
+    /// be inferred or the sums overflow.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ledger_parser::{BalanceOverTime, BalanceOverTimeSettings, Grouping, GroupingSettings};
+    ///
+    /// let ledger: ledger_parser::Ledger = "2018-10-01 Shop\n  Expenses:Food  $10\n  Assets\n\n2018-12-01 Shop\n  Expenses:Food  $5\n  Assets\n"
+    ///     .parse()
+    ///     .unwrap();
+    /// let settings = BalanceOverTimeSettings::new(GroupingSettings::new(Grouping::Monthly))
+    ///     .with_query("expenses".parse().unwrap());
+    /// let report = BalanceOverTime::new(&ledger, &settings).unwrap();
+    /// assert_eq!(report.columns.len(), 3);
+    /// assert_eq!(report.rows[0].amounts[1].to_string(), "0");
+    /// assert_eq!(report.rows[0].total.to_string(), "$15");
+    /// ```
+    pub fn new(ledger: &Ledger, settings: &BalanceOverTimeSettings) -> Result<Self, ReportError> {
+        let mut postings = Vec::new();
+        for (item, ledger_item) in ledger.items.iter().enumerate() {
+            let LedgerItem::Transaction(transaction) = ledger_item else {
+                continue;
+            };
+            for (index, posting) in transaction.postings.iter().enumerate() {
+                let date = posting_date(transaction, posting, false);
+                if settings.begin.is_some_and(|begin| date < begin)
+                    || settings.end.is_some_and(|end| date >= end)
+                    || settings
+                        .query
+                        .as_ref()
+                        .is_some_and(|query| !query.matches(transaction, index))
+                {
+                    continue;
+                }
+                let amount = transaction.posting_amount(item, index)?;
+                let account = match settings.depth {
+                    Some(depth) if posting.account.depth() > depth => AccountName::new(
+                        posting
+                            .account
+                            .segments()
+                            .take(depth)
+                            .collect::<Vec<_>>()
+                            .join(":"),
+                    )
+                    .expect("segments of a valid account name"),
+                    _ => posting.account.clone(),
+                };
+                postings.push((date, account, amount));
+            }
+        }
+
+        let grouping = &settings.grouping;
+        let begin = settings
+            .begin
+            .or_else(|| postings.iter().map(|(date, _, _)| *date).min());
+        let end = settings.end.or_else(|| {
+            postings
+                .iter()
+                .map(|(date, _, _)| grouping.period_end(grouping.period_start(*date)))
+                .max()
+        });
+        let columns = match (begin, end) {
+            (Some(begin), Some(end)) => grouping
+                .periods(begin, end)
+                .into_iter()
+                .map(|(begin, end)| ReportColumn {
+                    begin,
+                    end,
+                    label: grouping.label(begin),
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        let mut accounts: BTreeMap<AccountName, Vec<MixedAmount>> = BTreeMap::new();
+        let mut totals = vec![MixedAmount::new(); columns.len()];
+        for (date, account, amount) in postings {
+            let column = columns.partition_point(|column| column.end <= date);
+            let sum = &mut accounts
+                .entry(account)
+                .or_insert_with(|| vec![MixedAmount::new(); columns.len()])[column];
+            *sum = sum.checked_add(&amount)?;
+            totals[column] = totals[column].checked_add(&amount)?;
+        }
+        let rows = accounts
+            .into_iter()
+            .map(|(account, amounts)| {
+                let total = amounts
+                    .iter()
+                    .try_fold(MixedAmount::new(), |total, amount| {
+                        total.checked_add(amount)
+                    })?;
+                Ok(BalanceOverTimeRow {
+                    account,
+                    total,
+                    amounts,
+                })
+            })
+            .collect::<Result<_, ReportError>>()?;
+
+        Ok(BalanceOverTime {
+            columns,
+            rows,
+            totals,
+        })
+    }
+}
+
+/// Writes the report as a table like `hledger balance --monthly`: a row per account
+/// with a right-aligned column per period, and the totals at the end.
+impl fmt::Display for BalanceOverTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut table = vec![(
+            String::new(),
+            self.columns
+                .iter()
+                .map(|column| column.label.clone())
+                .collect::<Vec<_>>(),
+        )];
+        for row in &self.rows {
+            table.push((
+                row.account.to_string(),
+                row.amounts.iter().map(MixedAmount::to_string).collect(),
+            ));
+        }
+        table.push((
+            String::new(),
+            self.totals.iter().map(MixedAmount::to_string).collect(),
+        ));
+
+        let name_width = table.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+        let widths = (0..self.columns.len())
+            .map(|column| {
+                table
+                    .iter()
+                    .map(|(_, cells)| cells[column].chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect::<Vec<_>>();
+        let separator = |f: &mut fmt::Formatter, line: &str, cross: &str| {
+            let cells_width = widths.iter().map(|width| width + 1).sum::<usize>();
+            writeln!(
+                f,
+                "{}{}{}",
+                line.repeat(name_width + 1),
+                cross,
+                line.repeat(cells_width)
+            )
+        };
+
+        let last = table.len() - 1;
+        for (index, (name, cells)) in table.iter().enumerate() {
+            if index == last {
+                separator(f, "-", "++")?;
+            }
+            write!(f, "{:<width$} ||", name, width = name_width)?;
+            for (cell, width) in cells.iter().zip(&widths) {
+                write!(f, " {:>width$}", cell, width = width)?;
+            }
+            writeln!(f)?;
+            if index == 0 {
+                separator(f, "=", "++")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"2018-09-30 Shop
+  Expenses:Food:Groceries  $10
+  Assets:Bank
+
+2018-10-01 Shop
+  Expenses:Food  $5
+  Expenses:Rent  $100
+  Assets:Bank
+
+2018-12-24 Shop
+  Expenses:Food  $20
+  Assets:Cash
+"#;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn period_boundaries() {
+        let day = date(2018, 10, 3); // Wednesday
+        let weekly = GroupingSettings::new(Grouping::Weekly);
+        assert_eq!(weekly.period_start(day), date(2018, 10, 1));
+        let weekly = weekly.with_week_start(Weekday::Sun);
+        assert_eq!(weekly.period_start(day), date(2018, 9, 30));
+        assert_eq!(weekly.period_end(date(2018, 9, 30)), date(2018, 10, 7));
+
+        let quarterly = GroupingSettings::new(Grouping::Quarterly);
+        assert_eq!(quarterly.period_start(day), date(2018, 10, 1));
+        assert_eq!(quarterly.label(date(2018, 10, 1)), "2018Q4");
+        let quarterly = quarterly.with_fiscal_year_start(4);
+        assert_eq!(quarterly.period_start(date(2018, 9, 30)), date(2018, 7, 1));
+        assert_eq!(quarterly.period_start(date(2018, 2, 1)), date(2018, 1, 1));
+
+        let yearly = GroupingSettings::new(Grouping::Yearly).with_fiscal_year_start(4);
+        assert_eq!(yearly.period_start(date(2018, 3, 31)), date(2017, 4, 1));
+        assert_eq!(yearly.period_start(date(2018, 4, 1)), date(2018, 4, 1));
+        assert_eq!(yearly.period_end(date(2018, 4, 1)), date(2019, 4, 1));
+        assert_eq!(yearly.label(date(2018, 4, 1)), "2018-04");
+
+        assert_eq!(
+            GroupingSettings::new(Grouping::Monthly).periods(date(2018, 9, 15), date(2018, 11, 1)),
+            vec![
+                (date(2018, 9, 1), date(2018, 10, 1)),
+                (date(2018, 10, 1), date(2018, 11, 1))
+            ]
+        );
+    }
+
+    #[test]
+    fn grouped_postings() {
+        let ledger: Ledger = SOURCE.parse().unwrap();
+        let groups = group_postings(
+            &ledger,
+            &RegisterSettings::default().with_query("expenses".parse().unwrap()),
+            &GroupingSettings::new(Grouping::Quarterly),
+        )
+        .unwrap();
+        let summary = groups
+            .iter()
+            .map(|group| {
+                let totals = group
+                    .rows
+                    .iter()
+                    .map(|row| row.total.to_string())
+                    .collect::<Vec<_>>();
+                format!("{} {}", group.begin, totals.join(" "))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(summary, vec!["2018-07-01 $10", "2018-10-01 $5 $105 $125"]);
+        assert_eq!(groups[1].end, date(2019, 1, 1));
+    }
+
+    #[test]
+    fn balance_over_time() {
+        let ledger: Ledger = SOURCE.parse().unwrap();
+        let settings = BalanceOverTimeSettings::new(GroupingSettings::new(Grouping::Monthly))
+            .with_depth(2)
+            .with_query("expenses".parse().unwrap());
+        let report = BalanceOverTime::new(&ledger, &settings).unwrap();
+        assert_eq!(
+            report.to_string(),
+            r#"              || 2018-09 2018-10 2018-11 2018-12
+==============++================================
+Expenses:Food ||     $10      $5       0     $20
+Expenses:Rent ||       0    $100       0       0
+--------------++--------------------------------
+              ||     $10    $105       0     $20
+"#
+        );
+        assert_eq!(report.rows[0].total.to_string(), "$35");
+
+        let settings = BalanceOverTimeSettings::new(GroupingSettings::new(Grouping::Quarterly))
+            .with_begin(date(2018, 10, 1))
+            .with_end(date(2019, 1, 1));
+        let report = BalanceOverTime::new(&ledger, &settings).unwrap();
+        assert_eq!(report.columns.len(), 1);
+        assert_eq!(report.columns[0].label, "2018Q4");
+        let accounts = report
+            .rows
+            .iter()
+            .map(|row| format!("{} {}", row.account, row.amounts[0]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            accounts,
+            vec![
+                "Assets:Bank $-105",
+                "Assets:Cash $-20",
+                "Expenses:Food $25",
+                "Expenses:Rent $100"
+            ]
+        );
+        assert!(report.totals[0].is_zero());
+    }
+
+    #[test]
+    fn uninferable_amounts() {
+        let ledger: Ledger = format!(
+            "{}\n2018-12-20 Shop\n  Expenses:Food  $1\n  Assets\n  Income\n",
+            SOURCE
+        )
+        .parse()
+        .unwrap();
+        let settings = BalanceOverTimeSettings::new(GroupingSettings::new(Grouping::Monthly));
+        let error = BalanceOverTime::new(&ledger, &settings).unwrap_err();
+        assert!(matches!(error, ReportError::Inference { posting: 1, .. }));
+
+        // Postings not matching the query are not summed, so they need no amount.
+        let settings = settings.with_query("expenses".parse().unwrap());
+        assert!(BalanceOverTime::new(&ledger, &settings).is_ok());
+    }
+}
@@ -0,0 +1,429 @@
+use crate::model::*;
+use crate::ParseError;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+///
+/// Access to the files for [`resolve_includes`].
+///
+/// Implemented for the file system ([`FsLoader`]) and for in-memory maps of paths
+/// to file contents.
+///
+pub trait FileLoader {
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+
+    /// Paths of the files and directories directly inside `dir`.
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<PathBuf>>;
+
+    /// Directory `~` is expanded to.
+    fn home_dir(&self) -> Option<PathBuf> {
+        std::env::var_os("HOME").map(PathBuf::from)
+    }
+}
+
+/// Loads files from the file system.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FsLoader;
+
+impl FileLoader for FsLoader {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        std::fs::read_to_string(path)
+    }
+
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        std::fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect()
+    }
+}
+
+impl FileLoader for HashMap<PathBuf, String> {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        self.get(path).cloned().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{}: no such file", path.display()),
+            )
+        })
+    }
+
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut entries = self
+            .keys()
+            .filter_map(|path| path.strip_prefix(dir).ok())
+            .filter_map(|rest| rest.components().next())
+            .map(|name| dir.join(name))
+            .collect::<Vec<_>>();
+        entries.sort();
+        entries.dedup();
+        Ok(entries)
+    }
+}
+
+///
+/// Ledger merged from a file and all the files it includes.
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ResolvedLedger {
+    /// Items of all the files, with the `include` directives replaced by the included items.
+    pub ledger: Ledger,
+    /// Loaded files, in the order they were first loaded.
+    pub files: Vec<PathBuf>,
+    /// Index into `files` of the file each item of `ledger` comes from.
+    /// Spans of the items are positions in that file.
+    pub item_files: Vec<usize>,
+}
+
+impl ResolvedLedger {
+    /// File the item at `index` comes from.
+    pub fn file_of(&self, index: usize) -> &Path {
+        &self.files[self.item_files[index]]
+    }
+}
+
+#[derive(Debug)]
+pub enum IncludeError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        error: ParseError,
+    },
+    /// The file includes itself (directly or indirectly). Contains the chain of includes.
+    Cycle(Vec<PathBuf>),
+    /// Include pattern with wildcards in the file at `path` matching no files.
+    NoMatches {
+        path: PathBuf,
+        pattern: PathBuf,
+    },
+}
+
+impl fmt::Display for IncludeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IncludeError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            IncludeError::Parse { path, error } => write!(f, "{}: {}", path.display(), error),
+            IncludeError::Cycle(paths) => {
+                write!(f, "include cycle: ")?;
+                for (i, path) in paths.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " -> ")?;
+                    }
+                    write!(f, "{}", path.display())?;
+                }
+                Ok(())
+            }
+            IncludeError::NoMatches { path, pattern } => write!(
+                f,
+                "{}: no files match {}",
+                path.display(),
+                pattern.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IncludeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            IncludeError::Io { error, .. } => Some(error),
+            IncludeError::Parse { error, .. } => Some(error),
+            IncludeError::Cycle(_) | IncludeError::NoMatches { .. } => None,
+        }
+    }
+}
+
+/// Parses the file at `path` and, recursively, all the files it includes.
+///
+/// Included paths are relative to the directory of the including file, may start
+/// with `~` (the home directory of the loader) and may contain `*` and `?` wildcards
+/// (all the matching files are included, in alphabetical order, and a pattern matching
+/// no files is an error).
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashMap;
+/// use std::path::PathBuf;
+///
+/// let files = HashMap::from([
+///     (PathBuf::from("/main.ledger"), "include prices.ledger\n".to_owned()),
+///     (PathBuf::from("/prices.ledger"), "P 2017-11-12 12:00:00 mBH 5.00 PLN\n".to_owned()),
+/// ]);
+/// let resolved = ledger_parser::resolve_includes("/main.ledger", &files).unwrap();
+/// assert_eq!(resolved.file_of(0), PathBuf::from("/prices.ledger"));
+/// ```
+pub fn resolve_includes(
+    path: impl AsRef<Path>,
+    loader: &impl FileLoader,
+) -> Result<ResolvedLedger, IncludeError> {
+    let mut resolver = Resolver {
+        loader,
+        resolved: ResolvedLedger {
+            ledger: Ledger { items: Vec::new() },
+            files: Vec::new(),
+            item_files: Vec::new(),
+        },
+        stack: Vec::new(),
+    };
+    resolver.resolve(&normalize(path.as_ref()))?;
+    Ok(resolver.resolved)
+}
+
+struct Resolver<'l, L> {
+    loader: &'l L,
+    resolved: ResolvedLedger,
+    /// Files being resolved, to detect cycles.
+    stack: Vec<PathBuf>,
+}
+
+impl<L: FileLoader> Resolver<'_, L> {
+    fn resolve(&mut self, path: &Path) -> Result<(), IncludeError> {
+        if self.stack.iter().any(|included| included == path) {
+            let mut cycle = self.stack.clone();
+            cycle.push(path.to_owned());
+            return Err(IncludeError::Cycle(cycle));
+        }
+
+        let io_error = |error| IncludeError::Io {
+            path: path.to_owned(),
+            error,
+        };
+        let source = self.loader.read_to_string(path).map_err(io_error)?;
+        let ledger: Ledger = source.parse().map_err(|error| IncludeError::Parse {
+            path: path.to_owned(),
+            error,
+        })?;
+
+        let file = match self.resolved.files.iter().position(|file| file == path) {
+            Some(file) => file,
+            None => {
+                self.resolved.files.push(path.to_owned());
+                self.resolved.files.len() - 1
+            }
+        };
+        let dir = path.parent().unwrap_or(Path::new(""));
+
+        self.stack.push(path.to_owned());
+        for item in ledger.items {
+            match item {
+                LedgerItem::Include(include) => {
+                    let pattern = self.include_path(dir, &include.file);
+                    let included = self.expand(&pattern)?;
+                    if included.is_empty() {
+                        return Err(IncludeError::NoMatches {
+                            path: path.to_owned(),
+                            pattern,
+                        });
+                    }
+                    for included in included {
+                        self.resolve(&included)?;
+                    }
+                }
+                item => {
+                    self.resolved.ledger.items.push(item);
+                    self.resolved.item_files.push(file);
+                }
+            }
+        }
+        self.stack.pop();
+        Ok(())
+    }
+
+    fn include_path(&self, dir: &Path, include: &str) -> PathBuf {
+        let home = || self.loader.home_dir().unwrap_or_default();
+        let path = if include == "~" {
+            home()
+        } else if let Some(rest) = include.strip_prefix("~/") {
+            home().join(rest)
+        } else {
+            dir.join(include)
+        };
+        normalize(&path)
+    }
+
+    /// Paths matching `pattern` (just the pattern itself if it has no wildcards).
+    fn expand(&self, pattern: &Path) -> Result<Vec<PathBuf>, IncludeError> {
+        let mut paths = vec![PathBuf::new()];
+        for component in pattern.components() {
+            let name = component.as_os_str().to_string_lossy();
+            if !name.contains(['*', '?']) {
+                for path in &mut paths {
+                    path.push(component);
+                }
+                continue;
+            }
+
+            let mut matches = Vec::new();
+            for dir in &paths {
+                let entries = self
+                    .loader
+                    .read_dir(dir)
+                    .map_err(|error| IncludeError::Io {
+                        path: dir.clone(),
+                        error,
+                    })?;
+                for entry in entries {
+                    let matched = entry.file_name().is_some_and(|file_name| {
+                        wildcard_match(&name, &file_name.to_string_lossy())
+                    });
+                    if matched {
+                        matches.push(entry);
+                    }
+                }
+            }
+            matches.sort();
+            paths = matches;
+        }
+        Ok(paths)
+    }
+}
+
+/// Removes `.` and `..` components without accessing the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push(component);
+                }
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Matches `name` against a pattern with `*` (any characters) and `?` (one character).
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    // matched[j]: the pattern read so far matches the first j characters of the name
+    let mut matched = vec![false; name.len() + 1];
+    matched[0] = true;
+    for &p in &pattern {
+        let mut next = vec![false; name.len() + 1];
+        for j in 0..=name.len() {
+            next[j] = match p {
+                '*' => matched[j] || (j > 0 && next[j - 1]),
+                '?' => j > 0 && matched[j - 1],
+                c => j > 0 && matched[j - 1] && name[j - 1] == c,
+            };
+        }
+        matched = next;
+    }
+    matched[name.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Files(HashMap<PathBuf, String>);
+
+    impl FileLoader for Files {
+        fn read_to_string(&self, path: &Path) -> io::Result<String> {
+            self.0.read_to_string(path)
+        }
+
+        fn read_dir(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+            if dir == Path::new("/locked") {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "permission denied",
+                ));
+            }
+            self.0.read_dir(dir)
+        }
+
+        fn home_dir(&self) -> Option<PathBuf> {
+            Some(PathBuf::from("/home/user"))
+        }
+    }
+
+    fn files(files: &[(&str, &str)]) -> Files {
+        Files(
+            files
+                .iter()
+                .map(|(path, source)| (PathBuf::from(path), source.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn includes_are_merged() {
+        let loader = files(&[
+            (
+                "/ledger/main.ledger",
+                "; main\ninclude accounts/*.ledger\ninclude ~/common.ledger\n; end\n",
+            ),
+            ("/ledger/accounts/b.ledger", "; b\n"),
+            (
+                "/ledger/accounts/a.ledger",
+                "; a\ninclude ../../shared/x.ledger\n",
+            ),
+            ("/ledger/accounts/a.txt", "; not included\n"),
+            ("/shared/x.ledger", "; x\n"),
+            ("/home/user/common.ledger", "; common\n"),
+        ]);
+        let resolved = resolve_includes("/ledger/./main.ledger", &loader).unwrap();
+
+        let comments = resolved
+            .ledger
+            .items
+            .iter()
+            .map(|item| match item {
+                LedgerItem::LineComment(comment) => comment.comment.as_str(),
+                _ => panic!("expected comment"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(comments, vec!["main", "a", "x", "b", "common", "end"]);
+        assert_eq!(resolved.file_of(2), Path::new("/shared/x.ledger"));
+        assert_eq!(resolved.file_of(5), Path::new("/ledger/main.ledger"));
+        assert_eq!(resolved.files.len(), 5);
+    }
+
+    #[test]
+    fn errors() {
+        let loader = files(&[
+            ("/a.ledger", "include b.ledger\n"),
+            ("/b.ledger", "include a.ledger\n"),
+            ("/c.ledger", "include missing.ledger\n"),
+            ("/d.ledger", "include locked/*.ledger\n"),
+            ("/e.ledger", "include missing/*.ledger\n"),
+        ]);
+        let error = resolve_includes("/a.ledger", &loader).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "include cycle: /a.ledger -> /b.ledger -> /a.ledger"
+        );
+        assert!(matches!(
+            resolve_includes("/c.ledger", &loader),
+            Err(IncludeError::Io { ref path, .. }) if path == Path::new("/missing.ledger")
+        ));
+        assert!(matches!(
+            resolve_includes("/d.ledger", &loader),
+            Err(IncludeError::Io { ref path, .. }) if path == Path::new("/locked")
+        ));
+        assert_eq!(
+            resolve_includes("/e.ledger", &loader)
+                .unwrap_err()
+                .to_string(),
+            "/e.ledger: no files match /missing/*.ledger"
+        );
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(wildcard_match("*.ledger", "a.ledger"));
+        assert!(wildcard_match("20??-*", "2018-10"));
+        assert!(!wildcard_match("*.ledger", "a.txt"));
+        assert!(!wildcard_match("?", ""));
+    }
+}
@@ -0,0 +1,278 @@
+use crate::model::*;
+use crate::parser;
+use crate::ParseError;
+use nom::Finish;
+use std::ops::Range;
+
+///
+/// Ledger for editor integrations, which is kept up to date with text edits
+/// by re-parsing only the top-level items affected by them.
+///
+/// The source is kept as a list of parts which can be parsed separately (each starts
+/// with a line which always begins a new top-level item). A part which fails to parse
+/// contributes no items to the ledger; its error is available from `errors()`.
+///
+pub struct IncrementalLedger {
+    source: String,
+    ledger: Ledger,
+    chunks: Vec<Chunk>,
+}
+
+struct Chunk {
+    bytes: Range<usize>,
+    first_line: usize,
+    items: usize,
+    error: Option<ParseError>,
+}
+
+///
+/// Items changed by an edit.
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ItemChanges {
+    /// Indices of the replaced items in the previous ledger.
+    pub removed: Range<usize>,
+    /// Indices of the re-parsed items in the updated ledger.
+    pub inserted: Range<usize>,
+}
+
+impl IncrementalLedger {
+    pub fn new(source: String) -> Self {
+        let (chunks, items) = parse_region(&source, 0..source.len(), 1);
+        IncrementalLedger {
+            source,
+            ledger: Ledger { items },
+            chunks,
+        }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn ledger(&self) -> &Ledger {
+        &self.ledger
+    }
+
+    /// Errors of the parts of the source which could not be parsed.
+    pub fn errors(&self) -> impl Iterator<Item = &ParseError> {
+        self.chunks.iter().filter_map(|chunk| chunk.error.as_ref())
+    }
+
+    /// Replaces `range` of the source with `text` and re-parses the affected items.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is out of bounds or does not lie on char boundaries.
+    pub fn edit(&mut self, range: Range<usize>, text: &str) -> ItemChanges {
+        // The part before the edit is included too, as the edited line may stop
+        // being the start of a new item and be attached to it.
+        let first = self
+            .chunks
+            .partition_point(|chunk| chunk.bytes.end <= range.start.saturating_sub(1));
+        let last = self
+            .chunks
+            .partition_point(|chunk| chunk.bytes.end <= range.end)
+            .min(self.chunks.len().saturating_sub(1));
+        let affected = first..(last + 1).min(self.chunks.len());
+
+        let region = match (self.chunks.get(affected.start), self.chunks.get(last)) {
+            (Some(first), Some(last)) => first.bytes.start..last.bytes.end,
+            _ => 0..self.source.len(),
+        };
+        let first_line = self.chunks.get(first).map_or(1, |chunk| chunk.first_line);
+        let old_lines = self.source[region.clone()].matches('\n').count();
+
+        self.source.replace_range(range.clone(), text);
+        let new_end = region.end + text.len() - range.len();
+        let new_lines = self.source[region.start..new_end].matches('\n').count();
+        let (chunks, items) = parse_region(&self.source, region.start..new_end, first_line);
+
+        let first_item = self.chunks[..affected.start]
+            .iter()
+            .map(|chunk| chunk.items)
+            .sum::<usize>();
+        let removed = first_item
+            ..first_item
+                + self.chunks[affected.clone()]
+                    .iter()
+                    .map(|chunk| chunk.items)
+                    .sum::<usize>();
+        let inserted = first_item..first_item + items.len();
+
+        self.ledger.items.splice(removed.clone(), items);
+        let next_chunk = affected.start + chunks.len();
+        self.chunks.splice(affected, chunks);
+
+        // Move the following items by the change of the source length.
+        let shift = |offset: usize, old: usize, new: usize| offset + new - old;
+        for chunk in &mut self.chunks[next_chunk..] {
+            chunk.bytes = shift(chunk.bytes.start, region.end, new_end)
+                ..shift(chunk.bytes.end, region.end, new_end);
+            chunk.first_line = shift(chunk.first_line, old_lines, new_lines);
+            if chunk.error.is_some() && new_lines != old_lines {
+                // The error names lines of the source, so it is rendered again.
+                chunk.error = parse_chunk(&self.source, chunk.bytes.clone(), chunk.first_line)
+                    .0
+                    .error;
+            }
+        }
+        for item in &mut self.ledger.items[inserted.end..] {
+            item.for_each_span_mut(&mut |span| {
+                span.bytes = shift(span.bytes.start, region.end, new_end)
+                    ..shift(span.bytes.end, region.end, new_end);
+                span.lines = shift(span.lines.start, old_lines, new_lines)
+                    ..shift(span.lines.end, old_lines, new_lines);
+            });
+        }
+
+        ItemChanges { removed, inserted }
+    }
+}
+
+/// Parses `region` of the source, which starts at line `first_line`.
+fn parse_region(
+    source: &str,
+    region: Range<usize>,
+    mut first_line: usize,
+) -> (Vec<Chunk>, Vec<LedgerItem>) {
+    let text = &source[region.clone()];
+    let starts = std::iter::once(0)
+        .chain(parser::item_starts(text))
+        .filter(|&start| start < text.len())
+        .collect::<Vec<_>>();
+    let ends = starts.iter().skip(1).copied().chain(Some(text.len()));
+
+    let mut chunks = Vec::with_capacity(starts.len());
+    let mut items = Vec::new();
+    for (&start, end) in starts.iter().zip(ends) {
+        let bytes = region.start + start..region.start + end;
+        let (chunk, chunk_items) = parse_chunk(source, bytes, first_line);
+        first_line += text[start..end].matches('\n').count();
+        chunks.push(chunk);
+        items.extend(chunk_items);
+    }
+    (chunks, items)
+}
+
+/// Parses a single part of the source, which starts at line `first_line`.
+fn parse_chunk(source: &str, bytes: Range<usize>, first_line: usize) -> (Chunk, Vec<LedgerItem>) {
+    let chunk_text = &source[bytes.clone()];
+    let mut chunk = Chunk {
+        bytes: bytes.clone(),
+        first_line,
+        items: 0,
+        error: None,
+    };
+    let mut items = Vec::new();
+    match parser::parse_ledger_at(chunk_text, bytes.start, first_line).finish() {
+        Ok((_, ledger)) => {
+            chunk.items = ledger.items.len();
+            items.extend(ledger.items.into_iter().map(|item| item.into_owned()));
+        }
+        Err(error) => {
+            chunk.error = Some(ParseError::String(parser::convert_error_at(
+                chunk_text, error, first_line,
+            )))
+        }
+    }
+    (chunk, items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"; Example 1
+
+2018-10-01 Payee 1
+  TEST:ABC  $1.20
+  TEST:DEF
+
+2018-10-02 Payee 2
+  TEST:ABC  $2.20
+  TEST:DEF
+P 2017-11-12 12:00:00 mBH 5.00 PLN
+"#;
+
+    fn check_same_as_full_parse(ledger: &IncrementalLedger) {
+        let expected: Ledger = ledger.source().parse().unwrap();
+        assert_eq!(ledger.ledger(), &expected);
+        let spans = |ledger: &Ledger| {
+            let mut spans = Vec::new();
+            for mut item in ledger.items.clone() {
+                item.for_each_span_mut(&mut |span| spans.push(span.clone()));
+            }
+            spans
+        };
+        assert_eq!(spans(ledger.ledger()), spans(&expected));
+    }
+
+    #[test]
+    fn edit_inside_transaction() {
+        let mut ledger = IncrementalLedger::new(SOURCE.to_owned());
+        let pos = SOURCE.find("$1.20").unwrap();
+        let changes = ledger.edit(pos + 1..pos + 2, "10");
+        assert_eq!(
+            changes,
+            ItemChanges {
+                removed: 2..4,
+                inserted: 2..4
+            }
+        );
+        check_same_as_full_parse(&ledger);
+        assert!(ledger.source().contains("$10.20"));
+    }
+
+    #[test]
+    fn edits_changing_item_boundaries() {
+        let mut ledger = IncrementalLedger::new(SOURCE.to_owned());
+
+        // the empty line between the transactions is removed
+        let pos = SOURCE.find("\n2018-10-02").unwrap();
+        ledger.edit(pos..pos + 1, "");
+        check_same_as_full_parse(&ledger);
+
+        // a new transaction is typed at the end, line by line
+        let end = ledger.source().len();
+        ledger.edit(end..end, "2018-10-03 Payee 3\n");
+        assert_eq!(ledger.errors().count(), 1);
+        let end = ledger.source().len();
+        ledger.edit(end..end, "  TEST:ABC  $3\n  TEST:DEF\n");
+        assert_eq!(ledger.errors().count(), 0);
+        check_same_as_full_parse(&ledger);
+
+        // the commodity price becomes a posting comment by indenting it
+        let pos = ledger.source().find("P 2017").unwrap();
+        let changes = ledger.edit(pos..pos, "  ;");
+        check_same_as_full_parse(&ledger);
+        assert_eq!(changes.inserted.len() + 1, changes.removed.len());
+
+        // everything is removed
+        let end = ledger.source().len();
+        ledger.edit(0..end, "");
+        assert!(ledger.ledger().items.is_empty());
+        ledger.edit(0..0, SOURCE);
+        check_same_as_full_parse(&ledger);
+    }
+
+    #[test]
+    fn errors_follow_line_shifts() {
+        let source = SOURCE.replace("  TEST:ABC  $2.20\n  TEST:DEF\n", "");
+        let mut ledger = IncrementalLedger::new(source);
+        let errors = |ledger: &IncrementalLedger| {
+            ledger.errors().map(ToString::to_string).collect::<Vec<_>>()
+        };
+        let before = errors(&ledger);
+        assert_eq!(before.len(), 1);
+        assert!(before[0].contains("line 7"), "{}", before[0]);
+
+        ledger.edit(0..0, "; Inserted\n; lines\n");
+        let after = errors(&ledger);
+        assert_ne!(after, before);
+        assert_eq!(
+            after,
+            errors(&IncrementalLedger::new(ledger.source().to_owned()))
+        );
+    }
+}
@@ -0,0 +1,436 @@
+use crate::model::*;
+use crate::register::posting_date;
+use crate::{AccountName, AmountError};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use std::fmt;
+
+///
+/// How sales are matched to the open lots, unless the posting selects a lot
+/// with a lot price (`-10 AAPL {$50} @ $60`).
+///
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum LotMatching {
+    /// Oldest lots first.
+    #[default]
+    Fifo,
+    /// Newest lots first.
+    Lifo,
+    /// All open lots are merged into one with their average cost.
+    Average,
+}
+
+///
+/// Open (part of a) purchase.
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Lot {
+    pub date: NaiveDate,
+    /// Remaining quantity.
+    pub quantity: Decimal,
+    /// Cost of one unit.
+    pub cost: Amount,
+    /// Cost of the remaining quantity. Exact for lots bought at a total price
+    /// (`{{}}`/`@@`), where `cost * quantity` may not be.
+    pub total_cost: Amount,
+    /// Index of the transaction in `Ledger::items` and of the posting which opened the lot.
+    pub item: usize,
+    pub posting: usize,
+}
+
+///
+/// Sale of a commodity, with the realized gain (or loss, if negative).
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Disposal {
+    pub date: NaiveDate,
+    pub account: AccountName,
+    pub item: usize,
+    pub posting: usize,
+    /// Sold amount (positive).
+    pub amount: Amount,
+    /// Lots the sale was matched to, with the quantities taken from them.
+    pub lots: Vec<Lot>,
+    pub cost_basis: Amount,
+    /// Sale price (`@`/`@@`), `None` if the posting has no price.
+    pub proceeds: Option<Amount>,
+    /// `proceeds - cost_basis`.
+    pub gain: Option<Amount>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum InventoryError {
+    /// More was sold than there is in the (selected) open lots.
+    InsufficientQuantity {
+        item: usize,
+        posting: usize,
+        requested: Decimal,
+        available: Decimal,
+    },
+    /// Costs or proceeds of the lots are in different commodities, or overflow.
+    Amount {
+        item: usize,
+        posting: usize,
+        error: AmountError,
+    },
+}
+
+impl fmt::Display for InventoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InventoryError::InsufficientQuantity {
+                item,
+                posting,
+                requested,
+                available,
+            } => write!(
+                f,
+                "item {} posting {}: cannot sell {}, only {} in open lots",
+                item, posting, requested, available
+            ),
+            InventoryError::Amount {
+                item,
+                posting,
+                error,
+            } => write!(f, "item {} posting {}: {}", item, posting, error),
+        }
+    }
+}
+
+impl std::error::Error for InventoryError {}
+
+///
+/// Open lots per account and commodity, and the sales made from them.
+///
+/// Postings with a positive amount and a lot price (or, without one, a price) open
+/// lots at that cost. Postings with a negative amount to an account holding lots
+/// of the commodity close them. Other postings are not tracked.
+///
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Inventory {
+    lots: BTreeMap<(AccountName, String), Vec<Lot>>,
+    disposals: Vec<Disposal>,
+}
+
+impl Inventory {
+    /// Goes through the postings in date order (using posting dates where given).
+    pub fn new(ledger: &Ledger, matching: LotMatching) -> Result<Self, InventoryError> {
+        let mut postings = Vec::new();
+        for (item, ledger_item) in ledger.items.iter().enumerate() {
+            if let LedgerItem::Transaction(transaction) = ledger_item {
+                for (index, posting) in transaction.postings.iter().enumerate() {
+                    if let Some(ref amount) = posting.amount {
+                        let date = posting_date(transaction, posting, false);
+                        postings.push((date, item, index, posting, amount));
+                    }
+                }
+            }
+        }
+        // The sort is stable, so postings with the same date stay in file order.
+        postings.sort_by_key(|&(date, ..)| date);
+
+        let mut inventory = Inventory::default();
+        for (date, item, index, posting, amount) in postings {
+            let key = (
+                posting.account.clone(),
+                amount.amount.commodity.name.clone(),
+            );
+            let quantity = amount.amount.quantity;
+            if quantity.is_sign_positive() && !quantity.is_zero() {
+                let prices = prices(
+                    amount.lot_price.as_ref().or(amount.price.as_ref()),
+                    quantity,
+                )
+                .map_err(|error| InventoryError::Amount {
+                    item,
+                    posting: index,
+                    error,
+                })?;
+                if let Some((cost, total_cost)) = prices {
+                    inventory.lots.entry(key).or_default().push(Lot {
+                        date,
+                        quantity,
+                        cost,
+                        total_cost,
+                        item,
+                        posting: index,
+                    });
+                }
+            } else if quantity.is_sign_negative() && inventory.lots.contains_key(&key) {
+                let lots = inventory.lots.get_mut(&key).unwrap();
+                let disposal = dispose(lots, date, item, index, posting, amount, matching)?;
+                inventory.disposals.push(disposal);
+                if lots.is_empty() {
+                    inventory.lots.remove(&key);
+                }
+            }
+        }
+        Ok(inventory)
+    }
+
+    /// Open lots of `commodity_name` in `account`, oldest first.
+    pub fn lots(&self, account: &AccountName, commodity_name: &str) -> &[Lot] {
+        self.lots
+            .get(&(account.clone(), commodity_name.to_owned()))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// All open lots, by account and commodity name.
+    pub fn open_lots(&self) -> impl Iterator<Item = (&AccountName, &str, &[Lot])> {
+        self.lots.iter().map(|((account, commodity_name), lots)| {
+            (account, commodity_name.as_str(), lots.as_slice())
+        })
+    }
+
+    /// Sales in date order.
+    pub fn disposals(&self) -> &[Disposal] {
+        &self.disposals
+    }
+}
+
+/// Price of one unit and of the whole posting of `quantity`.
+fn prices(
+    price: Option<&Price>,
+    quantity: Decimal,
+) -> Result<Option<(Amount, Amount)>, AmountError> {
+    let quantity = quantity.abs();
+    let prices = match price {
+        Some(Price::Unit(price)) => {
+            let unit = Amount::new(price.quantity, price.commodity.clone());
+            let total = unit.checked_mul(quantity)?;
+            Some((unit, total))
+        }
+        Some(Price::Total(price)) => {
+            let total = Amount::new(price.quantity, price.commodity.clone());
+            Some((total.checked_div(quantity)?, total))
+        }
+        None => None,
+    };
+    Ok(prices)
+}
+
+fn dispose(
+    lots: &mut Vec<Lot>,
+    date: NaiveDate,
+    item: usize,
+    index: usize,
+    posting: &Posting,
+    amount: &PostingAmount,
+    matching: LotMatching,
+) -> Result<Disposal, InventoryError> {
+    let amount_error = |error| InventoryError::Amount {
+        item,
+        posting: index,
+        error,
+    };
+    let requested = -amount.amount.quantity;
+
+    let lot_price = prices(amount.lot_price.as_ref(), requested).map_err(amount_error)?;
+    let selected = match lot_price {
+        Some((cost, _)) => lots
+            .iter()
+            .enumerate()
+            .filter(|(_, lot)| lot.cost == cost)
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>(),
+        None => {
+            if matching == LotMatching::Average && lots.len() > 1 {
+                merge(lots).map_err(amount_error)?;
+            }
+            let mut selected = (0..lots.len()).collect::<Vec<_>>();
+            if matching == LotMatching::Lifo {
+                selected.reverse();
+            }
+            selected
+        }
+    };
+
+    let available = selected
+        .iter()
+        .map(|&lot| lots[lot].quantity)
+        .sum::<Decimal>();
+    if available < requested {
+        return Err(InventoryError::InsufficientQuantity {
+            item,
+            posting: index,
+            requested,
+            available,
+        });
+    }
+
+    let mut remaining = requested;
+    let mut used = Vec::new();
+    for lot in selected {
+        if remaining.is_zero() {
+            break;
+        }
+        let lot = &mut lots[lot];
+        let taken = remaining.min(lot.quantity);
+        // The whole remaining cost for the rest of the lot, so it adds up exactly.
+        let taken_cost = if taken == lot.quantity {
+            lot.total_cost.clone()
+        } else {
+            lot.total_cost
+                .checked_mul(taken)
+                .and_then(|cost| cost.checked_div(lot.quantity))
+                .map_err(amount_error)?
+        };
+        lot.total_cost = (&lot.total_cost - &taken_cost).map_err(amount_error)?;
+        lot.quantity -= taken;
+        remaining -= taken;
+        used.push(Lot {
+            quantity: taken,
+            total_cost: taken_cost,
+            ..lot.clone()
+        });
+    }
+    lots.retain(|lot| !lot.quantity.is_zero());
+
+    let mut cost_basis = used[0].total_cost.clone();
+    for lot in &used[1..] {
+        cost_basis = (&cost_basis + &lot.total_cost).map_err(amount_error)?;
+    }
+    let proceeds = prices(amount.price.as_ref(), requested)
+        .map_err(amount_error)?
+        .map(|(_, total)| total);
+    let gain = match proceeds {
+        Some(ref proceeds) => Some((proceeds - &cost_basis).map_err(amount_error)?),
+        None => None,
+    };
+
+    Ok(Disposal {
+        date,
+        account: posting.account.clone(),
+        item,
+        posting: index,
+        amount: -&amount.amount,
+        lots: used,
+        cost_basis,
+        proceeds,
+        gain,
+    })
+}
+
+/// Replaces the lots with a single lot at their average cost, dated as the oldest one.
+fn merge(lots: &mut Vec<Lot>) -> Result<(), AmountError> {
+    let mut quantity = Decimal::ZERO;
+    let mut total = Amount::new(Decimal::ZERO, lots[0].total_cost.commodity.clone());
+    for lot in lots.iter() {
+        quantity = quantity
+            .checked_add(lot.quantity)
+            .ok_or(AmountError::Overflow)?;
+        total = (&total + &lot.total_cost)?;
+    }
+    let merged = Lot {
+        quantity,
+        cost: total.checked_div(quantity)?,
+        total_cost: total,
+        ..lots[0].clone()
+    };
+    *lots = vec![merged];
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"2018-01-01 Buy
+  Assets:Broker  10 AAPL @ $100
+  Assets:Bank
+
+2018-02-01 Buy
+  Assets:Broker  10 AAPL {$120}
+  Assets:Bank
+
+2018-03-01 Sell
+  Assets:Broker  -15 AAPL @ $130
+  Assets:Bank
+  Income:Gains
+"#;
+
+    fn gains(matching: LotMatching) -> (String, String, Decimal) {
+        let ledger: Ledger = SOURCE.parse().unwrap();
+        let inventory = Inventory::new(&ledger, matching).unwrap();
+        let disposal = &inventory.disposals()[0];
+        let account = "Assets:Broker".parse().unwrap();
+        let remaining = inventory.lots(&account, "AAPL");
+        assert_eq!(remaining.len(), 1);
+        (
+            disposal.cost_basis.to_string(),
+            disposal.gain.as_ref().unwrap().to_string(),
+            remaining[0].quantity,
+        )
+    }
+
+    #[test]
+    fn matching_methods() {
+        assert_eq!(
+            gains(LotMatching::Fifo),
+            ("$1600".to_owned(), "$350".to_owned(), Decimal::new(5, 0))
+        );
+        assert_eq!(
+            gains(LotMatching::Lifo),
+            ("$1700".to_owned(), "$250".to_owned(), Decimal::new(5, 0))
+        );
+        assert_eq!(
+            gains(LotMatching::Average),
+            ("$1650".to_owned(), "$300".to_owned(), Decimal::new(5, 0))
+        );
+    }
+
+    #[test]
+    fn lot_selection_and_errors() {
+        let source = SOURCE.replace("-15 AAPL @ $130", "-5 AAPL {$120} @@ $650");
+        let ledger: Ledger = source.parse().unwrap();
+        let inventory = Inventory::new(&ledger, LotMatching::Fifo).unwrap();
+        let disposal = &inventory.disposals()[0];
+        assert_eq!(disposal.lots.len(), 1);
+        assert_eq!(disposal.lots[0].item, 2);
+        assert_eq!(disposal.amount.to_string(), "5 AAPL");
+        assert_eq!(disposal.gain.as_ref().unwrap().to_string(), "$50");
+        let account = "Assets:Broker".parse().unwrap();
+        let lots = inventory.lots(&account, "AAPL");
+        assert_eq!(
+            lots.iter().map(|lot| lot.quantity).collect::<Vec<_>>(),
+            vec![Decimal::new(10, 0), Decimal::new(5, 0)]
+        );
+
+        let source = SOURCE.replace("-15 AAPL @ $130", "-11 AAPL {$120}");
+        let ledger: Ledger = source.parse().unwrap();
+        assert_eq!(
+            Inventory::new(&ledger, LotMatching::Fifo),
+            Err(InventoryError::InsufficientQuantity {
+                item: 4,
+                posting: 0,
+                requested: Decimal::new(11, 0),
+                available: Decimal::new(10, 0)
+            })
+        );
+    }
+
+    #[test]
+    fn total_prices() {
+        let ledger: Ledger = "2018-01-01 Buy\n  Assets:Broker  3 AAPL {{$10}}\n  Assets:Bank\n\n\
+            2018-02-01 Sell\n  Assets:Broker  -3 AAPL @@ $16\n  Assets:Bank\n\n\
+            2018-03-01 Buy\n  Assets:Broker  6 AAPL @ $2\n  Assets:Bank\n\n\
+            2018-04-01 Sell\n  Assets:Broker  -6 AAPL @@ $1\n  Assets:Bank\n"
+            .parse()
+            .unwrap();
+        let inventory = Inventory::new(&ledger, LotMatching::Fifo).unwrap();
+        let summary = inventory
+            .disposals()
+            .iter()
+            .map(|disposal| {
+                format!(
+                    "{} {} {}",
+                    disposal.cost_basis,
+                    disposal.proceeds.as_ref().unwrap(),
+                    disposal.gain.as_ref().unwrap()
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(summary, vec!["$10 $16 $6", "$12 $1 $-11"]);
+    }
+}
@@ -29,16 +29,98 @@
 //!   ```ledger-cli,ignore
 //!   P DATE SYMBOL PRICE
 //!   ```
+//! - Automated transactions with format:
+//!
+//!   ```ledger-cli,ignore
+//!   = PREDICATE
+//!     ACCOUNT  MULTIPLIER|AMOUNT
+//!   ```
+//!
+//! - Periodic transactions with format:
+//!
+//!   ```ledger-cli,ignore
+//!   ~ PERIOD
+//!     ACCOUNT  [AMOUNT]
+//!   ```
+//!
 //! - Command directives: `include`
 
 mod model;
 pub use model::*;
 
+mod account;
+pub use account::*;
+
+mod amount;
+pub use amount::*;
+
+mod balance;
+pub use balance::*;
+
+mod assertion;
+pub use assertion::*;
+
+mod include;
+pub use include::*;
+
+mod tree;
+pub use tree::*;
+
+mod register;
+pub use register::*;
+
+mod valuation;
+pub use valuation::*;
+
+mod inventory;
+pub use inventory::*;
+
+mod automated;
+pub use automated::*;
+
+mod period;
+pub use period::*;
+
+mod budget;
+pub use budget::*;
+
+mod forecast;
+pub use forecast::*;
+
+mod expr;
+pub use expr::*;
+
+mod query;
+pub use query::*;
+
+mod grouping;
+pub use grouping::*;
+
+mod duplicates;
+pub use duplicates::*;
+
 mod serializer;
 pub use serializer::*;
 
+pub mod borrowed;
+
 mod parser;
 
+mod cst;
+pub use cst::*;
+
+mod stream;
+pub use stream::*;
+
+mod incremental;
+pub use incremental::*;
+
+#[cfg(feature = "parallel")]
+mod parallel;
+#[cfg(feature = "parallel")]
+pub use parallel::*;
+
+use nom::{error::convert_error, Finish};
 use std::fmt;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -77,3 +159,23 @@ impl std::error::Error for ParseError {
 pub fn parse(input: &str) -> Result<Ledger, ParseError> {
     input.parse()
 }
+
+/// Parses ledger-cli source to AST tree borrowing text from `input`.
+///
+/// Cheaper than [`parse`] for read-only use. Use [`borrowed::Ledger::into_owned`] to get
+/// the owned model.
+///
+/// # Examples
+///
+/// ```
+/// let input = "2018-10-01 Description\n  TEST:Account 123  $1.20\n  TEST:Account 345\n";
+/// let ledger = ledger_parser::parse_borrowed(input).unwrap();
+/// assert_eq!(ledger.items.len(), 1);
+/// assert_eq!(ledger.into_owned(), ledger_parser::parse(input).unwrap());
+/// ```
+pub fn parse_borrowed(input: &str) -> Result<borrowed::Ledger<'_>, ParseError> {
+    match parser::parse_ledger(input).finish() {
+        Ok((_, result)) => Ok(result),
+        Err(error) => Err(ParseError::String(convert_error(input, error))),
+    }
+}
@@ -1,17 +1,31 @@
-use crate::parser;
+use crate::borrowed;
 use crate::serializer::*;
+use crate::AccountName;
 use crate::ParseError;
 use chrono::{NaiveDate, NaiveDateTime};
-use nom::{error::convert_error, Finish};
 use ordered_float::NotNan;
 use rust_decimal::Decimal;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::ops::Range;
 use std::str::FromStr;
 
+/// Version of the JSON shape produced by the `serde` feature. It is increased whenever
+/// a change to the model would make previously serialized documents fail to deserialize.
+///
+/// Structs are objects with the field names used in Rust. Enums with data are objects
+/// with a `"type"` field (the variant name in snake case) and the data in `"value"`
+/// (`"amount"` for `Price` and `Balance`). Unit enums are snake case strings.
+/// Quantities are decimal strings, dates are `YYYY-MM-DD` and spans are omitted if absent.
+#[cfg(feature = "serde")]
+pub const JSON_SCHEMA_VERSION: u32 = 1;
+
 ///
 /// Main document. Contains transactions and/or commodity prices.
 ///
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Ledger {
     pub items: Vec<LedgerItem>,
 }
@@ -20,11 +34,7 @@ impl FromStr for Ledger {
     type Err = ParseError;
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
-        let result = parser::parse_ledger(input);
-        match result.finish() {
-            Ok((_, result)) => Ok(result),
-            Err(error) => Err(ParseError::String(convert_error(input, error))),
-        }
+        crate::parse_borrowed(input).map(borrowed::Ledger::into_owned)
     }
 }
 
@@ -39,14 +49,105 @@ impl fmt::Display for Ledger {
     }
 }
 
+///
+/// Location of a parsed element in the source text.
+///
+/// Spans are filled in by the parser and are `None` for values built in code.
+/// They are ignored when comparing elements for equality.
+///
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Span {
+    /// Byte offsets into the source (end exclusive).
+    pub bytes: Range<usize>,
+    /// One-based line numbers (end exclusive).
+    pub lines: Range<usize>,
+}
+
 #[non_exhaustive]
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
 pub enum LedgerItem {
     EmptyLine,
-    LineComment(String),
+    LineComment(LineComment),
     Transaction(Transaction),
     CommodityPrice(CommodityPrice),
-    Include(String),
+    Include(Include),
+    AutomatedTransaction(AutomatedTransaction),
+    PeriodicTransaction(PeriodicTransaction),
+}
+
+impl LedgerItem {
+    /// Calls `f` for every span recorded in the item.
+    pub(crate) fn for_each_span_mut(&mut self, f: &mut dyn FnMut(&mut Span)) {
+        fn visit(span: &mut Option<Span>, f: &mut dyn FnMut(&mut Span)) {
+            if let Some(span) = span {
+                f(span);
+            }
+        }
+
+        fn visit_tags(tags: &mut [Tag], f: &mut dyn FnMut(&mut Span)) {
+            for tag in tags {
+                visit(&mut tag.span, f);
+            }
+        }
+
+        fn visit_posting(posting: &mut Posting, f: &mut dyn FnMut(&mut Span)) {
+            visit(&mut posting.span, f);
+            if let Some(ref mut amount) = posting.amount {
+                visit(&mut amount.span, f);
+                visit(&mut amount.amount.span, f);
+                for price in amount.lot_price.iter_mut().chain(amount.price.iter_mut()) {
+                    match price {
+                        Price::Unit(price) | Price::Total(price) => visit(&mut price.span, f),
+                    }
+                }
+            }
+            if let Some(Balance::Amount(ref mut balance)) = posting.balance {
+                visit(&mut balance.span, f);
+            }
+            visit_tags(&mut posting.metadata.tags, f);
+        }
+
+        match self {
+            LedgerItem::LineComment(comment) => visit(&mut comment.span, f),
+            LedgerItem::Include(include) => visit(&mut include.span, f),
+            LedgerItem::Transaction(transaction) => {
+                visit(&mut transaction.span, f);
+                visit_tags(&mut transaction.posting_metadata.tags, f);
+                for posting in &mut transaction.postings {
+                    visit_posting(posting, f);
+                }
+            }
+            LedgerItem::CommodityPrice(commodity_price) => {
+                visit(&mut commodity_price.span, f);
+                visit(&mut commodity_price.amount.span, f);
+            }
+            LedgerItem::AutomatedTransaction(automated) => {
+                visit(&mut automated.span, f);
+                visit_tags(&mut automated.posting_metadata.tags, f);
+                for posting in &mut automated.postings {
+                    visit(&mut posting.span, f);
+                    if let AutomatedAmount::Amount(ref mut amount) = posting.amount {
+                        visit(&mut amount.span, f);
+                    }
+                    visit_tags(&mut posting.metadata.tags, f);
+                }
+            }
+            LedgerItem::PeriodicTransaction(periodic) => {
+                visit(&mut periodic.span, f);
+                visit_tags(&mut periodic.posting_metadata.tags, f);
+                for posting in &mut periodic.postings {
+                    visit_posting(posting, f);
+                }
+            }
+            _ => {}
+        }
+    }
 }
 
 impl fmt::Display for LedgerItem {
@@ -60,10 +161,71 @@ impl fmt::Display for LedgerItem {
     }
 }
 
+///
+/// Comment on its own line (`; text`), outside of transactions.
+///
+#[derive(Debug, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LineComment {
+    /// Text after the comment character.
+    pub comment: String,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub span: Option<Span>,
+}
+
+impl LineComment {
+    pub fn new(comment: &str) -> Self {
+        LineComment {
+            comment: comment.to_owned(),
+            span: None,
+        }
+    }
+}
+
+impl PartialEq for LineComment {
+    fn eq(&self, other: &Self) -> bool {
+        self.comment == other.comment
+    }
+}
+
+///
+/// `include` directive.
+///
+#[derive(Debug, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Include {
+    /// Path of the included file(s), as written.
+    pub file: String,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub span: Option<Span>,
+}
+
+impl Include {
+    pub fn new(file: &str) -> Self {
+        Include {
+            file: file.to_owned(),
+            span: None,
+        }
+    }
+}
+
+impl PartialEq for Include {
+    fn eq(&self, other: &Self) -> bool {
+        self.file == other.file
+    }
+}
+
 ///
 /// Transaction.
 ///
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Transaction {
     pub status: Option<TransactionStatus>,
     pub code: Option<String>,
@@ -73,6 +235,31 @@ pub struct Transaction {
     pub effective_date: Option<NaiveDate>,
     pub posting_metadata: PostingMetadata,
     pub postings: Vec<Posting>,
+    /// Generated from a periodic transaction.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "std::ops::Not::not")
+    )]
+    pub forecast: bool,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub span: Option<Span>,
+}
+
+impl PartialEq for Transaction {
+    fn eq(&self, other: &Self) -> bool {
+        self.status == other.status
+            && self.code == other.code
+            && self.description == other.description
+            && self.comment == other.comment
+            && self.date == other.date
+            && self.effective_date == other.effective_date
+            && self.posting_metadata == other.posting_metadata
+            && self.postings == other.postings
+            && self.forecast == other.forecast
+    }
 }
 
 impl fmt::Display for Transaction {
@@ -87,6 +274,8 @@ impl fmt::Display for Transaction {
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum TransactionStatus {
     Pending,
     Cleared,
@@ -103,15 +292,40 @@ impl fmt::Display for TransactionStatus {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Posting {
-    pub account: String,
+    pub account: AccountName,
     pub reality: Reality,
     pub amount: Option<PostingAmount>,
     pub balance: Option<Balance>,
     pub status: Option<TransactionStatus>,
     pub comment: Option<String>,
     pub metadata: PostingMetadata,
+    /// Added by an automated transaction.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "std::ops::Not::not")
+    )]
+    pub generated: bool,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub span: Option<Span>,
+}
+
+impl PartialEq for Posting {
+    fn eq(&self, other: &Self) -> bool {
+        self.account == other.account
+            && self.reality == other.reality
+            && self.amount == other.amount
+            && self.balance == other.balance
+            && self.status == other.status
+            && self.comment == other.comment
+            && self.metadata == other.metadata
+            && self.generated == other.generated
+    }
 }
 
 impl fmt::Display for Posting {
@@ -126,17 +340,33 @@ impl fmt::Display for Posting {
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Reality {
     Real,
     BalancedVirtual,
     UnbalancedVirtual,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PostingAmount {
     pub amount: Amount,
     pub lot_price: Option<Price>,
     pub price: Option<Price>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub span: Option<Span>,
+}
+
+impl PartialEq for PostingAmount {
+    fn eq(&self, other: &Self) -> bool {
+        self.amount == other.amount
+            && self.lot_price == other.lot_price
+            && self.price == other.price
+    }
 }
 
 impl fmt::Display for PostingAmount {
@@ -150,10 +380,23 @@ impl fmt::Display for PostingAmount {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Amount {
+    #[cfg_attr(feature = "serde", serde(with = "rust_decimal::serde::str"))]
     pub quantity: Decimal,
     pub commodity: Commodity,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub span: Option<Span>,
+}
+
+impl PartialEq for Amount {
+    fn eq(&self, other: &Self) -> bool {
+        self.quantity == other.quantity && self.commodity == other.commodity
+    }
 }
 
 impl fmt::Display for Amount {
@@ -168,24 +411,37 @@ impl fmt::Display for Amount {
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Commodity {
     pub name: String,
     pub position: CommodityPosition,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum CommodityPosition {
     Left,
     Right,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "amount", rename_all = "snake_case")
+)]
 pub enum Price {
     Unit(Amount),
     Total(Amount),
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "amount", rename_all = "snake_case")
+)]
 pub enum Balance {
     Zero,
     Amount(Amount),
@@ -205,11 +461,25 @@ impl fmt::Display for Balance {
 ///
 /// Commodity price.
 ///
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct CommodityPrice {
     pub datetime: NaiveDateTime,
     pub commodity_name: String,
     pub amount: Amount,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub span: Option<Span>,
+}
+
+impl PartialEq for CommodityPrice {
+    fn eq(&self, other: &Self) -> bool {
+        self.datetime == other.datetime
+            && self.commodity_name == other.commodity_name
+            && self.amount == other.amount
+    }
 }
 
 impl fmt::Display for CommodityPrice {
@@ -223,23 +493,156 @@ impl fmt::Display for CommodityPrice {
     }
 }
 
+///
+/// Automated transaction (`= PREDICATE`). Its postings are added to the postings
+/// of later transactions which match the predicate.
+///
+#[derive(Debug, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AutomatedTransaction {
+    /// Text after `=`, e.g. `^Expenses:Food`.
+    pub predicate: String,
+    pub comment: Option<String>,
+    pub posting_metadata: PostingMetadata,
+    pub postings: Vec<AutomatedPosting>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub span: Option<Span>,
+}
+
+impl PartialEq for AutomatedTransaction {
+    fn eq(&self, other: &Self) -> bool {
+        self.predicate == other.predicate
+            && self.comment == other.comment
+            && self.posting_metadata == other.posting_metadata
+            && self.postings == other.postings
+    }
+}
+
+impl fmt::Display for AutomatedTransaction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.to_string_pretty(&SerializerSettings::default())
+        )?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AutomatedPosting {
+    pub account: AccountName,
+    pub reality: Reality,
+    pub amount: AutomatedAmount,
+    pub comment: Option<String>,
+    pub metadata: PostingMetadata,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub span: Option<Span>,
+}
+
+impl PartialEq for AutomatedPosting {
+    fn eq(&self, other: &Self) -> bool {
+        self.account == other.account
+            && self.reality == other.reality
+            && self.amount == other.amount
+            && self.comment == other.comment
+            && self.metadata == other.metadata
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
+pub enum AutomatedAmount {
+    /// Amount without a commodity, multiplies the amount of the matched posting.
+    Multiplier(#[cfg_attr(feature = "serde", serde(with = "rust_decimal::serde::str"))] Decimal),
+    /// Amount with a commodity, used as it is.
+    Amount(Amount),
+}
+
+///
+/// Periodic transaction (`~ PERIOD`), e.g. a monthly budget.
+///
+#[derive(Debug, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PeriodicTransaction {
+    /// Period expression after `~`, e.g. `Monthly` or `Every 2 weeks from 2018-10-01`.
+    pub period: String,
+    pub comment: Option<String>,
+    pub posting_metadata: PostingMetadata,
+    pub postings: Vec<Posting>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub span: Option<Span>,
+}
+
+impl PartialEq for PeriodicTransaction {
+    fn eq(&self, other: &Self) -> bool {
+        self.period == other.period
+            && self.comment == other.comment
+            && self.posting_metadata == other.posting_metadata
+            && self.postings == other.postings
+    }
+}
+
+impl fmt::Display for PeriodicTransaction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.to_string_pretty(&SerializerSettings::default())
+        )?;
+        Ok(())
+    }
+}
+
 ///
 /// Posting metadata. Also appears on Transaction
 ///
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PostingMetadata {
     pub date: Option<NaiveDate>,
     pub effective_date: Option<NaiveDate>,
     pub tags: Vec<Tag>,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Tag {
     pub name: String,
     pub value: Option<TagValue>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub span: Option<Span>,
+}
+
+impl PartialEq for Tag {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.value == other.value
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
 pub enum TagValue {
     String(String),
     Integer(i64),
@@ -280,7 +683,8 @@ mod tests {
                     commodity: Commodity {
                         name: "€".to_owned(),
                         position: CommodityPosition::Right,
-                    }
+                    },
+                    span: None,
                 }
             ),
             "42.00 €"
@@ -293,7 +697,8 @@ mod tests {
                     commodity: Commodity {
                         name: "USD".to_owned(),
                         position: CommodityPosition::Left,
-                    }
+                    },
+                    span: None,
                 }
             ),
             "USD42.00"
@@ -315,8 +720,10 @@ mod tests {
                     commodity: Commodity {
                         name: "PLN".to_owned(),
                         position: CommodityPosition::Right
-                    }
-                }
+                    },
+                    span: None,
+                },
+                span: None,
             }
         );
         let expected = "P 2017-11-12 12:00:00 mBH 5.00 PLN";
@@ -333,7 +740,8 @@ mod tests {
                     commodity: Commodity {
                         name: "€".to_owned(),
                         position: CommodityPosition::Right,
-                    }
+                    },
+                    span: None,
                 })
             ),
             "42.00 €"
@@ -347,7 +755,7 @@ mod tests {
             format!(
                 "{}",
                 Posting {
-                    account: "Assets:Checking".to_owned(),
+                    account: "Assets:Checking".parse().unwrap(),
                     reality: Reality::Real,
                     amount: Some(PostingAmount {
                         amount: Amount {
@@ -355,17 +763,20 @@ mod tests {
                             commodity: Commodity {
                                 name: "USD".to_owned(),
                                 position: CommodityPosition::Left,
-                            }
+                            },
+                            span: None,
                         },
                         lot_price: None,
                         price: None,
+                        span: None,
                     }),
                     balance: Some(Balance::Amount(Amount {
                         quantity: Decimal::new(5000, 2),
                         commodity: Commodity {
                             name: "USD".to_owned(),
                             position: CommodityPosition::Left,
-                        }
+                        },
+                        span: None,
                     })),
                     status: Some(TransactionStatus::Cleared),
                     comment: Some("asdf".to_owned()),
@@ -374,6 +785,8 @@ mod tests {
                         effective_date: None,
                         tags: vec![],
                     },
+                    generated: false,
+                    span: None,
                 }
             ),
             "* Assets:Checking  USD42.00 = USD50.00\n  ; asdf"
@@ -398,7 +811,7 @@ mod tests {
                 },
                 postings: vec![
                     Posting {
-                        account: "TEST:ABC 123".to_owned(),
+                        account: "TEST:ABC 123".parse().unwrap(),
                         reality: Reality::Real,
                         amount: Some(PostingAmount {
                             amount: Amount {
@@ -406,10 +819,12 @@ mod tests {
                                 commodity: Commodity {
                                     name: "$".to_owned(),
                                     position: CommodityPosition::Left
-                                }
+                                },
+                                span: None,
                             },
                             lot_price: None,
-                            price: None
+                            price: None,
+                            span: None,
                         }),
                         balance: None,
                         status: None,
@@ -419,9 +834,11 @@ mod tests {
                             effective_date: None,
                             tags: vec![],
                         },
+                        generated: false,
+                        span: None,
                     },
                     Posting {
-                        account: "TEST:ABC 123".to_owned(),
+                        account: "TEST:ABC 123".parse().unwrap(),
                         reality: Reality::Real,
                         amount: Some(PostingAmount {
                             amount: Amount {
@@ -429,10 +846,12 @@ mod tests {
                                 commodity: Commodity {
                                     name: "$".to_owned(),
                                     position: CommodityPosition::Left
-                                }
+                                },
+                                span: None,
                             },
                             lot_price: None,
-                            price: None
+                            price: None,
+                            span: None,
                         }),
                         balance: None,
                         status: None,
@@ -442,8 +861,12 @@ mod tests {
                             effective_date: None,
                             tags: vec![],
                         },
+                        generated: false,
+                        span: None,
                     }
-                ]
+                ],
+                forecast: false,
+                span: None,
             },
         );
         let expected = r#"2018-10-01=2018-10-14 ! (123) Marek Ogarek
@@ -475,7 +898,7 @@ mod tests {
                         },
                         postings: vec![
                             Posting {
-                                account: "TEST:ABC 123".to_owned(),
+                                account: "TEST:ABC 123".parse().unwrap(),
                                 reality: Reality::Real,
                                 amount: Some(PostingAmount {
                                     amount: Amount {
@@ -483,10 +906,12 @@ mod tests {
                                         commodity: Commodity {
                                             name: "$".to_owned(),
                                             position: CommodityPosition::Left
-                                        }
+                                        },
+                                        span: None,
                                     },
                                     lot_price: None,
-                                    price: None
+                                    price: None,
+                                    span: None,
                                 }),
                                 balance: None,
                                 status: None,
@@ -496,9 +921,11 @@ mod tests {
                                     effective_date: None,
                                     tags: vec![],
                                 },
+                                generated: false,
+                                span: None,
                             },
                             Posting {
-                                account: "TEST:ABC 123".to_owned(),
+                                account: "TEST:ABC 123".parse().unwrap(),
                                 reality: Reality::Real,
                                 amount: Some(PostingAmount {
                                     amount: Amount {
@@ -506,10 +933,12 @@ mod tests {
                                         commodity: Commodity {
                                             name: "$".to_owned(),
                                             position: CommodityPosition::Left
-                                        }
+                                        },
+                                        span: None,
                                     },
                                     lot_price: None,
-                                    price: None
+                                    price: None,
+                                    span: None,
                                 }),
                                 balance: None,
                                 status: None,
@@ -519,8 +948,12 @@ mod tests {
                                     effective_date: None,
                                     tags: vec![],
                                 },
+                                generated: false,
+                                span: None,
                             }
-                        ]
+                        ],
+                        forecast: false,
+                        span: None,
                     }),
                     LedgerItem::EmptyLine,
                     LedgerItem::Transaction(Transaction {
@@ -537,7 +970,7 @@ mod tests {
                         description: Some("Marek Ogarek".to_owned()),
                         postings: vec![
                             Posting {
-                                account: "TEST:ABC 123".to_owned(),
+                                account: "TEST:ABC 123".parse().unwrap(),
                                 reality: Reality::Real,
                                 amount: Some(PostingAmount {
                                     amount: Amount {
@@ -545,22 +978,26 @@ mod tests {
                                         commodity: Commodity {
                                             name: "$".to_owned(),
                                             position: CommodityPosition::Left
-                                        }
+                                        },
+                                        span: None,
                                     },
                                     lot_price: Some(Price::Unit(Amount {
                                         quantity: Decimal::new(500, 2),
                                         commodity: Commodity {
                                             name: "PLN".to_owned(),
                                             position: CommodityPosition::Right
-                                        }
+                                        },
+                                        span: None,
                                     })),
                                     price: Some(Price::Unit(Amount {
                                         quantity: Decimal::new(600, 2),
                                         commodity: Commodity {
                                             name: "PLN".to_owned(),
                                             position: CommodityPosition::Right
-                                        }
-                                    }))
+                                        },
+                                        span: None,
+                                    })),
+                                    span: None,
                                 }),
                                 balance: None,
                                 status: None,
@@ -570,9 +1007,11 @@ mod tests {
                                     effective_date: None,
                                     tags: vec![],
                                 },
+                                generated: false,
+                                span: None,
                             },
                             Posting {
-                                account: "TEST:ABC 123".to_owned(),
+                                account: "TEST:ABC 123".parse().unwrap(),
                                 reality: Reality::Real,
                                 amount: Some(PostingAmount {
                                     amount: Amount {
@@ -580,22 +1019,26 @@ mod tests {
                                         commodity: Commodity {
                                             name: "$".to_owned(),
                                             position: CommodityPosition::Left
-                                        }
+                                        },
+                                        span: None,
                                     },
                                     lot_price: Some(Price::Total(Amount {
                                         quantity: Decimal::new(500, 2),
                                         commodity: Commodity {
                                             name: "PLN".to_owned(),
                                             position: CommodityPosition::Right
-                                        }
+                                        },
+                                        span: None,
                                     })),
                                     price: Some(Price::Total(Amount {
                                         quantity: Decimal::new(600, 2),
                                         commodity: Commodity {
                                             name: "PLN".to_owned(),
                                             position: CommodityPosition::Right
-                                        }
-                                    }))
+                                        },
+                                        span: None,
+                                    })),
+                                    span: None,
                                 }),
                                 balance: None,
                                 status: None,
@@ -605,8 +1048,12 @@ mod tests {
                                     effective_date: None,
                                     tags: vec![],
                                 },
+                                generated: false,
+                                span: None,
                             }
-                        ]
+                        ],
+                        forecast: false,
+                        span: None,
                     }),
                     LedgerItem::EmptyLine,
                     LedgerItem::CommodityPrice(CommodityPrice {
@@ -620,8 +1067,10 @@ mod tests {
                             commodity: Commodity {
                                 name: "PLN".to_owned(),
                                 position: CommodityPosition::Right
-                            }
-                        }
+                            },
+                            span: None,
+                        },
+                        span: None,
                     }),
                 ]
             }
@@ -641,4 +1090,36 @@ P 2017-11-12 12:00:00 mBH 5.00 PLN
 "#;
         assert_eq!(actual, expected);
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trip() {
+        let ledger: Ledger = r#"2018-10-01 * Payee  ; :tag:
+  TEST:ABC  $1.20 @ 4.10 PLN
+  TEST:DEF  = 0
+P 2017-11-12 12:00:00 mBH 5.00 PLN
+"#
+        .parse()
+        .unwrap();
+        let json = serde_json::to_value(&ledger).unwrap();
+
+        let transaction = &json["items"][0];
+        assert_eq!(transaction["type"], "transaction");
+        assert_eq!(transaction["value"]["status"], "cleared");
+        assert_eq!(transaction["value"]["date"], "2018-10-01");
+        let posting = &transaction["value"]["postings"][0];
+        assert_eq!(posting["amount"]["amount"]["quantity"], "1.20");
+        assert_eq!(posting["amount"]["price"]["type"], "unit");
+        assert_eq!(
+            transaction["value"]["postings"][1]["balance"]["type"],
+            "zero"
+        );
+        assert_eq!(
+            transaction["value"]["posting_metadata"]["tags"][0]["name"],
+            "tag"
+        );
+
+        let deserialized: Ledger = serde_json::from_value(json).unwrap();
+        assert_eq!(deserialized, ledger);
+    }
 }
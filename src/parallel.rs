@@ -0,0 +1,144 @@
+use crate::model::*;
+use crate::parser;
+use crate::ParseError;
+use nom::Finish;
+use rayon::prelude::*;
+
+/// Inputs smaller than this are not split.
+const MIN_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Parses ledger-cli source to AST tree using all available threads.
+///
+/// The input is split at lines which always start a new top-level item, the parts are
+/// parsed concurrently and joined. The result (including errors) is the same as
+/// returned by [`parse`](crate::parse).
+///
+/// # Examples
+///
+/// ```
+/// let input = "2018-10-01 Description\n  TEST:Account 123  $1.20\n  TEST:Account 345\n";
+/// assert_eq!(
+///     ledger_parser::parse_parallel(input),
+///     ledger_parser::parse(input)
+/// );
+/// ```
+pub fn parse_parallel(input: &str) -> Result<Ledger, ParseError> {
+    let chunk_size = (input.len() / (rayon::current_num_threads() * 4)).max(MIN_CHUNK_SIZE);
+    parse_chunks(input, &split(input, chunk_size))
+}
+
+fn parse_chunks(input: &str, chunks: &[(usize, usize)]) -> Result<Ledger, ParseError> {
+    let mut first_lines = Vec::with_capacity(chunks.len());
+    let mut first_line = 1;
+    for &(start, end) in chunks {
+        first_lines.push(first_line);
+        first_line += input[start..end].matches('\n').count();
+    }
+
+    let results = chunks
+        .par_iter()
+        .zip(first_lines)
+        .map(|(&(start, end), first_line)| {
+            let chunk = &input[start..end];
+            match parser::parse_ledger_at(chunk, start, first_line).finish() {
+                Ok((_, ledger)) => Ok(ledger.into_owned().items),
+                Err(error) => Err(ParseError::String(parser::convert_error_at(
+                    chunk, error, first_line,
+                ))),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut items = Vec::new();
+    for result in results {
+        items.extend(result?);
+    }
+    Ok(Ledger { items })
+}
+
+/// Splits the input into parts of at least `chunk_size` bytes which can be parsed separately.
+fn split(input: &str, chunk_size: usize) -> Vec<(usize, usize)> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut search_from = chunk_size;
+
+    while search_from < input.len() {
+        // Searching bytes, as `search_from` does not have to be at a char boundary.
+        let next_start = input.as_bytes()[search_from - 1..]
+            .iter()
+            .enumerate()
+            .filter(|(_, &byte)| byte == b'\n')
+            .map(|(pos, _)| search_from + pos)
+            .find(|&line_start| parser::starts_item(&input[line_start..]));
+
+        match next_start {
+            Some(next_start) => {
+                chunks.push((start, next_start));
+                start = next_start;
+                search_from = next_start + chunk_size;
+            }
+            None => break,
+        }
+    }
+
+    chunks.push((start, input.len()));
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(count: usize) -> String {
+        let mut source = String::new();
+        for i in 0..count {
+            source.push_str(&format!(
+                "; Transaction {i}\n2018-10-01 Payee {i}\n  TEST:ABC  $1.20\n; attached\n  TEST:DEF\n\n"
+            ));
+        }
+        source
+    }
+
+    #[test]
+    fn split_at_item_starts() {
+        let input = source(100);
+        let chunks = split(&input, 100);
+        assert!(chunks.len() > 10);
+        for pair in chunks.windows(2) {
+            assert_eq!(pair[0].1, pair[1].0);
+            assert!(parser::starts_item(&input[pair[1].0..]));
+        }
+        assert_eq!(chunks.first().unwrap().0, 0);
+        assert_eq!(chunks.last().unwrap().1, input.len());
+    }
+
+    #[test]
+    fn same_result_as_sequential_parse() {
+        let input = source(200);
+        let expected: Ledger = input.parse().unwrap();
+        let ledger = parse_chunks(&input, &split(&input, 300)).unwrap();
+        assert_eq!(ledger, expected);
+
+        let spans = |ledger: &Ledger| {
+            ledger
+                .items
+                .iter()
+                .filter_map(|item| match item {
+                    LedgerItem::Transaction(t) => t.span.clone(),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(spans(&ledger), spans(&expected));
+    }
+
+    #[test]
+    fn same_error_as_sequential_parse() {
+        let input = source(200).replace(
+            "Payee 150\n  TEST:ABC  $1.20",
+            "Payee 150\n  TEST:ABC  $1.20 {",
+        );
+        let expected = input.parse::<Ledger>().unwrap_err();
+        assert_eq!(parse_chunks(&input, &split(&input, 300)), Err(expected));
+    }
+}
@@ -5,8 +5,8 @@ use nom::{
     character::complete::{
         alphanumeric1, char, digit0, digit1, line_ending, none_of, not_line_ending, space0, space1,
     },
-    combinator::{eof, map, map_opt, map_res, not, opt, peek, recognize, value, verify},
-    error::VerboseError,
+    combinator::{consumed, eof, map, map_opt, map_res, not, opt, peek, recognize, value, verify},
+    error::{convert_error, VerboseError},
     multi::{fold_many0, fold_many1, many0, many1, separated_list1},
     number::complete::double,
     sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
@@ -14,17 +14,41 @@ use nom::{
 };
 use ordered_float::NotNan;
 use rust_decimal::Decimal;
+use std::borrow::Cow;
 use std::str::FromStr;
 
-use crate::model::*;
+use crate::borrowed::*;
+use crate::model::{CommodityPosition, Reality, Span, TransactionStatus};
 
-type LedgerParseResult<'a, T> = IResult<&'a str, T, VerboseError<&'a str>>;
+pub(crate) type LedgerParseResult<'a, T> = IResult<&'a str, T, VerboseError<&'a str>>;
 
 fn is_commodity_char(c: char) -> bool {
     !"0123456789{}[]()~`!@#%^&*-=+\\'\",./? ;\t\r\n".contains(c)
 }
 
-fn eol_or_eof(input: &str) -> LedgerParseResult<&str> {
+/// Span of the input consumed between `start` and `rest`, without trailing whitespace.
+///
+/// Sub-parsers only see the remaining input, so the span is stored as distances from
+/// the end of the input. `parse_ledger` turns them into absolute positions.
+fn span_between(start: &str, rest: &str) -> Span {
+    let consumed = &start[..start.len() - rest.len()];
+    Span {
+        bytes: start.len()..start.len() - consumed.trim_end().len(),
+        lines: 0..0,
+    }
+}
+
+fn spanned<'a, O, F>(mut parser: F) -> impl FnMut(&'a str) -> LedgerParseResult<'a, (O, Span)>
+where
+    F: Parser<&'a str, O, VerboseError<&'a str>>,
+{
+    move |input: &'a str| {
+        let (rest, output) = parser.parse(input)?;
+        Ok((rest, (output, span_between(input, rest))))
+    }
+}
+
+fn eol_or_eof(input: &str) -> LedgerParseResult<'_, &str> {
     alt((line_ending, eof))(input)
 }
 
@@ -32,7 +56,7 @@ fn number_n<'a>(n: usize) -> impl FnMut(&'a str) -> IResult<&'a str, i32, Verbos
     map_res(take_while_m_n(n, n, AsChar::is_dec_digit), i32::from_str)
 }
 
-fn parse_date_internal(input: &str) -> LedgerParseResult<(i32, i32, i32)> {
+fn parse_date_internal(input: &str) -> LedgerParseResult<'_, (i32, i32, i32)> {
     tuple((
         terminated(number_n(4), alt((char('-'), char('/'), char('.')))),
         terminated(number_n(2), alt((char('-'), char('/'), char('.')))),
@@ -40,7 +64,7 @@ fn parse_date_internal(input: &str) -> LedgerParseResult<(i32, i32, i32)> {
     ))(input)
 }
 
-fn parse_time_internal(input: &str) -> LedgerParseResult<(i32, i32, i32)> {
+fn parse_time_internal(input: &str) -> LedgerParseResult<'_, (i32, i32, i32)> {
     tuple((
         terminated(number_n(2), char(':')),
         terminated(number_n(2), char(':')),
@@ -48,19 +72,19 @@ fn parse_time_internal(input: &str) -> LedgerParseResult<(i32, i32, i32)> {
     ))(input)
 }
 
-fn parse_datetime_internal(input: &str) -> LedgerParseResult<(i32, i32, i32, i32, i32, i32)> {
+fn parse_datetime_internal(input: &str) -> LedgerParseResult<'_, (i32, i32, i32, i32, i32, i32)> {
     separated_pair(parse_date_internal, space1, parse_time_internal)
         .map(|(date, time)| (date.0, date.1, date.2, time.0, time.1, time.2))
         .parse(input)
 }
 
-fn parse_date(input: &str) -> LedgerParseResult<NaiveDate> {
+pub(crate) fn parse_date(input: &str) -> LedgerParseResult<'_, NaiveDate> {
     map_opt(parse_date_internal, |value| {
         NaiveDate::from_ymd_opt(value.0, value.1 as u32, value.2 as u32)
     })(input)
 }
 
-fn parse_datetime(input: &str) -> LedgerParseResult<NaiveDateTime> {
+fn parse_datetime(input: &str) -> LedgerParseResult<'_, NaiveDateTime> {
     map_opt(
         parse_datetime_internal,
         |value| match NaiveDate::from_ymd_opt(value.0, value.1 as u32, value.2 as u32) {
@@ -70,7 +94,7 @@ fn parse_datetime(input: &str) -> LedgerParseResult<NaiveDateTime> {
     )(input)
 }
 
-fn parse_quantity(input: &str) -> LedgerParseResult<Decimal> {
+fn parse_quantity(input: &str) -> LedgerParseResult<'_, Decimal> {
     map_res(
         tuple((
             opt(tag("-")),
@@ -99,34 +123,41 @@ fn parse_quantity(input: &str) -> LedgerParseResult<Decimal> {
     )(input)
 }
 
-fn string_fragment(input: &str) -> LedgerParseResult<&str> {
+fn string_fragment(input: &str) -> LedgerParseResult<'_, &str> {
     alt((
         verify(is_not("\\\""), |s: &str| !s.is_empty()),
         value("\"", tag("\\\"")),
     ))(input)
 }
 
-fn string_between_quotes(input: &str) -> LedgerParseResult<String> {
+fn string_between_quotes(input: &str) -> LedgerParseResult<'_, Cow<'_, str>> {
     let string_contents = fold_many1(string_fragment, String::new, |mut string, fragment| {
         string.push_str(fragment);
         string
     });
 
-    delimited(char('"'), string_contents, char('"'))(input)
+    delimited(
+        char('"'),
+        alt((
+            terminated(is_not("\\\""), peek(char('"'))).map(Cow::Borrowed),
+            string_contents.map(Cow::Owned),
+        )),
+        char('"'),
+    )(input)
 }
 
-fn commodity_without_quotes(input: &str) -> LedgerParseResult<String> {
+fn commodity_without_quotes(input: &str) -> LedgerParseResult<'_, Cow<'_, str>> {
     take_while1(is_commodity_char)
-        .map(str::to_owned)
+        .map(Cow::Borrowed)
         .parse(input)
 }
 
-fn parse_commodity(input: &str) -> LedgerParseResult<String> {
+fn parse_commodity(input: &str) -> LedgerParseResult<'_, Cow<'_, str>> {
     alt((string_between_quotes, commodity_without_quotes))(input)
 }
 
-fn parse_amount(input: &str) -> LedgerParseResult<Amount> {
-    alt((
+pub(crate) fn parse_amount(input: &str) -> LedgerParseResult<'_, Amount<'_>> {
+    spanned(alt((
         tuple((
             opt(terminated(char('-'), space0)),
             terminated(parse_commodity, space0),
@@ -142,6 +173,7 @@ fn parse_amount(input: &str) -> LedgerParseResult<Amount> {
                 name,
                 position: CommodityPosition::Left,
             },
+            span: None,
         }),
         pair(terminated(parse_quantity, space0), parse_commodity).map(|(quantity, name)| Amount {
             quantity,
@@ -149,11 +181,18 @@ fn parse_amount(input: &str) -> LedgerParseResult<Amount> {
                 name,
                 position: CommodityPosition::Right,
             },
+            span: None,
         }),
-    ))(input)
+    )))
+    .map(|(amount, span)| Amount {
+        span: Some(span),
+        ..amount
+    })
+    .parse(input)
 }
 
-fn parse_posting_amount(input: &str) -> LedgerParseResult<PostingAmount> {
+fn parse_posting_amount(input: &str) -> LedgerParseResult<'_, PostingAmount<'_>> {
+    let start = input;
     let (input, amount) = parse_amount(input)?;
     let (input, lot_price) = opt(preceded(space0, parse_lot_price))(input)?;
     let (input, price) = opt(preceded(space0, parse_price))(input)?;
@@ -163,11 +202,12 @@ fn parse_posting_amount(input: &str) -> LedgerParseResult<PostingAmount> {
             amount,
             lot_price,
             price,
+            span: Some(span_between(start, input)),
         },
     ))
 }
 
-fn parse_lot_price(input: &str) -> LedgerParseResult<Price> {
+fn parse_lot_price(input: &str) -> LedgerParseResult<'_, Price<'_>> {
     alt((
         delimited(
             pair(tag("{{"), space0),
@@ -184,26 +224,28 @@ fn parse_lot_price(input: &str) -> LedgerParseResult<Price> {
     ))(input)
 }
 
-fn parse_price(input: &str) -> LedgerParseResult<Price> {
+fn parse_price(input: &str) -> LedgerParseResult<'_, Price<'_>> {
     alt((
         preceded(pair(tag("@@"), space0), parse_amount).map(Price::Total),
         preceded(pair(char('@'), space0), parse_amount).map(Price::Unit),
     ))(input)
 }
 
-fn parse_balance(input: &str) -> LedgerParseResult<Balance> {
+fn parse_balance(input: &str) -> LedgerParseResult<'_, Balance<'_>> {
     alt((
         parse_amount.map(Balance::Amount),
         value(Balance::Zero, char('0')),
     ))(input)
 }
 
-fn parse_commodity_price(input: &str) -> LedgerParseResult<CommodityPrice> {
+fn parse_commodity_price(input: &str) -> LedgerParseResult<'_, CommodityPrice<'_>> {
+    let start = input;
     let (input, _) = char('P')(input)?;
     let (input, datetime) = preceded(space1, parse_datetime)(input)?;
     let (input, commodity_name) = preceded(space1, parse_commodity)(input)?;
     let (input, amount) = preceded(space1, parse_amount)(input)?;
     let (input, _) = preceded(space0, opt(preceded(char(';'), not_line_ending)))(input)?;
+    let span = span_between(start, input);
     let (input, _) = eol_or_eof(input)?;
 
     Ok((
@@ -212,18 +254,19 @@ fn parse_commodity_price(input: &str) -> LedgerParseResult<CommodityPrice> {
             datetime,
             commodity_name,
             amount,
+            span: Some(span),
         },
     ))
 }
 
-fn parse_empty_line(input: &str) -> LedgerParseResult<&str> {
+fn parse_empty_line(input: &str) -> LedgerParseResult<'_, &str> {
     alt((
         terminated(space0, line_ending),
         terminated(space1, eof), // Must consume something or many0 errors to prevent infinite loop
     ))(input)
 }
 
-fn parse_global_line_comment(input: &str) -> LedgerParseResult<&str> {
+fn parse_global_line_comment(input: &str) -> LedgerParseResult<'_, &str> {
     let (input, _) = delimited(
         space0,
         alt((char(';'), char('#'), char('%'), char('|'), char('*'))),
@@ -233,14 +276,31 @@ fn parse_global_line_comment(input: &str) -> LedgerParseResult<&str> {
 }
 
 #[derive(Default)]
-struct Metadata {
-    comment: Option<String>,
+struct Metadata<'a> {
+    comment: Option<Cow<'a, str>>,
     date: Option<NaiveDate>,
     effective_date: Option<NaiveDate>,
-    tags: Vec<Tag>,
+    tags: Vec<Tag<'a>>,
+}
+
+/// Joins two optional comment parts, borrowing when there is only one of them.
+fn join_comments<'a>(
+    first: Option<Cow<'a, str>>,
+    second: Option<Cow<'a, str>>,
+    separator: char,
+) -> Option<Cow<'a, str>> {
+    match (first, second) {
+        (Some(first), Some(second)) => {
+            let mut joined = first.into_owned();
+            joined.push(separator);
+            joined.push_str(&second);
+            Some(Cow::Owned(joined))
+        }
+        (first, second) => first.or(second),
+    }
 }
 
-fn parse_metadata_date(input: &str) -> LedgerParseResult<Metadata> {
+fn parse_metadata_date(input: &str) -> LedgerParseResult<'_, Metadata<'_>> {
     map(
         preceded(
             space0,
@@ -258,7 +318,7 @@ fn parse_metadata_date(input: &str) -> LedgerParseResult<Metadata> {
     )(input)
 }
 
-fn parse_tag_value(input: &str) -> LedgerParseResult<TagValue> {
+fn parse_tag_value(input: &str) -> LedgerParseResult<'_, TagValue<'_>> {
     alt((
         map_res(
             recognize(pair(opt(char('-')), terminated(digit1, not(char('.'))))),
@@ -270,46 +330,49 @@ fn parse_tag_value(input: &str) -> LedgerParseResult<TagValue> {
     ))(input)
 }
 
-fn parse_metadata_tag_with_value(input: &str) -> LedgerParseResult<Metadata> {
+fn parse_metadata_tag_with_value(input: &str) -> LedgerParseResult<'_, Metadata<'_>> {
     map(
         preceded(
             space0,
-            alt((
+            spanned(alt((
                 pair(
                     terminated(alphanumeric1, pair(char(':'), space1)),
                     not_line_ending.map(str::trim_end),
                 )
-                .map(|(name, value)| (name, TagValue::String(value.to_owned()))),
+                .map(|(name, value)| (name, TagValue::String(value))),
                 pair(
                     terminated(alphanumeric1, pair(tag("::"), space1)),
                     parse_tag_value,
                 ),
-            )),
+            ))),
         ),
-        |(name, value)| Metadata {
+        |((name, value), span)| Metadata {
             tags: vec![Tag {
-                name: name.to_owned(),
+                name,
                 value: Some(value),
+                span: Some(span),
             }],
             ..Default::default()
         },
     )(input)
 }
 
-fn parse_tags(input: &str) -> LedgerParseResult<Vec<Tag>> {
+fn parse_tags(input: &str) -> LedgerParseResult<'_, Vec<Tag<'_>>> {
     delimited(
         char(':'),
         separated_list1(
             char(':'),
-            alphanumeric1
-                .map(str::to_owned)
-                .map(|name| Tag { name, value: None }),
+            spanned(alphanumeric1).map(|(name, span)| Tag {
+                name,
+                value: None,
+                span: Some(span),
+            }),
         ),
         char(':'),
     )(input)
 }
 
-fn parse_comment_with_tags(input: &str) -> LedgerParseResult<Metadata> {
+fn parse_comment_with_tags(input: &str) -> LedgerParseResult<'_, Metadata<'_>> {
     map(
         preceded(
             space0,
@@ -325,22 +388,14 @@ fn parse_comment_with_tags(input: &str) -> LedgerParseResult<Metadata> {
             )),
         ),
         |(s1, v, s2)| Metadata {
-            comment: s1
-                .into_iter()
-                .chain(s2)
-                .map(str::to_owned)
-                .reduce(|mut a, b| {
-                    a.push(' ');
-                    a.push_str(&b);
-                    a
-                }),
+            comment: join_comments(s1.map(Cow::Borrowed), s2.map(Cow::Borrowed), ' '),
             tags: v.unwrap_or_default(),
             ..Default::default()
         },
     )(input)
 }
 
-fn parse_metadata_comments(input: &str) -> LedgerParseResult<Metadata> {
+fn parse_metadata_comments(input: &str) -> LedgerParseResult<'_, Metadata<'_>> {
     terminated(
         fold_many0(
             preceded(
@@ -359,15 +414,7 @@ fn parse_metadata_comments(input: &str) -> LedgerParseResult<Metadata> {
                 let mut tags = meta1.tags;
                 tags.extend(meta2.tags);
                 Metadata {
-                    comment: meta1
-                        .comment
-                        .into_iter()
-                        .chain(meta2.comment)
-                        .reduce(|mut a, b| {
-                            a.push('\n');
-                            a.push_str(&b);
-                            a
-                        }),
+                    comment: join_comments(meta1.comment, meta2.comment, '\n'),
                     date: meta2.date.or(meta1.date),
                     effective_date: meta2.effective_date.or(meta1.effective_date),
                     tags,
@@ -378,7 +425,7 @@ fn parse_metadata_comments(input: &str) -> LedgerParseResult<Metadata> {
     )(input)
 }
 
-fn parse_include_file(input: &str) -> LedgerParseResult<&str> {
+fn parse_include_file(input: &str) -> LedgerParseResult<'_, &str> {
     let (input, _) = delimited(space0, tag("include"), space1)(input)?;
     verify(
         terminated(not_line_ending, eol_or_eof).map(str::trim_end),
@@ -386,7 +433,7 @@ fn parse_include_file(input: &str) -> LedgerParseResult<&str> {
     )(input)
 }
 
-fn take_until_hard_separator(input: &str) -> LedgerParseResult<&str> {
+fn take_until_hard_separator(input: &str) -> LedgerParseResult<'_, &str> {
     let mut second_space = false;
     for (pos, c) in input.char_indices() {
         if c == '\t' || c == '\r' || c == '\n' {
@@ -413,7 +460,7 @@ fn take_until_hard_separator(input: &str) -> LedgerParseResult<&str> {
             second_space = false;
 
             if pos == input.len() - 1 && pos > 0 {
-                return Ok(("", input));
+                return Ok((&input[input.len()..], input));
             }
         }
     }
@@ -421,33 +468,37 @@ fn take_until_hard_separator(input: &str) -> LedgerParseResult<&str> {
     Err(Err::Incomplete(Needed::new(1)))
 }
 
-fn parse_account(input: &str) -> LedgerParseResult<(&str, Reality)> {
-    let (input, name) = take_until_hard_separator(input)?;
-
-    if let Some(n1) = name.strip_prefix('[') {
-        if let Some(n2) = n1.strip_suffix(']') {
-            return Ok((input, (n2, Reality::BalancedVirtual)));
-        }
-    }
+fn parse_account(input: &str) -> LedgerParseResult<'_, (&str, Reality)> {
+    verify(
+        map(take_until_hard_separator, |name: &str| {
+            if let Some(n1) = name.strip_prefix('[') {
+                if let Some(n2) = n1.strip_suffix(']') {
+                    return (n2, Reality::BalancedVirtual);
+                }
+            }
 
-    if let Some(n1) = name.strip_prefix('(') {
-        if let Some(n2) = n1.strip_suffix(')') {
-            return Ok((input, (n2, Reality::UnbalancedVirtual)));
-        }
-    }
+            if let Some(n1) = name.strip_prefix('(') {
+                if let Some(n2) = n1.strip_suffix(')') {
+                    return (n2, Reality::UnbalancedVirtual);
+                }
+            }
 
-    Ok((input, (name, Reality::Real)))
+            (name, Reality::Real)
+        }),
+        |(name, _)| crate::account::validate(name).is_ok(),
+    )(input)
 }
 
-fn parse_transaction_status(input: &str) -> LedgerParseResult<TransactionStatus> {
+fn parse_transaction_status(input: &str) -> LedgerParseResult<'_, TransactionStatus> {
     alt((
         value(TransactionStatus::Cleared, char('*')),
         value(TransactionStatus::Pending, char('!')),
     ))(input)
 }
 
-fn parse_posting(input: &str) -> LedgerParseResult<Posting> {
+fn parse_posting(input: &str) -> LedgerParseResult<'_, Posting<'_>> {
     let (input, _) = space1(input)?;
+    let start = input;
     let (input, status) = opt(parse_transaction_status)(input)?;
     let (input, _) = space0(input)?;
     let (input, (account, reality)) = parse_account(input)?;
@@ -470,7 +521,7 @@ fn parse_posting(input: &str) -> LedgerParseResult<Posting> {
     Ok((
         input,
         Posting {
-            account: account.to_owned(),
+            account,
             reality,
             amount,
             balance,
@@ -481,26 +532,52 @@ fn parse_posting(input: &str) -> LedgerParseResult<Posting> {
                 effective_date,
                 tags,
             },
+            generated: false,
+            span: Some(span_between(start, input)),
         },
     ))
 }
 
-fn parse_payee(input: &str) -> LedgerParseResult<&str> {
+fn parse_payee(input: &str) -> LedgerParseResult<'_, &str> {
     alt((
         terminated(take_until_hard_separator, peek(pair(space1, char(';')))),
         not_line_ending,
     ))(input)
 }
 
-fn parse_transaction(input: &str) -> LedgerParseResult<Transaction> {
-    let (input, date) = parse_date(input)?;
-    let (input, effective_date) = opt(preceded(char('='), parse_date))(input)?;
-    let (input, status) = opt(preceded(space1, parse_transaction_status))(input)?;
-    let (input, code) = opt(preceded(
-        space1,
-        delimited(char('('), is_not(")"), char(')')),
-    ))(input)?;
-    let (input, description) = opt(preceded(space1, parse_payee))(input)?;
+/// Date, effective date, status, code and description of a transaction.
+type TransactionHeader<'a> = (
+    NaiveDate,
+    Option<NaiveDate>,
+    Option<TransactionStatus>,
+    Option<&'a str>,
+    Option<&'a str>,
+);
+
+/// Header of a transaction: its first line up to the comment.
+fn parse_transaction_header(input: &str) -> LedgerParseResult<'_, TransactionHeader<'_>> {
+    tuple((
+        parse_date,
+        opt(preceded(char('='), parse_date)),
+        opt(preceded(space1, parse_transaction_status)),
+        opt(preceded(
+            space1,
+            delimited(char('('), is_not(")"), char(')')),
+        )),
+        opt(preceded(space1, parse_payee)),
+    ))(input)
+}
+
+/// Length of the header of the transaction at the start of `input`.
+pub(crate) fn transaction_header_len(input: &str) -> Option<usize> {
+    let (rest, _) = parse_transaction_header(input).ok()?;
+    Some(input.len() - rest.len())
+}
+
+fn parse_transaction(input: &str) -> LedgerParseResult<'_, Transaction<'_>> {
+    let start = input;
+    let (input, (date, effective_date, status, code, description)) =
+        parse_transaction_header(input)?;
 
     let (
         input,
@@ -520,47 +597,392 @@ fn parse_transaction(input: &str) -> LedgerParseResult<Transaction> {
             date,
             effective_date,
             status,
-            code: code.map(str::to_owned),
-            description: description.map(str::to_owned),
+            code,
+            description,
+            posting_metadata: PostingMetadata {
+                date: posting_date,
+                effective_date: posting_effective_date,
+                tags,
+            },
+            postings,
+            forecast: false,
+            span: Some(span_between(start, input)),
+        },
+    ))
+}
+
+fn parse_automated_amount(input: &str) -> LedgerParseResult<'_, AutomatedAmount<'_>> {
+    alt((
+        parse_amount.map(AutomatedAmount::Amount),
+        parse_quantity.map(AutomatedAmount::Multiplier),
+    ))(input)
+}
+
+fn parse_automated_posting(input: &str) -> LedgerParseResult<'_, AutomatedPosting<'_>> {
+    let (input, _) = space1(input)?;
+    let start = input;
+    let (input, (account, reality)) = parse_account(input)?;
+    let (input, amount) = preceded(space0, parse_automated_amount)(input)?;
+
+    let (
+        input,
+        Metadata {
+            comment,
+            date,
+            effective_date,
+            tags,
+        },
+    ) = parse_metadata_comments(input)?;
+
+    Ok((
+        input,
+        AutomatedPosting {
+            account,
+            reality,
+            amount,
+            comment,
+            metadata: PostingMetadata {
+                date,
+                effective_date,
+                tags,
+            },
+            span: Some(span_between(start, input)),
+        },
+    ))
+}
+
+fn parse_automated_transaction(input: &str) -> LedgerParseResult<'_, AutomatedTransaction<'_>> {
+    let start = input;
+    let (input, _) = char('=')(input)?;
+    let (input, predicate) = preceded(
+        space0,
+        verify(parse_payee.map(str::trim_end), |s: &str| !s.is_empty()),
+    )(input)?;
+
+    let (
+        input,
+        Metadata {
+            comment,
+            date: posting_date,
+            effective_date: posting_effective_date,
+            tags,
+        },
+    ) = parse_metadata_comments(input)?;
+    let (input, postings) = many1(parse_automated_posting)(input)?;
+
+    Ok((
+        input,
+        AutomatedTransaction {
+            predicate,
+            comment,
             posting_metadata: PostingMetadata {
                 date: posting_date,
                 effective_date: posting_effective_date,
                 tags,
             },
             postings,
+            span: Some(span_between(start, input)),
         },
     ))
 }
 
-fn parse_ledger_item(input: &str) -> LedgerParseResult<LedgerItem> {
+fn parse_periodic_transaction(input: &str) -> LedgerParseResult<'_, PeriodicTransaction<'_>> {
+    let start = input;
+    let (input, _) = char('~')(input)?;
+    let (input, period) = preceded(
+        space0,
+        verify(parse_payee.map(str::trim_end), |s: &str| !s.is_empty()),
+    )(input)?;
+
+    let (
+        input,
+        Metadata {
+            comment,
+            date: posting_date,
+            effective_date: posting_effective_date,
+            tags,
+        },
+    ) = parse_metadata_comments(input)?;
+    let (input, postings) = many1(parse_posting)(input)?;
+
+    Ok((
+        input,
+        PeriodicTransaction {
+            period,
+            comment,
+            posting_metadata: PostingMetadata {
+                date: posting_date,
+                effective_date: posting_effective_date,
+                tags,
+            },
+            postings,
+            span: Some(span_between(start, input)),
+        },
+    ))
+}
+
+fn parse_ledger_item(input: &str) -> LedgerParseResult<'_, LedgerItem<'_>> {
     alt((
         value(LedgerItem::EmptyLine, parse_empty_line),
-        parse_global_line_comment
-            .map(str::to_owned)
-            .map(LedgerItem::LineComment),
+        spanned(parse_global_line_comment).map(|(comment, span)| {
+            LedgerItem::LineComment(LineComment {
+                comment,
+                span: Some(span),
+            })
+        }),
         parse_transaction.map(LedgerItem::Transaction),
         parse_commodity_price.map(LedgerItem::CommodityPrice),
-        parse_include_file
-            .map(str::to_owned)
-            .map(LedgerItem::Include),
+        spanned(parse_include_file).map(|(file, span)| {
+            LedgerItem::Include(Include {
+                file,
+                span: Some(span),
+            })
+        }),
+        parse_automated_transaction.map(LedgerItem::AutomatedTransaction),
+        parse_periodic_transaction.map(LedgerItem::PeriodicTransaction),
     ))(input)
 }
 
-pub fn parse_ledger(input: &str) -> LedgerParseResult<Ledger> {
+pub fn parse_ledger(input: &str) -> LedgerParseResult<'_, Ledger<'_>> {
+    parse_ledger_at(input, 0, 1)
+}
+
+/// Parses a part of a larger source which starts at byte `offset` and line `first_line`.
+/// Spans of the items are positions within the larger source.
+pub fn parse_ledger_at(
+    input: &str,
+    offset: usize,
+    first_line: usize,
+) -> LedgerParseResult<'_, Ledger<'_>> {
+    let source = input;
     let (input, items) = many0(parse_ledger_item)(input)?;
     let (input, _) = eof(input)?;
 
-    Ok((input, Ledger { items }))
+    let mut ledger = Ledger { items };
+    let resolver = SpanResolver::new(source, 0).at(offset, first_line);
+    for item in &mut ledger.items {
+        resolver.resolve_item(item);
+    }
+    Ok((input, ledger))
+}
+
+/// Checks if a line can only be the beginning of a new top-level item.
+///
+/// Indented lines, empty lines and `;` comments are excluded, as they may still belong
+/// to the preceding transaction. Splitting the source before such lines and parsing the
+/// parts separately gives the same items as parsing it at once.
+pub fn starts_item(line: &str) -> bool {
+    matches!(line.chars().next(), Some(c) if !c.is_whitespace() && c != ';')
+}
+
+/// Offsets of the lines in `input` (other than the first one) for which `starts_item` holds.
+pub fn item_starts(input: &str) -> impl Iterator<Item = usize> + '_ {
+    input
+        .match_indices('\n')
+        .map(|(pos, _)| pos + 1)
+        .filter(move |&pos| starts_item(&input[pos..]))
+}
+
+/// Formats a parsing error of a part of a larger source which starts at line `first_line`.
+pub fn convert_error_at(input: &str, error: VerboseError<&str>, first_line: usize) -> String {
+    let message = convert_error(input, error);
+    if first_line == 1 {
+        return message;
+    }
+
+    // Each entry of the message starts with a "N: at line L" header.
+    let mut result = String::with_capacity(message.len());
+    let mut entry_start = true;
+    for line in message.split_inclusive('\n') {
+        match line
+            .split_once(": at line ")
+            .filter(|(index, _)| entry_start && index.parse::<usize>().is_ok())
+        {
+            Some((index, rest)) => {
+                let digits =
+                    rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+                let line_number = rest[..digits].parse::<usize>().unwrap_or(0) + first_line - 1;
+                result.push_str(&format!(
+                    "{}: at line {}{}",
+                    index,
+                    line_number,
+                    &rest[digits..]
+                ));
+            }
+            None => result.push_str(line),
+        }
+        entry_start = line.trim().is_empty();
+    }
+    result
+}
+
+/// Parses ledger items together with the exact source text each one was parsed from,
+/// including its line ending. Spans of every item are relative to its own text.
+pub fn parse_ledger_items(input: &str) -> LedgerParseResult<'_, Vec<(LedgerItem<'_>, &str)>> {
+    let (input, mut items) = many0(consumed(parse_ledger_item))(input)?;
+    let (input, _) = eof(input)?;
+
+    let mut tail = 0;
+    for (text, item) in items.iter_mut().rev() {
+        SpanResolver::new(text, tail).resolve_item(item);
+        tail += text.len();
+    }
+    Ok((
+        input,
+        items.into_iter().map(|(text, item)| (item, text)).collect(),
+    ))
+}
+
+/// Converts spans recorded during parsing into positions within `source`.
+struct SpanResolver {
+    len: usize,
+    line_starts: Vec<usize>,
+    offset: usize,
+    first_line: usize,
+}
+
+impl SpanResolver {
+    /// `tail` is the length of the parsed input that followed `source`.
+    fn new(source: &str, tail: usize) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(pos, _)| pos + 1))
+            .collect();
+        SpanResolver {
+            len: source.len() + tail,
+            line_starts,
+            offset: 0,
+            first_line: 1,
+        }
+    }
+
+    /// Makes the positions relative to a larger source in which `source` starts
+    /// at byte `offset` and line `first_line`.
+    fn at(self, offset: usize, first_line: usize) -> Self {
+        SpanResolver {
+            offset,
+            first_line,
+            ..self
+        }
+    }
+
+    fn line_of(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|&start| start <= offset)
+    }
+
+    fn resolve(&self, span: &mut Option<Span>) {
+        if let Some(span) = span {
+            let start = self.len - span.bytes.start;
+            let end = self.len - span.bytes.end;
+            let first_line = self.line_of(start);
+            let last_line = if end > start {
+                self.line_of(end - 1)
+            } else {
+                first_line - 1
+            };
+            span.bytes = self.offset + start..self.offset + end;
+            span.lines = self.first_line - 1 + first_line..self.first_line + last_line;
+        }
+    }
+
+    fn resolve_item(&self, item: &mut LedgerItem) {
+        match item {
+            LedgerItem::LineComment(comment) => self.resolve(&mut comment.span),
+            LedgerItem::Include(include) => self.resolve(&mut include.span),
+            LedgerItem::Transaction(transaction) => self.resolve_transaction(transaction),
+            LedgerItem::CommodityPrice(commodity_price) => {
+                self.resolve(&mut commodity_price.span);
+                self.resolve(&mut commodity_price.amount.span);
+            }
+            LedgerItem::AutomatedTransaction(automated) => {
+                self.resolve(&mut automated.span);
+                self.resolve_tags(&mut automated.posting_metadata.tags);
+                for posting in &mut automated.postings {
+                    self.resolve(&mut posting.span);
+                    if let AutomatedAmount::Amount(ref mut amount) = posting.amount {
+                        self.resolve(&mut amount.span);
+                    }
+                    self.resolve_tags(&mut posting.metadata.tags);
+                }
+            }
+            LedgerItem::PeriodicTransaction(periodic) => {
+                self.resolve(&mut periodic.span);
+                self.resolve_tags(&mut periodic.posting_metadata.tags);
+                self.resolve_postings(&mut periodic.postings);
+            }
+            _ => {}
+        }
+    }
+
+    fn resolve_transaction(&self, transaction: &mut Transaction) {
+        self.resolve(&mut transaction.span);
+        self.resolve_tags(&mut transaction.posting_metadata.tags);
+        self.resolve_postings(&mut transaction.postings);
+    }
+
+    fn resolve_postings(&self, postings: &mut [Posting]) {
+        for posting in postings {
+            self.resolve(&mut posting.span);
+            if let Some(ref mut amount) = posting.amount {
+                self.resolve(&mut amount.span);
+                self.resolve(&mut amount.amount.span);
+                for price in amount.lot_price.iter_mut().chain(amount.price.iter_mut()) {
+                    match price {
+                        Price::Unit(amount) | Price::Total(amount) => {
+                            self.resolve(&mut amount.span)
+                        }
+                    }
+                }
+            }
+            if let Some(Balance::Amount(ref mut amount)) = posting.balance {
+                self.resolve(&mut amount.span);
+            }
+            self.resolve_tags(&mut posting.metadata.tags);
+        }
+    }
+
+    fn resolve_tags(&self, tags: &mut [Tag]) {
+        for tag in tags {
+            self.resolve(&mut tag.span);
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::model::{
+        Amount, Balance, Commodity, CommodityPrice, Ledger, LedgerItem, Posting, PostingAmount,
+        PostingMetadata, Price, Tag, TagValue, Transaction,
+    };
     use nom::{
         error::{ErrorKind, ParseError},
         Err::Error,
     };
 
+    // The parsers build the borrowed model, the tests compare with the owned one.
+    macro_rules! owned_parsers {
+        ($($name:ident -> $output:ty),* $(,)?) => {
+            $(
+                fn $name(input: &str) -> LedgerParseResult<'_, $output> {
+                    super::$name(input).map(|(input, output)| (input, output.into_owned()))
+                }
+            )*
+        };
+    }
+
+    owned_parsers! {
+        parse_commodity -> String,
+        parse_amount -> Amount,
+        parse_posting_amount -> PostingAmount,
+        parse_lot_price -> Price,
+        parse_price -> Price,
+        parse_balance -> Balance,
+        parse_commodity_price -> CommodityPrice,
+        parse_posting -> Posting,
+        parse_transaction -> Transaction,
+        parse_ledger -> Ledger,
+    }
+
     #[test]
     fn parse_date_test() {
         assert_eq!(
@@ -651,7 +1073,8 @@ mod tests {
                     commodity: Commodity {
                         name: "$".to_owned(),
                         position: CommodityPosition::Left
-                    }
+                    },
+                    span: None,
                 }
             ))
         );
@@ -664,7 +1087,8 @@ mod tests {
                     commodity: Commodity {
                         name: "$".to_owned(),
                         position: CommodityPosition::Left
-                    }
+                    },
+                    span: None,
                 }
             ))
         );
@@ -677,7 +1101,8 @@ mod tests {
                     commodity: Commodity {
                         name: "$".to_owned(),
                         position: CommodityPosition::Left
-                    }
+                    },
+                    span: None,
                 }
             ))
         );
@@ -690,7 +1115,8 @@ mod tests {
                     commodity: Commodity {
                         name: "$".to_owned(),
                         position: CommodityPosition::Left
-                    }
+                    },
+                    span: None,
                 }
             ))
         );
@@ -703,7 +1129,8 @@ mod tests {
                     commodity: Commodity {
                         name: "USD".to_owned(),
                         position: CommodityPosition::Right
-                    }
+                    },
+                    span: None,
                 }
             ))
         );
@@ -716,7 +1143,8 @@ mod tests {
                     commodity: Commodity {
                         name: "USD".to_owned(),
                         position: CommodityPosition::Right
-                    }
+                    },
+                    span: None,
                 }
             ))
         );
@@ -729,7 +1157,8 @@ mod tests {
                     commodity: Commodity {
                         name: "USD".to_owned(),
                         position: CommodityPosition::Right
-                    }
+                    },
+                    span: None,
                 }
             ))
         );
@@ -746,7 +1175,8 @@ mod tests {
                     commodity: Commodity {
                         name: "$".to_owned(),
                         position: CommodityPosition::Left
-                    }
+                    },
+                    span: None,
                 })
             ))
         );
@@ -759,7 +1189,8 @@ mod tests {
                     commodity: Commodity {
                         name: "$".to_owned(),
                         position: CommodityPosition::Left
-                    }
+                    },
+                    span: None,
                 })
             ))
         );
@@ -772,7 +1203,8 @@ mod tests {
                     commodity: Commodity {
                         name: "PLN".to_owned(),
                         position: CommodityPosition::Right
-                    }
+                    },
+                    span: None,
                 })
             ))
         );
@@ -785,7 +1217,8 @@ mod tests {
                     commodity: Commodity {
                         name: "PLN".to_owned(),
                         position: CommodityPosition::Right
-                    }
+                    },
+                    span: None,
                 })
             ))
         );
@@ -802,7 +1235,8 @@ mod tests {
                     commodity: Commodity {
                         name: "$".to_owned(),
                         position: CommodityPosition::Left
-                    }
+                    },
+                    span: None,
                 })
             ))
         );
@@ -815,7 +1249,8 @@ mod tests {
                     commodity: Commodity {
                         name: "$".to_owned(),
                         position: CommodityPosition::Left
-                    }
+                    },
+                    span: None,
                 })
             ))
         );
@@ -828,7 +1263,8 @@ mod tests {
                     commodity: Commodity {
                         name: "PLN".to_owned(),
                         position: CommodityPosition::Right
-                    }
+                    },
+                    span: None,
                 })
             ))
         );
@@ -841,7 +1277,8 @@ mod tests {
                     commodity: Commodity {
                         name: "PLN".to_owned(),
                         position: CommodityPosition::Right
-                    }
+                    },
+                    span: None,
                 })
             ))
         );
@@ -859,10 +1296,12 @@ mod tests {
                         commodity: Commodity {
                             name: "$".to_owned(),
                             position: CommodityPosition::Left
-                        }
+                        },
+                        span: None,
                     },
                     lot_price: None,
-                    price: None
+                    price: None,
+                    span: None,
                 }
             ))
         );
@@ -876,7 +1315,8 @@ mod tests {
                         commodity: Commodity {
                             name: "$".to_owned(),
                             position: CommodityPosition::Left
-                        }
+                        },
+                        span: None,
                     },
                     lot_price: None,
                     price: Some(Price::Unit(Amount {
@@ -884,8 +1324,10 @@ mod tests {
                         commodity: Commodity {
                             name: "PLN".to_owned(),
                             position: CommodityPosition::Right
-                        }
-                    }))
+                        },
+                        span: None,
+                    })),
+                    span: None,
                 }
             ))
         );
@@ -899,16 +1341,19 @@ mod tests {
                         commodity: Commodity {
                             name: "$".to_owned(),
                             position: CommodityPosition::Left
-                        }
+                        },
+                        span: None,
                     },
                     lot_price: Some(Price::Unit(Amount {
                         quantity: Decimal::new(500, 2),
                         commodity: Commodity {
                             name: "PLN".to_owned(),
                             position: CommodityPosition::Right
-                        }
+                        },
+                        span: None,
                     })),
                     price: None,
+                    span: None,
                 }
             ))
         );
@@ -922,22 +1367,26 @@ mod tests {
                         commodity: Commodity {
                             name: "$".to_owned(),
                             position: CommodityPosition::Left
-                        }
+                        },
+                        span: None,
                     },
                     lot_price: Some(Price::Total(Amount {
                         quantity: Decimal::new(500, 2),
                         commodity: Commodity {
                             name: "PLN".to_owned(),
                             position: CommodityPosition::Right
-                        }
+                        },
+                        span: None,
                     })),
                     price: Some(Price::Total(Amount {
                         quantity: Decimal::new(600, 2),
                         commodity: Commodity {
                             name: "PLN".to_owned(),
                             position: CommodityPosition::Right
-                        }
+                        },
+                        span: None,
                     })),
+                    span: None,
                 }
             ))
         );
@@ -954,7 +1403,8 @@ mod tests {
                     commodity: Commodity {
                         name: "$".to_owned(),
                         position: CommodityPosition::Left
-                    }
+                    },
+                    span: None,
                 })
             ))
         );
@@ -967,7 +1417,8 @@ mod tests {
                     commodity: Commodity {
                         name: "PLN".to_owned(),
                         position: CommodityPosition::Right
-                    }
+                    },
+                    span: None,
                 })
             ))
         );
@@ -991,8 +1442,10 @@ mod tests {
                         commodity: Commodity {
                             name: "PLN".to_owned(),
                             position: CommodityPosition::Right
-                        }
-                    }
+                        },
+                        span: None,
+                    },
+                    span: None,
                 }
             ))
         );
@@ -1020,6 +1473,9 @@ mod tests {
             parse_account("(TEST:ABC 123)"),
             Ok(("", ("TEST:ABC 123", Reality::UnbalancedVirtual)))
         );
+        assert!(parse_account("TEST::ABC  ").is_err());
+        assert!(parse_account("[:TEST]").is_err());
+        assert!(parse_account("TEST:  ").is_err());
     }
 
     #[test]
@@ -1041,7 +1497,7 @@ mod tests {
             Ok((
                 "",
                 Posting {
-                    account: "TEST:ABC 123".to_owned(),
+                    account: "TEST:ABC 123".parse().unwrap(),
                     reality: Reality::Real,
                     amount: Some(PostingAmount {
                         amount: Amount {
@@ -1049,10 +1505,12 @@ mod tests {
                             commodity: Commodity {
                                 name: "$".to_owned(),
                                 position: CommodityPosition::Left
-                            }
+                            },
+                            span: None,
                         },
                         lot_price: None,
-                        price: None
+                        price: None,
+                        span: None,
                     }),
                     balance: None,
                     status: None,
@@ -1062,6 +1520,8 @@ mod tests {
                         effective_date: None,
                         tags: vec![],
                     },
+                    generated: false,
+                    span: None,
                 }
             ))
         );
@@ -1070,7 +1530,7 @@ mod tests {
             Ok((
                 "",
                 Posting {
-                    account: "TEST:ABC 123".to_owned(),
+                    account: "TEST:ABC 123".parse().unwrap(),
                     reality: Reality::Real,
                     amount: Some(PostingAmount {
                         amount: Amount {
@@ -1078,10 +1538,12 @@ mod tests {
                             commodity: Commodity {
                                 name: "$".to_owned(),
                                 position: CommodityPosition::Left
-                            }
+                            },
+                            span: None,
                         },
                         lot_price: None,
-                        price: None
+                        price: None,
+                        span: None,
                     }),
                     balance: None,
                     status: Some(TransactionStatus::Pending),
@@ -1091,6 +1553,8 @@ mod tests {
                         effective_date: None,
                         tags: vec![],
                     },
+                    generated: false,
+                    span: None,
                 }
             ))
         );
@@ -1099,7 +1563,7 @@ mod tests {
             Ok((
                 "",
                 Posting {
-                    account: "TEST:ABC 123;test".to_owned(),
+                    account: "TEST:ABC 123;test".parse().unwrap(),
                     reality: Reality::Real,
                     amount: None,
                     balance: None,
@@ -1110,6 +1574,8 @@ mod tests {
                         effective_date: None,
                         tags: vec![],
                     },
+                    generated: false,
+                    span: None,
                 }
             ))
         );
@@ -1118,7 +1584,7 @@ mod tests {
             Ok((
                 "",
                 Posting {
-                    account: "TEST:ABC 123".to_owned(),
+                    account: "TEST:ABC 123".parse().unwrap(),
                     reality: Reality::Real,
                     amount: None,
                     balance: None,
@@ -1129,6 +1595,8 @@ mod tests {
                         effective_date: None,
                         tags: vec![],
                     },
+                    generated: false,
+                    span: None,
                 }
             ))
         );
@@ -1137,7 +1605,7 @@ mod tests {
             Ok((
                 "",
                 Posting {
-                    account: "TEST:ABC 123".to_owned(),
+                    account: "TEST:ABC 123".parse().unwrap(),
                     reality: Reality::Real,
                     amount: None,
                     balance: None,
@@ -1148,6 +1616,8 @@ mod tests {
                         effective_date: None,
                         tags: vec![],
                     },
+                    generated: false,
+                    span: None,
                 }
             ))
         );
@@ -1156,7 +1626,7 @@ mod tests {
             Ok((
                 "",
                 Posting {
-                    account: "TEST:ABC 123".to_owned(),
+                    account: "TEST:ABC 123".parse().unwrap(),
                     reality: Reality::Real,
                     amount: Some(PostingAmount {
                         amount: Amount {
@@ -1164,17 +1634,20 @@ mod tests {
                             commodity: Commodity {
                                 name: "$".to_owned(),
                                 position: CommodityPosition::Left
-                            }
+                            },
+                            span: None,
                         },
                         lot_price: None,
-                        price: None
+                        price: None,
+                        span: None,
                     }),
                     balance: Some(Balance::Amount(Amount {
                         quantity: Decimal::new(240, 2),
                         commodity: Commodity {
                             name: "$".to_owned(),
                             position: CommodityPosition::Left
-                        }
+                        },
+                        span: None,
                     })),
                     status: None,
                     comment: Some("comment".to_owned()),
@@ -1183,6 +1656,8 @@ mod tests {
                         effective_date: None,
                         tags: vec![],
                     },
+                    generated: false,
+                    span: None,
                 }
             ))
         );
@@ -1191,7 +1666,7 @@ mod tests {
             Ok((
                 "",
                 Posting {
-                    account: "TEST:ABC 123".to_owned(),
+                    account: "TEST:ABC 123".parse().unwrap(),
                     reality: Reality::Real,
                     amount: None,
                     balance: None,
@@ -1202,6 +1677,8 @@ mod tests {
                         effective_date: None,
                         tags: vec![],
                     },
+                    generated: false,
+                    span: None,
                 }
             ))
         );
@@ -1210,7 +1687,7 @@ mod tests {
             Ok((
                 "",
                 Posting {
-                    account: "TEST:ABC 123".to_owned(),
+                    account: "TEST:ABC 123".parse().unwrap(),
                     reality: Reality::Real,
                     amount: None,
                     balance: None,
@@ -1221,6 +1698,8 @@ mod tests {
                         effective_date: None,
                         tags: vec![],
                     },
+                    generated: false,
+                    span: None,
                 }
             ))
         );
@@ -1229,7 +1708,7 @@ mod tests {
             Ok((
                 "",
                 Posting {
-                    account: "TEST:ABC 123".to_owned(),
+                    account: "TEST:ABC 123".parse().unwrap(),
                     reality: Reality::Real,
                     amount: None,
                     balance: None,
@@ -1240,6 +1719,8 @@ mod tests {
                         effective_date: None,
                         tags: vec![],
                     },
+                    generated: false,
+                    span: None,
                 }
             ))
         );
@@ -1248,7 +1729,7 @@ mod tests {
             Ok((
                 "",
                 Posting {
-                    account: "TEST:ABC 123".to_owned(),
+                    account: "TEST:ABC 123".parse().unwrap(),
                     reality: Reality::Real,
                     amount: None,
                     balance: None,
@@ -1259,6 +1740,8 @@ mod tests {
                         effective_date: Some(NaiveDate::from_ymd_opt(2018, 10, 1).unwrap()),
                         tags: vec![],
                     },
+                    generated: false,
+                    span: None,
                 }
             ))
         );
@@ -1267,7 +1750,7 @@ mod tests {
             Ok((
                 "",
                 Posting {
-                    account: "TEST:ABC 123".to_owned(),
+                    account: "TEST:ABC 123".parse().unwrap(),
                     reality: Reality::Real,
                     amount: None,
                     balance: None,
@@ -1278,6 +1761,8 @@ mod tests {
                         effective_date: Some(NaiveDate::from_ymd_opt(2018, 10, 14).unwrap()),
                         tags: vec![],
                     },
+                    generated: false,
+                    span: None,
                 }
             ))
         );
@@ -1286,7 +1771,7 @@ mod tests {
             Ok((
                 "",
                 Posting {
-                    account: "TEST:ABC 123".to_owned(),
+                    account: "TEST:ABC 123".parse().unwrap(),
                     reality: Reality::Real,
                     amount: None,
                     balance: None,
@@ -1298,8 +1783,11 @@ mod tests {
                         tags: vec![Tag {
                             name: "Tag".to_owned(),
                             value: Some(TagValue::String("tag value".to_owned())),
+                            span: None,
                         }],
                     },
+                    generated: false,
+                    span: None,
                 }
             ))
         );
@@ -1308,7 +1796,7 @@ mod tests {
             Ok((
                 "",
                 Posting {
-                    account: "TEST:ABC 123".to_owned(),
+                    account: "TEST:ABC 123".parse().unwrap(),
                     reality: Reality::Real,
                     amount: None,
                     balance: None,
@@ -1321,13 +1809,17 @@ mod tests {
                             Tag {
                                 name: "tag1".to_owned(),
                                 value: None,
+                                span: None,
                             },
                             Tag {
                                 name: "tag2".to_owned(),
                                 value: None,
+                                span: None,
                             }
                         ],
                     },
+                    generated: false,
+                    span: None,
                 }
             ))
         );
@@ -1336,7 +1828,7 @@ mod tests {
             Ok((
                 "",
                 Posting {
-                    account: "TEST:ABC 123".to_owned(),
+                    account: "TEST:ABC 123".parse().unwrap(),
                     reality: Reality::Real,
                     amount: None,
                     balance: None,
@@ -1349,13 +1841,17 @@ mod tests {
                             Tag {
                                 name: "tag1".to_owned(),
                                 value: None,
+                                span: None,
                             },
                             Tag {
                                 name: "tag2".to_owned(),
                                 value: None,
+                                span: None,
                             }
                         ],
                     },
+                    generated: false,
+                    span: None,
                 }
             ))
         );
@@ -1364,7 +1860,7 @@ mod tests {
             Ok((
                 "",
                 Posting {
-                    account: "TEST:ABC 123".to_owned(),
+                    account: "TEST:ABC 123".parse().unwrap(),
                     reality: Reality::Real,
                     amount: None,
                     balance: None,
@@ -1377,9 +1873,12 @@ mod tests {
                             name: "Tag".to_owned(),
                             value: Some(TagValue::Date(
                                 NaiveDate::from_ymd_opt(2018, 1, 5).unwrap()
-                            ))
+                            )),
+                            span: None,
                         }],
                     },
+                    generated: false,
+                    span: None,
                 }
             ))
         );
@@ -1388,7 +1887,7 @@ mod tests {
             Ok((
                 "",
                 Posting {
-                    account: "TEST:ABC 123".to_owned(),
+                    account: "TEST:ABC 123".parse().unwrap(),
                     reality: Reality::Real,
                     amount: None,
                     balance: None,
@@ -1399,9 +1898,12 @@ mod tests {
                         effective_date: None,
                         tags: vec![Tag {
                             name: "TheAnswer".to_owned(),
-                            value: Some(TagValue::Integer(42))
+                            value: Some(TagValue::Integer(42)),
+                            span: None,
                         }],
                     },
+                    generated: false,
+                    span: None,
                 }
             ))
         );
@@ -1410,7 +1912,7 @@ mod tests {
             Ok((
                 "",
                 Posting {
-                    account: "TEST:ABC 123".to_owned(),
+                    account: "TEST:ABC 123".parse().unwrap(),
                     reality: Reality::Real,
                     amount: None,
                     balance: None,
@@ -1421,9 +1923,12 @@ mod tests {
                         effective_date: None,
                         tags: vec![Tag {
                             name: "ISquared".to_owned(),
-                            value: Some(TagValue::Integer(-1))
+                            value: Some(TagValue::Integer(-1)),
+                            span: None,
                         }],
                     },
+                    generated: false,
+                    span: None,
                 }
             ))
         );
@@ -1432,7 +1937,7 @@ mod tests {
             Ok((
                 "",
                 Posting {
-                    account: "TEST:ABC 123".to_owned(),
+                    account: "TEST:ABC 123".parse().unwrap(),
                     reality: Reality::Real,
                     amount: None,
                     balance: None,
@@ -1445,9 +1950,12 @@ mod tests {
                             name: "Pi".to_owned(),
                             value: Some(TagValue::Float(
                                 NotNan::new(std::f64::consts::PI).unwrap()
-                            ))
+                            )),
+                            span: None,
                         }],
                     },
+                    generated: false,
+                    span: None,
                 }
             ))
         );
@@ -1478,7 +1986,7 @@ mod tests {
                     description: Some("Marek Ogarek".to_owned()),
                     postings: vec![
                         Posting {
-                            account: "TEST:ABC 123".to_owned(),
+                            account: "TEST:ABC 123".parse().unwrap(),
                             reality: Reality::Real,
                             amount: Some(PostingAmount {
                                 amount: Amount {
@@ -1486,10 +1994,12 @@ mod tests {
                                     commodity: Commodity {
                                         name: "$".to_owned(),
                                         position: CommodityPosition::Left
-                                    }
+                                    },
+                                    span: None,
                                 },
                                 lot_price: None,
-                                price: None
+                                price: None,
+                                span: None,
                             }),
                             balance: None,
                             status: None,
@@ -1499,9 +2009,11 @@ mod tests {
                                 effective_date: None,
                                 tags: vec![],
                             },
+                            generated: false,
+                            span: None,
                         },
                         Posting {
-                            account: "TEST:ABC 123".to_owned(),
+                            account: "TEST:ABC 123".parse().unwrap(),
                             reality: Reality::Real,
                             amount: Some(PostingAmount {
                                 amount: Amount {
@@ -1509,10 +2021,12 @@ mod tests {
                                     commodity: Commodity {
                                         name: "$".to_owned(),
                                         position: CommodityPosition::Left
-                                    }
+                                    },
+                                    span: None,
                                 },
                                 lot_price: None,
-                                price: None
+                                price: None,
+                                span: None,
                             }),
                             balance: None,
                             status: None,
@@ -1522,8 +2036,12 @@ mod tests {
                                 effective_date: None,
                                 tags: vec![],
                             },
+                            generated: false,
+                            span: None,
                         }
-                    ]
+                    ],
+                    forecast: false,
+                    span: None,
                 }
             ))
         );
@@ -1551,7 +2069,7 @@ mod tests {
                     description: Some("Marek Ogarek ; one space".to_owned()),
                     postings: vec![
                         Posting {
-                            account: "TEST:ABC 123".to_owned(),
+                            account: "TEST:ABC 123".parse().unwrap(),
                             reality: Reality::Real,
                             amount: Some(PostingAmount {
                                 amount: Amount {
@@ -1559,10 +2077,12 @@ mod tests {
                                     commodity: Commodity {
                                         name: "$".to_owned(),
                                         position: CommodityPosition::Left
-                                    }
+                                    },
+                                    span: None,
                                 },
                                 lot_price: None,
-                                price: None
+                                price: None,
+                                span: None,
                             }),
                             balance: None,
                             status: None,
@@ -1572,10 +2092,12 @@ mod tests {
                                 effective_date: None,
                                 tags: vec![],
                             },
+                            generated: false,
+                            span: None,
                         },
                         Posting {
                             balance: None,
-                            account: "TEST:DEF 123".to_owned(),
+                            account: "TEST:DEF 123".parse().unwrap(),
                             reality: Reality::Real,
                             amount: Some(PostingAmount {
                                 amount: Amount {
@@ -1583,10 +2105,12 @@ mod tests {
                                     commodity: Commodity {
                                         name: "EUR".to_owned(),
                                         position: CommodityPosition::Left
-                                    }
+                                    },
+                                    span: None,
                                 },
                                 lot_price: None,
-                                price: None
+                                price: None,
+                                span: None,
                             }),
                             status: None,
                             comment: None,
@@ -1595,9 +2119,11 @@ mod tests {
                                 effective_date: None,
                                 tags: vec![],
                             },
+                            generated: false,
+                            span: None,
                         },
                         Posting {
-                            account: "TEST:GHI 123".to_owned(),
+                            account: "TEST:GHI 123".parse().unwrap(),
                             reality: Reality::Real,
                             amount: None,
                             balance: None,
@@ -1608,9 +2134,11 @@ mod tests {
                                 effective_date: None,
                                 tags: vec![],
                             },
+                            generated: false,
+                            span: None,
                         },
                         Posting {
-                            account: "TEST:JKL 123".to_owned(),
+                            account: "TEST:JKL 123".parse().unwrap(),
                             reality: Reality::Real,
                             amount: Some(PostingAmount {
                                 amount: Amount {
@@ -1618,10 +2146,12 @@ mod tests {
                                     commodity: Commodity {
                                         name: "EUR".to_owned(),
                                         position: CommodityPosition::Left
-                                    }
+                                    },
+                                    span: None,
                                 },
                                 lot_price: None,
-                                price: None
+                                price: None,
+                                span: None,
                             }),
                             balance: None,
                             status: None,
@@ -1631,8 +2161,12 @@ mod tests {
                                 effective_date: None,
                                 tags: vec![],
                             },
+                            generated: false,
+                            span: None,
                         },
-                    ]
+                    ],
+                    forecast: false,
+                    span: None,
                 }
             ))
         );
@@ -1658,7 +2192,7 @@ mod tests {
                     description: Some("Marek Ogarek  two spaces".to_owned()),
                     postings: vec![
                         Posting {
-                            account: "TEST:ABC 123".to_owned(),
+                            account: "TEST:ABC 123".parse().unwrap(),
                             reality: Reality::Real,
                             amount: Some(PostingAmount {
                                 amount: Amount {
@@ -1666,10 +2200,12 @@ mod tests {
                                     commodity: Commodity {
                                         name: "$".to_owned(),
                                         position: CommodityPosition::Left
-                                    }
+                                    },
+                                    span: None,
                                 },
                                 lot_price: None,
-                                price: None
+                                price: None,
+                                span: None,
                             }),
                             balance: None,
                             status: None,
@@ -1679,9 +2215,11 @@ mod tests {
                                 effective_date: None,
                                 tags: vec![],
                             },
+                            generated: false,
+                            span: None,
                         },
                         Posting {
-                            account: "TEST:DEF 123".to_owned(),
+                            account: "TEST:DEF 123".parse().unwrap(),
                             reality: Reality::Real,
                             amount: None,
                             balance: None,
@@ -1692,8 +2230,12 @@ mod tests {
                                 effective_date: None,
                                 tags: vec![],
                             },
+                            generated: false,
+                            span: None,
                         },
-                    ]
+                    ],
+                    forecast: false,
+                    span: None,
                 }
             ))
         );
@@ -1721,7 +2263,7 @@ mod tests {
                     description: None,
                     postings: vec![
                         Posting {
-                            account: "TEST:ABC 123".to_owned(),
+                            account: "TEST:ABC 123".parse().unwrap(),
                             reality: Reality::Real,
                             amount: Some(PostingAmount {
                                 amount: Amount {
@@ -1729,10 +2271,12 @@ mod tests {
                                     commodity: Commodity {
                                         name: "$".to_owned(),
                                         position: CommodityPosition::Left
-                                    }
+                                    },
+                                    span: None,
                                 },
                                 lot_price: None,
-                                price: None
+                                price: None,
+                                span: None,
                             }),
                             balance: None,
                             status: None,
@@ -1742,9 +2286,11 @@ mod tests {
                                 effective_date: None,
                                 tags: vec![],
                             },
+                            generated: false,
+                            span: None,
                         },
                         Posting {
-                            account: "TEST:DEF 123".to_owned(),
+                            account: "TEST:DEF 123".parse().unwrap(),
                             reality: Reality::Real,
                             amount: None,
                             balance: None,
@@ -1755,8 +2301,12 @@ mod tests {
                                 effective_date: None,
                                 tags: vec![],
                             },
+                            generated: false,
+                            span: None,
                         },
-                    ]
+                    ],
+                    forecast: false,
+                    span: None,
                 }
             ))
         );
@@ -1803,4 +2353,57 @@ P 2017-11-12 12:00:00 mBH 5.00 PLN  ; comment
         assert!(matches!(res.items[8], LedgerItem::EmptyLine));
         assert!(matches!(res.items[9], LedgerItem::Transaction(_)));
     }
+
+    #[test]
+    fn parse_ledger_spans_test() {
+        let input = "; Example\nP 2017-11-12 12:00:00 mBH 5.00 PLN\n2018-10-01 Payee\n  TEST:ABC  $1.20 ; :tag:\n  TEST:DEF\ninclude other.ledger  \n";
+        let ledger = parse_ledger(input).unwrap().1;
+
+        let LedgerItem::LineComment(ref comment) = ledger.items[0] else {
+            panic!("expected comment");
+        };
+        let span = comment.span.clone().unwrap();
+        assert_eq!(&input[span.bytes], "; Example");
+        assert_eq!(span.lines, 1..2);
+        let LedgerItem::Include(ref include) = ledger.items[3] else {
+            panic!("expected include");
+        };
+        let span = include.span.clone().unwrap();
+        assert_eq!(&input[span.bytes], "include other.ledger");
+        assert_eq!(span.lines, 6..7);
+
+        let LedgerItem::CommodityPrice(ref price) = ledger.items[1] else {
+            panic!("expected commodity price");
+        };
+        let span = price.span.clone().unwrap();
+        assert_eq!(&input[span.bytes], "P 2017-11-12 12:00:00 mBH 5.00 PLN");
+        assert_eq!(span.lines, 2..3);
+        assert_eq!(&input[price.amount.span.clone().unwrap().bytes], "5.00 PLN");
+
+        let LedgerItem::Transaction(ref transaction) = ledger.items[2] else {
+            panic!("expected transaction");
+        };
+        let span = transaction.span.clone().unwrap();
+        assert_eq!(
+            &input[span.bytes],
+            "2018-10-01 Payee\n  TEST:ABC  $1.20 ; :tag:\n  TEST:DEF"
+        );
+        assert_eq!(span.lines, 3..6);
+
+        let posting = &transaction.postings[0];
+        let span = posting.span.clone().unwrap();
+        assert_eq!(&input[span.bytes], "TEST:ABC  $1.20 ; :tag:");
+        assert_eq!(span.lines, 4..5);
+        let amount = posting.amount.as_ref().unwrap();
+        assert_eq!(&input[amount.span.clone().unwrap().bytes], "$1.20");
+        assert_eq!(&input[amount.amount.span.clone().unwrap().bytes], "$1.20");
+        assert_eq!(
+            &input[posting.metadata.tags[0].span.clone().unwrap().bytes],
+            "tag"
+        );
+        assert_eq!(
+            &input[transaction.postings[1].span.clone().unwrap().bytes],
+            "TEST:DEF"
+        );
+    }
 }
@@ -0,0 +1,599 @@
+use crate::model::*;
+use crate::ParseError;
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+use std::fmt;
+use std::iter::Peekable;
+use std::str::SplitWhitespace;
+
+///
+/// Length of the periods of a period expression.
+///
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Interval {
+    Days(u32),
+    Weeks(u32),
+    Months(u32),
+    Years(u32),
+}
+
+impl Interval {
+    /// Start of the calendar day, week (starting on Monday), month, quarter (for
+    /// multiples of three months) or year containing `date`.
+    pub fn align(self, date: NaiveDate) -> NaiveDate {
+        self.align_with(date, Weekday::Mon, 1)
+    }
+
+    /// Same as [`align`](Interval::align), with weeks starting on `week_start` and
+    /// quarters and years starting in the month `fiscal_year_start` (1 to 12).
+    pub fn align_with(
+        self,
+        date: NaiveDate,
+        week_start: Weekday,
+        fiscal_year_start: u32,
+    ) -> NaiveDate {
+        let months = match self {
+            Interval::Days(_) => return date,
+            Interval::Weeks(_) => {
+                let days = (date.weekday().num_days_from_monday() + 7
+                    - week_start.num_days_from_monday())
+                    % 7;
+                return date - Days::new(days.into());
+            }
+            Interval::Months(months) if months % 3 == 0 => 3,
+            Interval::Months(_) => 1,
+            Interval::Years(_) => 12,
+        };
+        let month = date.year() * 12 + date.month0() as i32;
+        let start = month - (month - (fiscal_year_start as i32 - 1)).rem_euclid(months);
+        NaiveDate::from_ymd_opt(start.div_euclid(12), start.rem_euclid(12) as u32 + 1, 1).unwrap()
+    }
+
+    /// Start of the `n`-th period after the one starting on `start`, or `None` if it
+    /// is out of the range of dates.
+    pub fn nth(self, start: NaiveDate, n: u32) -> Option<NaiveDate> {
+        match self {
+            Interval::Days(days) => {
+                start.checked_add_days(Days::new(u64::from(days) * u64::from(n)))
+            }
+            Interval::Weeks(weeks) => {
+                start.checked_add_days(Days::new((7 * u64::from(weeks)).checked_mul(u64::from(n))?))
+            }
+            Interval::Months(months) => {
+                start.checked_add_months(Months::new(months.checked_mul(n)?))
+            }
+            Interval::Years(years) => {
+                start.checked_add_months(Months::new(years.checked_mul(12)?.checked_mul(n)?))
+            }
+        }
+    }
+
+    /// Start of the period before the one starting on `start`.
+    fn previous(self, start: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            Interval::Days(days) => start.checked_sub_days(Days::new(days.into())),
+            Interval::Weeks(weeks) => start.checked_sub_days(Days::new(7 * u64::from(weeks))),
+            Interval::Months(months) => start.checked_sub_months(Months::new(months)),
+            Interval::Years(years) => start.checked_sub_months(Months::new(years.checked_mul(12)?)),
+        }
+    }
+}
+
+///
+/// Parsed period expression, as used by periodic transactions (`~ PERIOD`) and for
+/// report ranges: an optional interval with optional first and last (exclusive) dates.
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Period {
+    pub interval: Option<Interval>,
+    pub begin: Option<NaiveDate>,
+    /// Day after the last day of the period.
+    pub end: Option<NaiveDate>,
+}
+
+impl Period {
+    /// Parses a period expression like `every 2 weeks from 2024/01/01 to 2024/06/30`,
+    /// `monthly in 2024`, `last quarter` or `this year`, ignoring case.
+    ///
+    /// The interval is `daily`, `weekly`, `biweekly`, `monthly`, `bimonthly`,
+    /// `quarterly`, `yearly` (`annually`) or `every [N] day|week|month|quarter|year[s]`.
+    /// The range is given by `from` (`since`) and `to` (`until`, exclusive) followed by
+    /// a date, or by `in` (or nothing) followed by a date standing for the whole day,
+    /// month or year. Dates are `YYYY`, `YYYY/MM`, `YYYY/MM/DD`, `MM/DD`, a month name,
+    /// `today`, `yesterday`, `tomorrow` or `this|last|next day|week|month|quarter|year`
+    /// (with `-`, `/` or `.` separators). Relative dates, and dates without a year, are
+    /// resolved against `today`. Weeks start on Monday.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use ledger_parser::{Interval, Period};
+    ///
+    /// let today = NaiveDate::from_ymd_opt(2024, 5, 15).unwrap();
+    /// let period = Period::parse("monthly in 2024", today).unwrap();
+    /// assert_eq!(period.interval, Some(Interval::Months(1)));
+    /// assert_eq!(period.end, NaiveDate::from_ymd_opt(2025, 1, 1));
+    ///
+    /// let period = Period::parse("last quarter", today).unwrap();
+    /// assert_eq!(period.interval, None);
+    /// assert_eq!(period.begin, NaiveDate::from_ymd_opt(2024, 1, 1));
+    /// assert_eq!(period.end, NaiveDate::from_ymd_opt(2024, 4, 1));
+    /// ```
+    pub fn parse(text: &str, today: NaiveDate) -> Result<Period, ParseError> {
+        let lowercase = text.to_lowercase();
+        let mut words = lowercase.split_whitespace().peekable();
+        let mut period = Period {
+            interval: None,
+            begin: None,
+            end: None,
+        };
+        if words.peek().is_none() {
+            return Err(error(text, "empty period expression"));
+        }
+
+        while let Some(&word) = words.peek() {
+            let interval = match word {
+                "daily" => Some(Interval::Days(1)),
+                "weekly" => Some(Interval::Weeks(1)),
+                "biweekly" => Some(Interval::Weeks(2)),
+                "monthly" => Some(Interval::Months(1)),
+                "bimonthly" => Some(Interval::Months(2)),
+                "quarterly" => Some(Interval::Months(3)),
+                "yearly" | "annually" => Some(Interval::Years(1)),
+                _ => None,
+            };
+            if let Some(interval) = interval {
+                words.next();
+                period.set_interval(text, interval)?;
+                continue;
+            }
+
+            match word {
+                "every" => {
+                    words.next();
+                    let count = match words.peek().map(|word| word.parse::<u32>()) {
+                        Some(Ok(count)) if count > 0 => {
+                            words.next();
+                            count
+                        }
+                        Some(Ok(_)) => return Err(error(text, "interval must not be zero")),
+                        _ => 1,
+                    };
+                    let unit = words
+                        .next()
+                        .ok_or_else(|| error(text, "missing interval unit"))?;
+                    let interval = unit_interval(text, unit, count)?;
+                    period.set_interval(text, interval)?;
+                }
+                "from" | "since" => {
+                    words.next();
+                    let (begin, _) = parse_range(text, &mut words, today)?;
+                    period.set_begin(text, begin)?;
+                }
+                "to" | "until" => {
+                    words.next();
+                    let (end, _) = parse_range(text, &mut words, today)?;
+                    period.set_end(text, end)?;
+                }
+                "in" => {
+                    words.next();
+                    let (begin, end) = parse_range(text, &mut words, today)?;
+                    period.set_begin(text, begin)?;
+                    period.set_end(text, end)?;
+                }
+                _ if !is_date(word) => {
+                    return Err(error(text, &format!("unknown interval or date {:?}", word)));
+                }
+                _ => {
+                    let (begin, end) = parse_range(text, &mut words, today)?;
+                    period.set_begin(text, begin)?;
+                    period.set_end(text, end)?;
+                }
+            }
+        }
+        Ok(period)
+    }
+
+    fn set_interval(&mut self, text: &str, interval: Interval) -> Result<(), ParseError> {
+        if self.interval.replace(interval).is_some() {
+            return Err(error(text, "more than one interval"));
+        }
+        Ok(())
+    }
+
+    fn set_begin(&mut self, text: &str, begin: NaiveDate) -> Result<(), ParseError> {
+        if self.begin.replace(begin).is_some() {
+            return Err(error(text, "more than one start date"));
+        }
+        Ok(())
+    }
+
+    fn set_end(&mut self, text: &str, end: NaiveDate) -> Result<(), ParseError> {
+        if self.end.replace(end).is_some() {
+            return Err(error(text, "more than one end date"));
+        }
+        Ok(())
+    }
+
+    /// Periods (start and exclusive end) which start on or after `begin` and before `end`.
+    /// Without a first date, the periods are aligned to the calendar. Without an
+    /// interval, the whole period is a single one.
+    pub fn periods(&self, begin: NaiveDate, end: NaiveDate) -> Vec<(NaiveDate, NaiveDate)> {
+        let start = self.begin.unwrap_or_else(|| {
+            self.interval
+                .map_or(begin, |interval| interval.align(begin))
+        });
+        let end = self.end.map_or(end, |last| last.min(end));
+        let Some(interval) = self.interval else {
+            return if begin <= start && start < end {
+                vec![(start, end)]
+            } else {
+                Vec::new()
+            };
+        };
+        let mut periods = Vec::new();
+        for n in 0.. {
+            let (Some(first), Some(next)) = (interval.nth(start, n), interval.nth(start, n + 1))
+            else {
+                break;
+            };
+            if first >= end {
+                break;
+            }
+            if first >= begin {
+                periods.push((first, next));
+            }
+        }
+        periods
+    }
+}
+
+fn error(text: &str, message: &str) -> ParseError {
+    ParseError::String(format!("{} in period expression {:?}", message, text))
+}
+
+/// Interval of `count` units, where the unit may be plural.
+fn unit_interval(text: &str, unit: &str, count: u32) -> Result<Interval, ParseError> {
+    Ok(match unit.trim_end_matches('s') {
+        "day" => Interval::Days(count),
+        "week" => Interval::Weeks(count),
+        "month" => Interval::Months(count),
+        "quarter" => Interval::Months(
+            count
+                .checked_mul(3)
+                .ok_or_else(|| error(text, "interval too long"))?,
+        ),
+        "year" => Interval::Years(count),
+        _ => return Err(error(text, &format!("unknown interval unit {:?}", unit))),
+    })
+}
+
+/// Parses a date at the beginning of `words` into the first day and the day after
+/// the last day of the day, month or year it stands for.
+fn parse_range(
+    text: &str,
+    words: &mut Peekable<SplitWhitespace>,
+    today: NaiveDate,
+) -> Result<(NaiveDate, NaiveDate), ParseError> {
+    let word = words.next().ok_or_else(|| error(text, "missing date"))?;
+    let invalid = || error(text, &format!("invalid date {:?}", word));
+    let range = |start: NaiveDate, interval: Interval| {
+        interval
+            .nth(start, 1)
+            .map(|end| (start, end))
+            .ok_or_else(invalid)
+    };
+
+    match word {
+        "today" => return range(today, Interval::Days(1)),
+        "yesterday" => {
+            return range(today.pred_opt().ok_or_else(invalid)?, Interval::Days(1));
+        }
+        "tomorrow" => return range(today.succ_opt().ok_or_else(invalid)?, Interval::Days(1)),
+        "this" | "last" | "next" => {
+            let unit = words
+                .next()
+                .ok_or_else(|| error(text, "missing date unit"))?;
+            let interval = unit_interval(text, unit, 1)
+                .map_err(|_| error(text, &format!("unknown date unit {:?}", unit)))?;
+            let current = interval.align(today);
+            let start = match word {
+                "last" => interval.previous(current),
+                "next" => interval.nth(current, 1),
+                _ => Some(current),
+            };
+            return range(start.ok_or_else(invalid)?, interval);
+        }
+        _ => {}
+    }
+
+    if let Some(month) = month(word) {
+        let start = NaiveDate::from_ymd_opt(today.year(), month, 1).unwrap();
+        return range(start, Interval::Months(1));
+    }
+
+    let parts = word
+        .split(['-', '/', '.'])
+        .map(|part| part.parse::<u32>().map_err(|_| invalid()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let year_first = word.len() >= 4 && word[..4].bytes().all(|c| c.is_ascii_digit());
+    let year = |year: u32| i32::try_from(year).map_err(|_| invalid());
+    let date = |year: i32, month: u32, day: u32| {
+        NaiveDate::from_ymd_opt(year, month, day).ok_or_else(invalid)
+    };
+    match parts[..] {
+        [y] if year_first => range(date(year(y)?, 1, 1)?, Interval::Years(1)),
+        [y, m] if year_first => range(date(year(y)?, m, 1)?, Interval::Months(1)),
+        [m, d] => range(date(today.year(), m, d)?, Interval::Days(1)),
+        [y, m, d] if year_first => range(date(year(y)?, m, d)?, Interval::Days(1)),
+        _ => Err(invalid()),
+    }
+}
+
+/// Checks if the word starts a date: a number, a month name or a relative date.
+fn is_date(word: &str) -> bool {
+    word.starts_with(|c: char| c.is_ascii_digit())
+        || month(word).is_some()
+        || ["today", "yesterday", "tomorrow", "this", "last", "next"].contains(&word)
+}
+
+/// Month (1 to 12) of a month name, abbreviated to at least three letters.
+fn month(word: &str) -> Option<u32> {
+    const NAMES: [&str; 12] = [
+        "january",
+        "february",
+        "march",
+        "april",
+        "may",
+        "june",
+        "july",
+        "august",
+        "september",
+        "october",
+        "november",
+        "december",
+    ];
+    let index = NAMES
+        .iter()
+        .position(|name| word.len() >= 3 && name.starts_with(word))?;
+    Some(index as u32 + 1)
+}
+
+///
+/// Periodic transaction with a period expression which is not supported.
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PeriodicTransactionError {
+    /// Index of the periodic transaction in `Ledger::items`.
+    pub item: usize,
+    pub period: String,
+    pub message: String,
+}
+
+impl fmt::Display for PeriodicTransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unsupported period expression {:?}: {}",
+            self.period, self.message
+        )
+    }
+}
+
+impl std::error::Error for PeriodicTransactionError {}
+
+impl PeriodicTransaction {
+    /// Transaction with the postings of the periodic transaction on `date`,
+    /// marked as forecast.
+    pub fn transaction_on(&self, date: NaiveDate) -> Transaction {
+        Transaction {
+            status: None,
+            code: None,
+            description: Some(self.period.clone()),
+            comment: self.comment.clone(),
+            date,
+            effective_date: None,
+            posting_metadata: self.posting_metadata.clone(),
+            postings: self.postings.clone(),
+            forecast: true,
+            span: None,
+        }
+    }
+}
+
+/// Periodic transactions of the ledger with their indices in `Ledger::items` and their
+/// parsed periods, relative dates resolved against `today`. Every period has an interval.
+pub(crate) fn periodic_transactions(
+    ledger: &Ledger,
+    today: NaiveDate,
+) -> Result<Vec<(usize, &PeriodicTransaction, Period)>, PeriodicTransactionError> {
+    let mut periodic_transactions = Vec::new();
+    for (item, ledger_item) in ledger.items.iter().enumerate() {
+        if let LedgerItem::PeriodicTransaction(periodic) = ledger_item {
+            let error = |message: String| PeriodicTransactionError {
+                item,
+                period: periodic.period.clone(),
+                message,
+            };
+            let period =
+                Period::parse(&periodic.period, today).map_err(|err| error(err.to_string()))?;
+            if period.interval.is_none() {
+                return Err(error("missing interval".to_owned()));
+            }
+            periodic_transactions.push((item, periodic, period));
+        }
+    }
+    Ok(periodic_transactions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2018, month, day).unwrap()
+    }
+
+    fn parse(text: &str) -> Result<Period, ParseError> {
+        Period::parse(text, date(10, 17))
+    }
+
+    #[test]
+    fn parse_periods() {
+        assert_eq!(
+            parse("Monthly"),
+            Ok(Period {
+                interval: Some(Interval::Months(1)),
+                begin: None,
+                end: None
+            })
+        );
+        assert_eq!(
+            parse("every 2 weeks from 2018/10/01 to 2018-12-01"),
+            Ok(Period {
+                interval: Some(Interval::Weeks(2)),
+                begin: Some(date(10, 1)),
+                end: Some(date(12, 1))
+            })
+        );
+        assert_eq!(
+            parse("Every quarter").unwrap().interval,
+            Some(Interval::Months(3))
+        );
+        assert_eq!(
+            parse("weekly in 2018/11"),
+            Ok(Period {
+                interval: Some(Interval::Weeks(1)),
+                begin: Some(date(11, 1)),
+                end: Some(date(12, 1))
+            })
+        );
+        assert_eq!(
+            parse("since 2018.03 until dec"),
+            Ok(Period {
+                interval: None,
+                begin: Some(date(3, 1)),
+                end: Some(date(12, 1))
+            })
+        );
+        assert_eq!(
+            parse("2018").unwrap().end,
+            Some(date(1, 1).with_year(2019).unwrap())
+        );
+        assert_eq!(parse("from 11/5").unwrap().begin, Some(date(11, 5)));
+    }
+
+    #[test]
+    fn relative_dates() {
+        // 2018-10-17 is a Wednesday.
+        let range = |text: &str| {
+            let period = parse(text).unwrap();
+            (period.begin.unwrap(), period.end.unwrap())
+        };
+        assert_eq!(range("today"), (date(10, 17), date(10, 18)));
+        assert_eq!(range("yesterday"), (date(10, 16), date(10, 17)));
+        assert_eq!(range("this week"), (date(10, 15), date(10, 22)));
+        assert_eq!(range("next week"), (date(10, 22), date(10, 29)));
+        assert_eq!(range("last month"), (date(9, 1), date(10, 1)));
+        assert_eq!(range("last quarter"), (date(7, 1), date(10, 1)));
+        assert_eq!(
+            range("this year"),
+            (date(1, 1), date(1, 1).with_year(2019).unwrap())
+        );
+        assert_eq!(
+            parse("daily in next month").unwrap().interval,
+            Some(Interval::Days(1))
+        );
+    }
+
+    #[test]
+    fn invalid_periods() {
+        for text in [
+            "",
+            "every 0 days",
+            "every fortnight",
+            "fortnightly",
+            "monthly from",
+            "monthly weekly",
+            "from 2018 from 2019",
+            "in 2018-13",
+            "last decade",
+        ] {
+            assert!(parse(text).is_err(), "{}", text);
+        }
+        assert_eq!(
+            parse("every 3 fortnights"),
+            Err(ParseError::String(
+                "unknown interval unit \"fortnights\" in period expression \"every 3 fortnights\""
+                    .to_owned()
+            ))
+        );
+        assert_eq!(
+            parse("every 2000000000 quarters"),
+            Err(ParseError::String(
+                "interval too long in period expression \"every 2000000000 quarters\"".to_owned()
+            ))
+        );
+        assert_eq!(
+            parse("Fortnightly"),
+            Err(ParseError::String(
+                "unknown interval or date \"fortnightly\" in period expression \"Fortnightly\""
+                    .to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn calendar_periods() {
+        let monthly = parse("monthly").unwrap();
+        assert_eq!(
+            monthly.periods(date(10, 1), date(12, 1)),
+            vec![(date(10, 1), date(11, 1)), (date(11, 1), date(12, 1))]
+        );
+        // The period which started before the range is left out.
+        assert_eq!(
+            monthly.periods(date(10, 15), date(12, 1)),
+            vec![(date(11, 1), date(12, 1))]
+        );
+
+        let weekly = parse("weekly until 2018-10-15").unwrap();
+        assert_eq!(
+            weekly.periods(date(10, 1), date(12, 1)),
+            vec![(date(10, 1), date(10, 8)), (date(10, 8), date(10, 15))]
+        );
+
+        let quarterly = parse("quarterly").unwrap();
+        assert_eq!(
+            quarterly.periods(date(2, 1), date(8, 1)),
+            vec![(date(4, 1), date(7, 1)), (date(7, 1), date(10, 1))]
+        );
+
+        let range = parse("in 2018/11").unwrap();
+        assert_eq!(
+            range.periods(date(10, 1), date(12, 15)),
+            vec![(date(11, 1), date(12, 1))]
+        );
+
+        // 2018-10-17 is a Wednesday.
+        assert_eq!(Interval::Weeks(2).align(date(10, 17)), date(10, 15));
+        assert_eq!(
+            Interval::Weeks(1).align_with(date(10, 17), Weekday::Sun, 1),
+            date(10, 14)
+        );
+        assert_eq!(Interval::Months(3).align(date(2, 5)), date(1, 1));
+        assert_eq!(
+            Interval::Months(3).align_with(date(1, 5), Weekday::Mon, 2),
+            NaiveDate::from_ymd_opt(2017, 11, 1).unwrap()
+        );
+        assert_eq!(
+            Interval::Years(1).align_with(date(2, 5), Weekday::Mon, 4),
+            NaiveDate::from_ymd_opt(2017, 4, 1).unwrap()
+        );
+
+        // Intervals too long to add to a date end the periods instead of overflowing.
+        assert_eq!(Interval::Years(400_000_000).nth(date(1, 1), 1), None);
+        assert_eq!(Interval::Months(u32::MAX).nth(date(1, 1), 2), None);
+        let long = parse("every 400000000 years").unwrap();
+        assert_eq!(long.periods(date(10, 1), date(12, 1)), vec![]);
+    }
+}
@@ -0,0 +1,362 @@
+use crate::model::*;
+use crate::parser::LedgerParseResult;
+use crate::ParseError;
+use nom::{
+    branch::alt,
+    bytes::complete::{escaped, is_not, tag_no_case},
+    character::complete::{anychar, char, multispace0, multispace1, none_of, satisfy},
+    combinator::{all_consuming, cut, map_res, not, opt, peek, recognize, verify},
+    error::convert_error,
+    multi::many0,
+    sequence::{delimited, pair, preceded, terminated},
+    Finish, Parser,
+};
+use regex::{Regex, RegexBuilder};
+use std::borrow::Cow;
+use std::str::FromStr;
+
+///
+/// Report query, as given to ledger-cli on the command line, e.g.
+/// `Expenses:Food and payee Walmart` or `tag receipt and not %reconciled`.
+///
+/// Terms are regular expressions, matched ignoring case and optionally between slashes
+/// (with slashes in them escaped as `\/`):
+/// - `TERM`: account name
+/// - `payee TERM`, `desc TERM`, `@TERM`: transaction description
+/// - `note TERM`, `=TERM`: comment of the posting or of its transaction
+/// - `tag NAME[=VALUE]`, `%NAME[=VALUE]`: tag of the posting or of its transaction
+///
+/// Terms are combined with `not` (`!`), `and` (`&`) and `or` (`|`), from the highest
+/// precedence, and grouped with parentheses. Terms without an operator between them
+/// are combined with `or`, so `food dining` matches both accounts.
+///
+#[derive(Debug, Clone)]
+pub enum Query {
+    Account(Regex),
+    Payee(Regex),
+    Note(Regex),
+    /// Tag name and optional value.
+    Tag(Regex, Option<Regex>),
+    Not(Box<Query>),
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+}
+
+impl FromStr for Query {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match all_consuming(preceded(multispace0, parse_or))(input).finish() {
+            Ok((_, query)) => Ok(query),
+            Err(error) => Err(ParseError::String(convert_error(input, error))),
+        }
+    }
+}
+
+impl Query {
+    /// Checks if the posting at index `posting` of `transaction` matches the query.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `posting` is out of bounds.
+    pub fn matches(&self, transaction: &Transaction, posting: usize) -> bool {
+        let posting_ref = &transaction.postings[posting];
+        match self {
+            Query::Account(pattern) => pattern.is_match(posting_ref.account.as_str()),
+            Query::Payee(pattern) => transaction
+                .description
+                .as_ref()
+                .is_some_and(|description| pattern.is_match(description)),
+            Query::Note(pattern) => [&posting_ref.comment, &transaction.comment]
+                .into_iter()
+                .flatten()
+                .any(|comment| pattern.is_match(comment)),
+            Query::Tag(name, value) => posting_ref
+                .metadata
+                .tags
+                .iter()
+                .chain(&transaction.posting_metadata.tags)
+                .any(|tag| {
+                    name.is_match(&tag.name)
+                        && value.as_ref().is_none_or(|value| {
+                            tag.value.as_ref().is_some_and(|tag_value| match tag_value {
+                                TagValue::Date(date) => value.is_match(&date.to_string()),
+                                tag_value => value.is_match(&tag_value.to_string()),
+                            })
+                        })
+                }),
+            Query::Not(query) => !query.matches(transaction, posting),
+            Query::And(left, right) => {
+                left.matches(transaction, posting) && right.matches(transaction, posting)
+            }
+            Query::Or(left, right) => {
+                left.matches(transaction, posting) || right.matches(transaction, posting)
+            }
+        }
+    }
+
+    /// Checks if any posting of the transaction matches the query.
+    pub fn matches_transaction(&self, transaction: &Transaction) -> bool {
+        (0..transaction.postings.len()).any(|posting| self.matches(transaction, posting))
+    }
+}
+
+impl Ledger {
+    /// Transactions with at least one posting matching the query, in file order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let ledger: ledger_parser::Ledger = "2018-10-01 Walmart\n  Expenses:Food  $10\n  Assets:Bank\n"
+    ///     .parse()
+    ///     .unwrap();
+    /// let query = "Expenses:Food and payee walmart".parse().unwrap();
+    /// assert_eq!(ledger.filter_transactions(&query).count(), 1);
+    /// ```
+    pub fn filter_transactions<'a>(
+        &'a self,
+        query: &'a Query,
+    ) -> impl Iterator<Item = &'a Transaction> + 'a {
+        self.transactions()
+            .filter(|transaction| query.matches_transaction(transaction))
+    }
+
+    /// Postings matching the query with their transactions, in file order.
+    pub fn filter_postings<'a>(
+        &'a self,
+        query: &'a Query,
+    ) -> impl Iterator<Item = (&'a Transaction, &'a Posting)> + 'a {
+        self.transactions().flat_map(move |transaction| {
+            transaction
+                .postings
+                .iter()
+                .enumerate()
+                .filter(move |(index, _)| query.matches(transaction, *index))
+                .map(move |(_, posting)| (transaction, posting))
+        })
+    }
+
+    pub(crate) fn transactions(&self) -> impl Iterator<Item = &Transaction> {
+        self.items.iter().filter_map(|item| match item {
+            LedgerItem::Transaction(transaction) => Some(transaction),
+            _ => None,
+        })
+    }
+}
+
+fn keyword<'a>(name: &'static str) -> impl FnMut(&'a str) -> LedgerParseResult<'a, &'a str> {
+    terminated(
+        tag_no_case(name),
+        peek(alt((multispace1, is_not_term_char))),
+    )
+}
+
+fn is_not_term_char(input: &str) -> LedgerParseResult<'_, &str> {
+    alt((
+        recognize(satisfy(|c| "()&|".contains(c))),
+        recognize(not(satisfy(|_| true))),
+    ))(input)
+}
+
+/// Regular expression, between slashes or up to a space, parenthesis, `&` or `|`.
+fn term(input: &str) -> LedgerParseResult<'_, Cow<'_, str>> {
+    alt((
+        delimited(
+            char('/'),
+            opt(escaped(none_of("\\/"), '\\', anychar)),
+            char('/'),
+        )
+        .map(|pattern: Option<&str>| match pattern {
+            Some(pattern) if pattern.contains("\\/") => Cow::Owned(pattern.replace("\\/", "/")),
+            pattern => Cow::Borrowed(pattern.unwrap_or_default()),
+        }),
+        verify(is_not(" \t\r\n()&|"), |term: &str| {
+            !["and", "or", "not"].contains(&term.to_lowercase().as_str())
+        })
+        .map(Cow::Borrowed),
+    ))(input)
+}
+
+fn regex(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern).case_insensitive(true).build()
+}
+
+fn parse_tag(input: &str) -> LedgerParseResult<'_, Query> {
+    map_res(term, |term| {
+        let (name, value) = match term.split_once('=') {
+            Some((name, value)) => (name, Some(regex(value)?)),
+            None => (term.as_ref(), None),
+        };
+        Ok::<_, regex::Error>(Query::Tag(regex(name)?, value))
+    })(input)
+}
+
+fn parse_term(input: &str) -> LedgerParseResult<'_, Query> {
+    terminated(
+        alt((
+            delimited(char('('), preceded(multispace0, parse_or), cut(char(')'))),
+            preceded(
+                pair(alt((keyword("not"), recognize(char('!')))), multispace0),
+                cut(parse_term),
+            )
+            .map(|query| Query::Not(Box::new(query))),
+            preceded(
+                alt((
+                    terminated(keyword("payee"), multispace0),
+                    terminated(keyword("desc"), multispace0),
+                    recognize(char('@')),
+                )),
+                cut(map_res(term, |term| regex(&term))),
+            )
+            .map(Query::Payee),
+            preceded(
+                alt((
+                    terminated(keyword("note"), multispace0),
+                    recognize(char('=')),
+                )),
+                cut(map_res(term, |term| regex(&term))),
+            )
+            .map(Query::Note),
+            preceded(
+                alt((
+                    terminated(keyword("tag"), multispace0),
+                    recognize(char('%')),
+                )),
+                cut(parse_tag),
+            ),
+            map_res(term, |term| regex(&term)).map(Query::Account),
+        )),
+        multispace0,
+    )(input)
+}
+
+fn parse_and(input: &str) -> LedgerParseResult<'_, Query> {
+    let (input, first) = parse_term(input)?;
+    let (input, rest) = many0(preceded(
+        terminated(alt((keyword("and"), recognize(char('&')))), multispace0),
+        cut(parse_term),
+    ))(input)?;
+    Ok((
+        input,
+        rest.into_iter().fold(first, |left, right| {
+            Query::And(Box::new(left), Box::new(right))
+        }),
+    ))
+}
+
+fn parse_or(input: &str) -> LedgerParseResult<'_, Query> {
+    let (input, first) = parse_and(input)?;
+    let (input, rest) = many0(alt((
+        preceded(
+            terminated(alt((keyword("or"), recognize(char('|')))), multispace0),
+            cut(parse_and),
+        ),
+        parse_and,
+    )))(input)?;
+    Ok((
+        input,
+        rest.into_iter().fold(first, |left, right| {
+            Query::Or(Box::new(left), Box::new(right))
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"2024-01-05 * Walmart  ; :receipt:
+  Expenses:Food  $12.50  ; groceries
+  Assets:Bank
+
+2024-01-06 Amtrak
+  ; :receipt:reconciled:
+  Expenses:Travel  $40  ; Trip: Boston
+  Assets:Bank
+
+2023-12-31 Walmart
+  Expenses:Home  $20
+  Liabilities:Card
+"#;
+
+    fn postings(query: &str) -> Vec<String> {
+        let ledger: Ledger = SOURCE.parse().unwrap();
+        let query: Query = query.parse().unwrap();
+        ledger
+            .filter_postings(&query)
+            .map(|(transaction, posting)| format!("{} {}", transaction.date, posting.account))
+            .collect()
+    }
+
+    #[test]
+    fn terms() {
+        assert_eq!(postings("food"), vec!["2024-01-05 Expenses:Food"]);
+        assert_eq!(
+            postings("/^expenses:(food|home)$/"),
+            vec!["2024-01-05 Expenses:Food", "2023-12-31 Expenses:Home"]
+        );
+        assert_eq!(
+            postings("Expenses and payee Walmart"),
+            vec!["2024-01-05 Expenses:Food", "2023-12-31 Expenses:Home"]
+        );
+        assert_eq!(
+            postings("expenses & @amtrak"),
+            vec!["2024-01-06 Expenses:Travel"]
+        );
+        assert_eq!(
+            postings("desc ^walmart$ and card"),
+            vec!["2023-12-31 Liabilities:Card"]
+        );
+        assert_eq!(postings("note grocer"), vec!["2024-01-05 Expenses:Food"]);
+        assert_eq!(
+            postings("note /groceries\\/?$/ and /^expenses:f\\w+$/"),
+            vec!["2024-01-05 Expenses:Food"]
+        );
+        assert!(matches!("/a\\/b/".parse(), Ok(Query::Account(regex)) if regex.as_str() == "a/b"));
+        assert_eq!(postings("=boston"), Vec::<String>::new());
+        assert_eq!(
+            postings("tag trip=boston"),
+            vec!["2024-01-06 Expenses:Travel"]
+        );
+        assert_eq!(
+            postings("%receipt and not %reconciled"),
+            vec!["2024-01-05 Expenses:Food", "2024-01-05 Assets:Bank"]
+        );
+    }
+
+    #[test]
+    fn operators() {
+        // Adjacent terms are combined with `or`, `and` binds tighter.
+        assert_eq!(
+            postings("food travel and @amtrak"),
+            vec!["2024-01-05 Expenses:Food", "2024-01-06 Expenses:Travel"]
+        );
+        assert_eq!(
+            postings("(food or travel) and not(@walmart)"),
+            vec!["2024-01-06 Expenses:Travel"]
+        );
+        assert_eq!(
+            postings("!assets & !expenses | home"),
+            vec!["2023-12-31 Expenses:Home", "2023-12-31 Liabilities:Card"]
+        );
+
+        let ledger: Ledger = SOURCE.parse().unwrap();
+        let query: Query = "card or travel".parse().unwrap();
+        let payees = ledger
+            .filter_transactions(&query)
+            .map(|transaction| transaction.description.as_deref().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(payees, vec!["Amtrak", "Walmart"]);
+    }
+
+    #[test]
+    fn errors() {
+        assert!("(food".parse::<Query>().is_err());
+        assert!("food and".parse::<Query>().is_err());
+        assert!("payee".parse::<Query>().is_err());
+        assert!("/(food/".parse::<Query>().is_err());
+        assert!("food)".parse::<Query>().is_err());
+        // `notes` is an account, not `not es`.
+        assert!(matches!("notes".parse(), Ok(Query::Account(_))));
+    }
+}
@@ -0,0 +1,211 @@
+use crate::model::*;
+use crate::{AccountName, MixedAmount, Query, ReportError};
+use chrono::NaiveDate;
+
+#[non_exhaustive]
+#[derive(Debug, Clone, Default)]
+pub struct RegisterSettings {
+    /// Only postings matching this query (e.g. an account regex) are listed.
+    pub query: Option<Query>,
+    /// Use effective dates (`DATE=EDATE`, `[=EDATE]`) where given.
+    pub effective_dates: bool,
+}
+
+impl RegisterSettings {
+    pub fn with_query(mut self, query: Query) -> Self {
+        self.query = Some(query);
+        self
+    }
+
+    pub fn with_effective_dates(mut self, effective_dates: bool) -> Self {
+        self.effective_dates = effective_dates;
+        self
+    }
+}
+
+///
+/// Single posting of the register report.
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RegisterRow {
+    pub date: NaiveDate,
+    /// Transaction description.
+    pub payee: Option<String>,
+    pub account: AccountName,
+    pub amount: MixedAmount,
+    /// Sum of the amounts of this and all the previous rows.
+    pub total: MixedAmount,
+    /// Index of the transaction in `Ledger::items`.
+    pub item: usize,
+    /// Index of the posting in the transaction.
+    pub posting: usize,
+}
+
+/// Lists the postings matching the query in date order, with running totals.
+///
+/// Posting dates are used where given, postings with the same date are kept in file
+/// order. Elided amounts are counted as inferred by [`Transaction::infer_amounts`],
+/// an error is returned if one cannot be inferred.
+///
+/// # Examples
+///
+/// ```
+/// use ledger_parser::{register, RegisterSettings};
+///
+/// let ledger: ledger_parser::Ledger = "2018-10-01 Payee\n  Assets:Bank  $10\n  Income\n"
+///     .parse()
+///     .unwrap();
+/// let rows = register(&ledger, &RegisterSettings::default().with_query("bank".parse().unwrap()))
+///     .unwrap();
+/// assert_eq!(rows[0].total.to_string(), "$10");
+/// ```
+pub fn register(
+    ledger: &Ledger,
+    settings: &RegisterSettings,
+) -> Result<Vec<RegisterRow>, ReportError> {
+    let mut rows = Vec::new();
+    for (item, ledger_item) in ledger.items.iter().enumerate() {
+        let LedgerItem::Transaction(transaction) = ledger_item else {
+            continue;
+        };
+        for (index, posting) in transaction.postings.iter().enumerate() {
+            let matches = settings
+                .query
+                .as_ref()
+                .is_none_or(|query| query.matches(transaction, index));
+            if !matches {
+                continue;
+            }
+
+            let amount = transaction.posting_amount(item, index)?;
+            rows.push(RegisterRow {
+                date: posting_date(transaction, posting, settings.effective_dates),
+                payee: transaction.description.clone(),
+                account: posting.account.clone(),
+                amount,
+                total: MixedAmount::new(),
+                item,
+                posting: index,
+            });
+        }
+    }
+
+    // The sort is stable, so rows with the same date stay in file order.
+    rows.sort_by_key(|row| row.date);
+    let mut total = MixedAmount::new();
+    for row in &mut rows {
+        total = total.checked_add(&row.amount)?;
+        row.total = total.clone();
+    }
+    Ok(rows)
+}
+
+/// Date of the posting, falling back to the dates of the transaction.
+pub(crate) fn posting_date(
+    transaction: &Transaction,
+    posting: &Posting,
+    effective: bool,
+) -> NaiveDate {
+    let date = posting.metadata.date.unwrap_or(transaction.date);
+    if effective {
+        posting
+            .metadata
+            .effective_date
+            .or(transaction.effective_date)
+            .unwrap_or(date)
+    } else {
+        date
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BalanceError;
+
+    const SOURCE: &str = r#"2018-10-03=2018-10-05 Shop
+  Expenses:Food  $3
+  Assets:Bank
+
+2018-10-01 Employer
+  Assets:Bank  $10
+  Assets:Cash  $5
+  ; [2018-10-04]
+  Income:Salary
+"#;
+
+    #[test]
+    fn running_totals_in_date_order() {
+        let ledger: Ledger = SOURCE.parse().unwrap();
+        let rows = register(
+            &ledger,
+            &RegisterSettings::default().with_query("ASSETS".parse().unwrap()),
+        )
+        .unwrap();
+        let summary = rows
+            .iter()
+            .map(|row| {
+                format!(
+                    "{} {} {} {} {}",
+                    row.date,
+                    row.payee.as_deref().unwrap_or(""),
+                    row.account,
+                    row.amount,
+                    row.total
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                "2018-10-01 Employer Assets:Bank $10 $10",
+                "2018-10-03 Shop Assets:Bank $-3 $7",
+                "2018-10-04 Employer Assets:Cash $5 $12",
+            ]
+        );
+        assert_eq!((rows[1].item, rows[1].posting), (0, 1));
+
+        let query = "assets and payee shop".parse().unwrap();
+        let rows = register(&ledger, &RegisterSettings::default().with_query(query)).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].account, "Assets:Bank");
+    }
+
+    #[test]
+    fn effective_dates() {
+        let ledger: Ledger = SOURCE.parse().unwrap();
+        let settings = RegisterSettings::default()
+            .with_query("^assets:".parse().unwrap())
+            .with_effective_dates(true);
+        let rows = register(&ledger, &settings).unwrap();
+        assert_eq!(
+            rows.iter()
+                .map(|row| row.payee.as_deref())
+                .collect::<Vec<_>>(),
+            vec![Some("Employer"), Some("Employer"), Some("Shop")]
+        );
+        assert_eq!(rows[2].date, NaiveDate::from_ymd_opt(2018, 10, 5).unwrap());
+        assert_eq!(
+            register(&ledger, &RegisterSettings::default())
+                .unwrap()
+                .len(),
+            5
+        );
+    }
+
+    #[test]
+    fn uninferable_amounts() {
+        let ledger: Ledger = "2018-10-01 Shop\n  Expenses  $5\n  Assets\n  Income\n"
+            .parse()
+            .unwrap();
+        let error = register(&ledger, &RegisterSettings::default()).unwrap_err();
+        assert!(matches!(
+            error,
+            ReportError::Inference {
+                item: 0,
+                posting: 1,
+                error: BalanceError::MultipleNullAmounts { .. }
+            }
+        ));
+    }
+}
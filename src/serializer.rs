@@ -45,7 +45,7 @@ pub trait Serializer {
     fn to_string_pretty(&self, settings: &SerializerSettings) -> String {
         let mut res = Vec::new();
         self.write(&mut res, settings).unwrap();
-        return std::str::from_utf8(&res).unwrap().to_owned();
+        std::str::from_utf8(&res).unwrap().to_owned()
     }
 }
 
@@ -68,7 +68,9 @@ impl Serializer for LedgerItem {
     {
         match self {
             LedgerItem::EmptyLine => write!(writer, "{}", settings.eol)?,
-            LedgerItem::LineComment(comment) => write!(writer, "; {}{}", comment, settings.eol)?,
+            LedgerItem::LineComment(comment) => {
+                write!(writer, "; {}{}", comment.comment, settings.eol)?
+            }
             LedgerItem::Transaction(transaction) => {
                 transaction.write(writer, settings)?;
                 write!(writer, "{}", settings.eol)?;
@@ -77,7 +79,17 @@ impl Serializer for LedgerItem {
                 commodity_price.write(writer, settings)?;
                 write!(writer, "{}", settings.eol)?;
             }
-            LedgerItem::Include(file) => write!(writer, "include {}{}", file, settings.eol)?,
+            LedgerItem::Include(include) => {
+                write!(writer, "include {}{}", include.file, settings.eol)?
+            }
+            LedgerItem::AutomatedTransaction(automated) => {
+                automated.write(writer, settings)?;
+                write!(writer, "{}", settings.eol)?;
+            }
+            LedgerItem::PeriodicTransaction(periodic) => {
+                periodic.write(writer, settings)?;
+                write!(writer, "{}", settings.eol)?;
+            }
         }
         Ok(())
     }
@@ -118,26 +130,48 @@ impl Serializer for Transaction {
             }
         }
 
-        if let Some(ref comment) = self.comment {
-            for comment in comment.split('\n') {
-                write!(writer, "{}{}; {}", settings.eol, settings.indent, comment)?;
-            }
-        }
+        write_body(
+            writer,
+            settings,
+            &self.comment,
+            &self.posting_metadata,
+            &self.postings,
+        )
+    }
+}
 
-        for tag in &self.posting_metadata.tags {
-            write!(writer, "{}{}; {}", settings.eol, settings.indent, tag.name)?;
-            if let Some(ref value) = tag.value {
-                write!(writer, ": {}", value)?;
-            };
+/// Writes the comment, the tags and the postings of a transaction, or of an automated
+/// or periodic transaction, each on its own indented line.
+fn write_body<W, P>(
+    writer: &mut W,
+    settings: &SerializerSettings,
+    comment: &Option<String>,
+    metadata: &PostingMetadata,
+    postings: &[P],
+) -> Result<(), io::Error>
+where
+    W: io::Write,
+    P: Serializer,
+{
+    if let Some(ref comment) = comment {
+        for comment in comment.split('\n') {
+            write!(writer, "{}{}; {}", settings.eol, settings.indent, comment)?;
         }
+    }
 
-        for posting in &self.postings {
-            write!(writer, "{}{}", settings.eol, settings.indent)?;
-            posting.write(writer, settings)?;
-        }
+    for tag in &metadata.tags {
+        write!(writer, "{}{}; {}", settings.eol, settings.indent, tag.name)?;
+        if let Some(ref value) = tag.value {
+            write!(writer, ": {}", value)?;
+        };
+    }
 
-        Ok(())
+    for posting in postings {
+        write!(writer, "{}{}", settings.eol, settings.indent)?;
+        posting.write(writer, settings)?;
     }
+
+    Ok(())
 }
 
 impl Serializer for TransactionStatus {
@@ -202,6 +236,78 @@ impl Serializer for Posting {
     }
 }
 
+impl Serializer for AutomatedTransaction {
+    fn write<W>(&self, writer: &mut W, settings: &SerializerSettings) -> Result<(), io::Error>
+    where
+        W: io::Write,
+    {
+        write!(writer, "= {}", self.predicate)?;
+
+        write_body(
+            writer,
+            settings,
+            &self.comment,
+            &self.posting_metadata,
+            &self.postings,
+        )
+    }
+}
+
+impl Serializer for PeriodicTransaction {
+    fn write<W>(&self, writer: &mut W, settings: &SerializerSettings) -> Result<(), io::Error>
+    where
+        W: io::Write,
+    {
+        write!(writer, "~ {}", self.period)?;
+
+        write_body(
+            writer,
+            settings,
+            &self.comment,
+            &self.posting_metadata,
+            &self.postings,
+        )
+    }
+}
+
+impl Serializer for AutomatedPosting {
+    fn write<W>(&self, writer: &mut W, settings: &SerializerSettings) -> Result<(), io::Error>
+    where
+        W: io::Write,
+    {
+        match self.reality {
+            Reality::Real => write!(writer, "{}", self.account)?,
+            Reality::BalancedVirtual => write!(writer, "[{}]", self.account)?,
+            Reality::UnbalancedVirtual => write!(writer, "({})", self.account)?,
+        }
+
+        write!(writer, "{}", settings.indent)?;
+        match self.amount {
+            AutomatedAmount::Multiplier(multiplier) => write!(writer, "{}", multiplier)?,
+            AutomatedAmount::Amount(ref amount) => amount.write(writer, settings)?,
+        }
+
+        for tag in &self.metadata.tags {
+            write!(writer, "{}; {}", settings.indent, tag.name)?;
+            if let Some(ref value) = tag.value {
+                write!(writer, ": {}", value)?;
+            };
+        }
+
+        if let Some(ref comment) = self.comment {
+            if !comment.contains('\n') && settings.posting_comments_sameline {
+                write!(writer, "{}; {}", settings.indent, comment)?;
+            } else {
+                for comment in comment.split('\n') {
+                    write!(writer, "{}{}; {}", settings.eol, settings.indent, comment)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
 impl Serializer for PostingAmount {
     fn write<W>(&self, writer: &mut W, settings: &SerializerSettings) -> Result<(), io::Error>
     where
@@ -0,0 +1,168 @@
+use crate::model::*;
+use crate::parser;
+use crate::ParseError;
+use nom::Finish;
+use std::collections::VecDeque;
+use std::io::BufRead;
+
+///
+/// Streaming parser. Reads ledger items one by one from a `BufRead`,
+/// keeping in memory only the text of the item being parsed.
+///
+/// Spans of the items (and line numbers in errors) are positions in the whole stream.
+/// A parsing error does not stop the iteration, the following items are still returned.
+/// A read error ends it.
+///
+pub struct LedgerReader<R> {
+    reader: R,
+    chunk: String,
+    next_line: String,
+    offset: usize,
+    first_line: usize,
+    items: VecDeque<Result<LedgerItem, ParseError>>,
+    finished: bool,
+}
+
+impl<R: BufRead> LedgerReader<R> {
+    pub fn new(reader: R) -> Self {
+        LedgerReader {
+            reader,
+            chunk: String::new(),
+            next_line: String::new(),
+            offset: 0,
+            first_line: 1,
+            items: VecDeque::new(),
+            finished: false,
+        }
+    }
+
+    /// Reads the text of the next top-level item (with the lines that may belong to it)
+    /// into `chunk`. Returns `false` at the end of the input.
+    fn read_chunk(&mut self) -> Result<bool, ParseError> {
+        self.chunk.clear();
+        std::mem::swap(&mut self.chunk, &mut self.next_line);
+
+        loop {
+            let read = self
+                .reader
+                .read_line(&mut self.next_line)
+                .map_err(|err| ParseError::String(format!("failed to read input: {}", err)))?;
+            if read == 0 {
+                return Ok(!self.chunk.is_empty());
+            }
+            if !self.chunk.is_empty() && parser::starts_item(&self.next_line) {
+                return Ok(true);
+            }
+            self.chunk.push_str(&self.next_line);
+            self.next_line.clear();
+        }
+    }
+
+    fn parse_chunk(&mut self) {
+        let chunk = self.chunk.as_str();
+        match parser::parse_ledger_at(chunk, self.offset, self.first_line).finish() {
+            Ok((_, ledger)) => self
+                .items
+                .extend(ledger.items.into_iter().map(|item| Ok(item.into_owned()))),
+            Err(error) => self
+                .items
+                .push_back(Err(ParseError::String(parser::convert_error_at(
+                    chunk,
+                    error,
+                    self.first_line,
+                )))),
+        }
+        self.offset += chunk.len();
+        self.first_line += chunk.matches('\n').count();
+    }
+}
+
+impl<R: BufRead> Iterator for LedgerReader<R> {
+    type Item = Result<LedgerItem, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.items.is_empty() && !self.finished {
+            match self.read_chunk() {
+                Ok(true) => self.parse_chunk(),
+                Ok(false) => self.finished = true,
+                Err(error) => {
+                    self.finished = true;
+                    return Some(Err(error));
+                }
+            }
+        }
+        self.items.pop_front()
+    }
+}
+
+/// Parses ledger-cli source from a reader item by item.
+///
+/// # Examples
+///
+/// ```
+/// let input = "; Example 1\n2018-10-01 Description\n  TEST:Account 123  $1.20\n  TEST:Account 345\n";
+/// for item in ledger_parser::parse_reader(input.as_bytes()) {
+///     println!("{}", item.unwrap());
+/// }
+/// ```
+pub fn parse_reader<R: BufRead>(reader: R) -> LedgerReader<R> {
+    LedgerReader::new(reader)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"; Example 1
+
+include other_file.ledger
+P 2017-11-12 12:00:00 mBH 5.00 PLN  ; comment
+
+2018-10-01=2018-10-14 ! (123) Marek Ogarek
+ TEST:ABC 123  $1.20
+; attached to the posting above
+ TEST:ABC 123  $1.20
+
+2018-10-02 Payee
+  ; :tag:
+  TEST:ABC 123  $1.20
+  TEST:DEF 123"#;
+
+    #[test]
+    fn same_items_as_full_parse() {
+        let items = parse_reader(SOURCE.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let ledger: Ledger = SOURCE.parse().unwrap();
+        assert_eq!(items, ledger.items);
+
+        let spans = |items: &[LedgerItem]| {
+            items
+                .iter()
+                .filter_map(|item| match item {
+                    LedgerItem::Transaction(t) => t.postings.last().unwrap().span.clone(),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(spans(&items), spans(&ledger.items));
+        assert_eq!(spans(&items)[1].lines, 14..15);
+    }
+
+    #[test]
+    fn errors_report_stream_lines_and_iteration_continues() {
+        let source = "; Comment\n\n2018-10-01 Payee\n  TEST:ABC  $1.20 {\n  TEST:DEF\nP 2017-11-12 12:00:00 mBH 5.00 PLN\n";
+        let items = parse_reader(source.as_bytes()).collect::<Vec<_>>();
+        assert_eq!(items.len(), 4);
+        assert!(matches!(items[0], Ok(LedgerItem::LineComment(_))));
+        assert!(matches!(items[1], Ok(LedgerItem::EmptyLine)));
+        let Err(ParseError::String(ref message)) = items[2] else {
+            panic!("expected error");
+        };
+        assert!(message.contains("at line 3"), "{}", message);
+        let Ok(LedgerItem::CommodityPrice(ref price)) = items[3] else {
+            panic!("expected commodity price");
+        };
+        assert_eq!(price.span.as_ref().unwrap().lines, 6..7);
+    }
+}
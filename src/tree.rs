@@ -0,0 +1,363 @@
+use crate::model::*;
+use crate::register::posting_date;
+use crate::{AccountName, AmountError, MixedAmount, ReportError};
+use chrono::NaiveDate;
+use std::fmt;
+
+#[non_exhaustive]
+#[derive(Debug, Clone, Default)]
+pub struct AccountTreeSettings {
+    /// Only postings on or after this date are counted.
+    pub begin: Option<NaiveDate>,
+    /// Only postings before this date are counted.
+    pub end: Option<NaiveDate>,
+    /// Accounts deeper than this are counted in their ancestor at this depth.
+    pub depth: Option<usize>,
+}
+
+impl AccountTreeSettings {
+    pub fn with_begin(mut self, begin: NaiveDate) -> Self {
+        self.begin = Some(begin);
+        self
+    }
+
+    pub fn with_end(mut self, end: NaiveDate) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    pub fn with_depth(mut self, depth: usize) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+}
+
+///
+/// Accounts of a ledger arranged by their names, with balances of the accounts
+/// (`own`) and of the accounts together with all their sub-accounts (`total`).
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AccountTree {
+    root: AccountNode,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AccountNode {
+    name: Option<AccountName>,
+    own: MixedAmount,
+    total: MixedAmount,
+    children: Vec<AccountNode>,
+}
+
+///
+/// Line of the `balance` report. Accounts without postings of their own and with
+/// a single sub-account are shown together with it, e.g. `Assets:Bank`.
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BalanceRow {
+    /// Displayed name, relative to the parent row.
+    pub name: String,
+    /// Full name of the (deepest) account of the row.
+    pub account: AccountName,
+    /// Nesting level, `0` for top-level rows.
+    pub indent: usize,
+    pub total: MixedAmount,
+}
+
+impl AccountNode {
+    fn new(name: Option<AccountName>) -> Self {
+        AccountNode {
+            name,
+            own: MixedAmount::new(),
+            total: MixedAmount::new(),
+            children: Vec::new(),
+        }
+    }
+
+    /// Full account name, `None` for the root.
+    pub fn name(&self) -> Option<&AccountName> {
+        self.name.as_ref()
+    }
+
+    /// Balance of the postings to this account.
+    pub fn own(&self) -> &MixedAmount {
+        &self.own
+    }
+
+    /// Balance of this account and all its sub-accounts.
+    pub fn total(&self) -> &MixedAmount {
+        &self.total
+    }
+
+    /// Sub-accounts sorted by name.
+    pub fn children(&self) -> &[AccountNode] {
+        &self.children
+    }
+
+    fn leaf(&self) -> &str {
+        self.name.as_ref().map_or("", AccountName::leaf)
+    }
+
+    fn child(&self, segment: &str) -> Result<usize, usize> {
+        self.children
+            .binary_search_by(|child| child.leaf().cmp(segment))
+    }
+
+    /// Depth-first, parents before children.
+    fn visit<'a>(&'a self, nodes: &mut Vec<&'a AccountNode>) {
+        nodes.push(self);
+        for child in &self.children {
+            child.visit(nodes);
+        }
+    }
+
+    fn rows(&self, indent: usize, prefix: Option<String>, rows: &mut Vec<BalanceRow>) {
+        let name = match prefix {
+            Some(prefix) => format!("{}:{}", prefix, self.leaf()),
+            None => self.leaf().to_owned(),
+        };
+        if self.children.len() == 1 && self.own.is_zero() {
+            return self.children[0].rows(indent, Some(name), rows);
+        }
+        rows.push(BalanceRow {
+            name,
+            account: self.name.clone().unwrap(),
+            indent,
+            total: self.total.clone(),
+        });
+        for child in &self.children {
+            child.rows(indent + 1, None, rows);
+        }
+    }
+}
+
+impl AccountTree {
+    /// Sums the postings of all the transactions in the ledger. Elided amounts are
+    /// counted as inferred by [`Transaction::infer_amounts`], posting dates are used
+    /// where given. Returns an error if an elided amount cannot be inferred.
+    pub fn new(ledger: &Ledger, settings: &AccountTreeSettings) -> Result<Self, ReportError> {
+        let mut tree = AccountTree {
+            root: AccountNode::new(None),
+        };
+        for (item, ledger_item) in ledger.items.iter().enumerate() {
+            let LedgerItem::Transaction(transaction) = ledger_item else {
+                continue;
+            };
+            for (index, posting) in transaction.postings.iter().enumerate() {
+                let date = posting_date(transaction, posting, false);
+                if settings.begin.is_some_and(|begin| date < begin)
+                    || settings.end.is_some_and(|end| date >= end)
+                {
+                    continue;
+                }
+                let amount = transaction.posting_amount(item, index)?;
+                tree.add(&posting.account, &amount, settings.depth)?;
+            }
+        }
+        Ok(tree)
+    }
+
+    fn add(
+        &mut self,
+        account: &AccountName,
+        amount: &MixedAmount,
+        depth: Option<usize>,
+    ) -> Result<(), AmountError> {
+        let mut node = &mut self.root;
+        node.total = node.total.checked_add(amount)?;
+        let mut name: Option<AccountName> = None;
+        for segment in account.segments().take(depth.unwrap_or(usize::MAX)) {
+            let child_name = match name {
+                Some(ref name) => name.join(segment),
+                None => AccountName::new(segment),
+            }
+            .expect("segments of a valid account name");
+            let index = match node.child(segment) {
+                Ok(index) => index,
+                Err(index) => {
+                    node.children
+                        .insert(index, AccountNode::new(Some(child_name.clone())));
+                    index
+                }
+            };
+            node = &mut node.children[index];
+            node.total = node.total.checked_add(amount)?;
+            name = Some(child_name);
+        }
+        node.own = node.own.checked_add(amount)?;
+        Ok(())
+    }
+
+    /// Node with all top-level accounts as children. Its total is the sum of all postings.
+    pub fn root(&self) -> &AccountNode {
+        &self.root
+    }
+
+    pub fn get(&self, account: &AccountName) -> Option<&AccountNode> {
+        let mut node = &self.root;
+        for segment in account.segments() {
+            node = &node.children[node.child(segment).ok()?];
+        }
+        Some(node)
+    }
+
+    /// All accounts, depth-first, parents before their sub-accounts.
+    pub fn accounts(&self) -> impl Iterator<Item = &AccountNode> {
+        let mut nodes = Vec::new();
+        self.root.visit(&mut nodes);
+        nodes.into_iter().skip(1)
+    }
+
+    /// Rows of the `balance` report, with single-child chains collapsed.
+    pub fn rows(&self) -> Vec<BalanceRow> {
+        let mut rows = Vec::new();
+        for child in &self.root.children {
+            child.rows(0, None, &mut rows);
+        }
+        rows
+    }
+}
+
+/// Writes the tree like `ledger balance`: totals right-aligned (one line per commodity)
+/// followed by the indented account names, and the grand total at the end.
+impl fmt::Display for AccountTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn write_amount(f: &mut fmt::Formatter, amount: &MixedAmount, name: &str) -> fmt::Result {
+            let mut amounts = amount.amounts().map(Amount::to_string).collect::<Vec<_>>();
+            if amounts.is_empty() {
+                amounts.push("0".to_owned());
+            }
+            let (last, rest) = amounts.split_last().unwrap();
+            for amount in rest {
+                writeln!(f, "{:>20}", amount)?;
+            }
+            writeln!(f, "{}", format!("{:>20}  {}", last, name).trim_end())
+        }
+
+        for row in self.rows() {
+            write_amount(
+                f,
+                &row.total,
+                &format!("{}{}", "  ".repeat(row.indent), row.name),
+            )?;
+        }
+        writeln!(f, "{}", "-".repeat(20))?;
+        write_amount(f, &self.root.total, "")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BalanceError;
+
+    const SOURCE: &str = r#"2018-10-01 Payee
+  Assets:Bank:Checking  $10
+  Assets:Cash  5 PLN
+  Income:Salary
+
+2018-10-02 Payee
+  Expenses:Food:Groceries  $3
+  Expenses  $1
+  Assets:Bank:Checking
+"#;
+
+    fn account(name: &str) -> AccountName {
+        name.parse().unwrap()
+    }
+
+    #[test]
+    fn balances_roll_up() {
+        let ledger: Ledger = SOURCE.parse().unwrap();
+        let tree = AccountTree::new(&ledger, &AccountTreeSettings::default()).unwrap();
+
+        let assets = tree.get(&account("Assets")).unwrap();
+        assert!(assets.own().is_zero());
+        assert_eq!(assets.total().to_string(), "$6, 5 PLN");
+        assert_eq!(
+            tree.get(&account("Expenses")).unwrap().own().to_string(),
+            "$1"
+        );
+        assert_eq!(
+            tree.get(&account("Expenses")).unwrap().total().to_string(),
+            "$4"
+        );
+        assert!(tree.root().total().is_zero());
+        assert!(tree.get(&account("Assets:Nothing")).is_none());
+        assert_eq!(
+            tree.accounts()
+                .map(|node| node.name().unwrap().as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "Assets",
+                "Assets:Bank",
+                "Assets:Bank:Checking",
+                "Assets:Cash",
+                "Expenses",
+                "Expenses:Food",
+                "Expenses:Food:Groceries",
+                "Income",
+                "Income:Salary"
+            ]
+        );
+
+        assert_eq!(
+            tree.to_string(),
+            r#"                  $6
+               5 PLN  Assets
+                  $6    Bank:Checking
+               5 PLN    Cash
+                  $4  Expenses
+                  $3    Food:Groceries
+                $-10
+              -5 PLN  Income:Salary
+--------------------
+                   0
+"#
+        );
+    }
+
+    #[test]
+    fn dates_and_depth() {
+        let ledger: Ledger = SOURCE.parse().unwrap();
+        let settings = AccountTreeSettings::default()
+            .with_begin(NaiveDate::from_ymd_opt(2018, 10, 2).unwrap())
+            .with_depth(1);
+        let tree = AccountTree::new(&ledger, &settings).unwrap();
+        let rows = tree.rows();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].name, "Assets");
+        assert_eq!(rows[0].total.to_string(), "$-4");
+        assert_eq!(rows[1].account, "Expenses");
+        assert_eq!(rows[1].total.to_string(), "$4");
+
+        let settings =
+            AccountTreeSettings::default().with_end(NaiveDate::from_ymd_opt(2018, 10, 2).unwrap());
+        let tree = AccountTree::new(&ledger, &settings).unwrap();
+        assert!(tree.get(&account("Expenses")).is_none());
+    }
+
+    #[test]
+    fn uninferable_amounts() {
+        let ledger: Ledger = format!(
+            "{}\n2018-10-03 Payee\n  Expenses  $1\n  Assets\n  Income\n",
+            SOURCE
+        )
+        .parse()
+        .unwrap();
+        let error = AccountTree::new(&ledger, &AccountTreeSettings::default()).unwrap_err();
+        assert!(matches!(
+            error,
+            ReportError::Inference {
+                item: 4,
+                posting: 1,
+                error: BalanceError::MultipleNullAmounts { .. }
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "item 4 posting 1: cannot infer the amount: \
+             only one real posting may have no amount, found [1, 2]"
+        );
+    }
+}
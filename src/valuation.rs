@@ -0,0 +1,443 @@
+use crate::model::*;
+use crate::register::posting_date;
+use crate::MixedAmount;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use rust_decimal::{Decimal, RoundingStrategy};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+///
+/// Where a price comes from.
+///
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PriceSource {
+    /// `P` directive at the given index of `Ledger::items`.
+    Directive { item: usize },
+    /// `@`/`@@` price of a posting.
+    Posting { item: usize, posting: usize },
+}
+
+///
+/// Price of one unit of `commodity_name` at a point in time.
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Quote {
+    pub datetime: NaiveDateTime,
+    pub commodity_name: String,
+    pub price: Amount,
+    pub source: PriceSource,
+}
+
+///
+/// Step of a conversion from one commodity to another with a single quote.
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Hop {
+    pub from: String,
+    pub to: String,
+    /// Price of one unit of `from` in `to`.
+    pub rate: Decimal,
+    /// Quote the rate was taken from.
+    pub quote: Quote,
+    /// The quote is the price of `to`, so its inverse was used.
+    pub inverted: bool,
+}
+
+///
+/// Single conversion made by [`PriceDb::value`].
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Conversion {
+    pub from: Amount,
+    pub to: Amount,
+    /// Price of one unit of `from` in the target commodity.
+    pub rate: Decimal,
+    /// Quotes the rate was taken from, from the commodity of `from` to the target.
+    pub hops: Vec<Hop>,
+}
+
+///
+/// Result of [`PriceDb::value`].
+///
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Valuation {
+    /// Value in the target commodity, plus the amounts which could not be converted.
+    pub value: MixedAmount,
+    pub conversions: Vec<Conversion>,
+    /// Amounts without a price in the target commodity, or whose value overflows.
+    pub unconverted: Vec<Amount>,
+}
+
+///
+/// Commodity prices from `P` directives and from the prices of postings.
+///
+#[derive(Debug, Default, Clone)]
+pub struct PriceDb {
+    /// Quotes by (commodity, price commodity), in the order of their date and time.
+    quotes: HashMap<(String, String), Vec<Quote>>,
+    /// Positions of the commodities, to write converted amounts in their style.
+    styles: HashMap<String, CommodityPosition>,
+    /// Display precisions of the commodities, to round converted amounts to.
+    precisions: HashMap<String, u32>,
+}
+
+impl PriceDb {
+    /// Quotes of the `P` directives and the posting prices of the ledger. Converted
+    /// amounts are rounded to the precisions of the ledger
+    /// (see [`Ledger::commodity_precisions`]), commodities used only in `P` directives
+    /// get the precision of those.
+    pub fn new(ledger: &Ledger) -> Self {
+        let mut db = PriceDb {
+            precisions: ledger.commodity_precisions(),
+            ..PriceDb::default()
+        };
+        let mut directive_precisions: HashMap<String, u32> = HashMap::new();
+        for (item, ledger_item) in ledger.items.iter().enumerate() {
+            match ledger_item {
+                LedgerItem::CommodityPrice(price) => {
+                    let precision = directive_precisions
+                        .entry(price.amount.commodity.name.clone())
+                        .or_default();
+                    *precision = (*precision).max(price.amount.quantity.scale());
+                    db.add(Quote {
+                        datetime: price.datetime,
+                        commodity_name: price.commodity_name.clone(),
+                        price: Amount::new(price.amount.quantity, price.amount.commodity.clone()),
+                        source: PriceSource::Directive { item },
+                    });
+                }
+                LedgerItem::Transaction(transaction) => {
+                    for (index, posting) in transaction.postings.iter().enumerate() {
+                        let Some(ref amount) = posting.amount else {
+                            continue;
+                        };
+                        db.add_style(&amount.amount.commodity);
+                        let quantity = amount.amount.quantity.abs();
+                        let price = match amount.price {
+                            Some(Price::Unit(ref price)) => price.clone(),
+                            Some(Price::Total(ref price)) => match price.checked_div(quantity) {
+                                Ok(price) => price,
+                                Err(_) => continue,
+                            },
+                            _ => continue,
+                        };
+                        db.add(Quote {
+                            datetime: posting_date(transaction, posting, false)
+                                .and_time(NaiveTime::MIN),
+                            commodity_name: amount.amount.commodity.name.clone(),
+                            price: Amount::new(price.quantity, price.commodity),
+                            source: PriceSource::Posting {
+                                item,
+                                posting: index,
+                            },
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        for (commodity_name, precision) in directive_precisions {
+            db.precisions.entry(commodity_name).or_insert(precision);
+        }
+        db
+    }
+
+    pub fn add(&mut self, quote: Quote) {
+        self.add_style(&quote.price.commodity);
+        let quotes = self
+            .quotes
+            .entry((
+                quote.commodity_name.clone(),
+                quote.price.commodity.name.clone(),
+            ))
+            .or_default();
+        // After the quotes with the same time, so the last one given is used.
+        let index = quotes.partition_point(|other| other.datetime <= quote.datetime);
+        quotes.insert(index, quote);
+    }
+
+    fn add_style(&mut self, commodity: &Commodity) {
+        self.styles
+            .entry(commodity.name.clone())
+            .or_insert(commodity.position);
+    }
+
+    /// Price of one unit of `commodity_name` in `target` on `date`, with the hops it is
+    /// made of. Without a quote between the two commodities, the price goes through
+    /// other commodities, using the shortest chain of quotes (like ledger does).
+    pub fn rate(
+        &self,
+        commodity_name: &str,
+        target: &str,
+        date: NaiveDate,
+    ) -> Option<(Decimal, Vec<Hop>)> {
+        let path = self.path(commodity_name, target, date)?;
+        let mut rate = Decimal::ONE;
+        let mut hops = Vec::new();
+        for pair in path.windows(2) {
+            let (hop_rate, quote, inverted) = self.hop(pair[0], pair[1], date)?;
+            rate = rate.checked_mul(hop_rate)?;
+            hops.push(Hop {
+                from: pair[0].to_owned(),
+                to: pair[1].to_owned(),
+                rate: hop_rate,
+                quote: quote.clone(),
+                inverted,
+            });
+        }
+        Some((rate, hops))
+    }
+
+    /// Shortest chain of commodities from `from` to `to`, each quoted in the next one
+    /// (or the other way around) on `date` or earlier. Found by a breadth-first search,
+    /// going through the commodities in alphabetical order.
+    fn path<'a>(&'a self, from: &'a str, to: &str, date: NaiveDate) -> Option<Vec<&'a str>> {
+        let mut neighbours: HashMap<&str, BTreeSet<&str>> = HashMap::new();
+        for (commodity, price_commodity) in self.quotes.keys() {
+            for (from, to) in [(commodity, price_commodity), (price_commodity, commodity)] {
+                if self.hop(from, to, date).is_some() {
+                    neighbours.entry(from).or_default().insert(to);
+                }
+            }
+        }
+
+        let mut previous: HashMap<&str, &str> = HashMap::new();
+        let mut queue = VecDeque::from([from]);
+        while let Some(commodity) = queue.pop_front() {
+            if commodity == to {
+                let mut path = vec![commodity];
+                while let Some(&commodity) = previous.get(path.last()?) {
+                    path.push(commodity);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for &next in neighbours.get(commodity).into_iter().flatten() {
+                if next != from && !previous.contains_key(next) {
+                    previous.insert(next, commodity);
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+
+    /// Latest quote of `commodity_name` in `target` from `date` or earlier, used
+    /// directly or inverted (if the quote is the price of `target`). Returns the rate
+    /// with the quote and whether it was inverted.
+    fn hop(
+        &self,
+        commodity_name: &str,
+        target: &str,
+        date: NaiveDate,
+    ) -> Option<(Decimal, &Quote, bool)> {
+        let latest = |from: &str, to: &str| {
+            let quotes = self.quotes.get(&(from.to_owned(), to.to_owned()))?;
+            let count = quotes.partition_point(|quote| quote.datetime.date() <= date);
+            count.checked_sub(1).map(|index| &quotes[index])
+        };
+
+        let direct = latest(commodity_name, target);
+        let inverse = latest(target, commodity_name).filter(|quote| !quote.price.is_zero());
+        match (direct, inverse) {
+            (Some(direct), Some(inverse)) if inverse.datetime > direct.datetime => {
+                Some((Decimal::ONE / inverse.price.quantity, inverse, true))
+            }
+            (Some(direct), _) => Some((direct.price.quantity, direct, false)),
+            (None, Some(inverse)) => Some((Decimal::ONE / inverse.price.quantity, inverse, true)),
+            (None, None) => None,
+        }
+    }
+
+    /// Converts the amounts to the `target` commodity with the prices valid on `date`
+    /// (see [`rate`](PriceDb::rate)), rounded to the precision of the target commodity.
+    /// Amounts already in the target commodity are kept, amounts without a price or
+    /// whose value overflows are left in their commodity.
+    pub fn value(&self, amount: &MixedAmount, target: &str, date: NaiveDate) -> Valuation {
+        let target_commodity = Commodity {
+            name: target.to_owned(),
+            position: self
+                .styles
+                .get(target)
+                .copied()
+                .unwrap_or(CommodityPosition::Right),
+        };
+        let mut valuation = Valuation {
+            value: MixedAmount::new(),
+            conversions: Vec::new(),
+            unconverted: Vec::new(),
+        };
+
+        for amount in amount.amounts() {
+            if amount.commodity.name == target {
+                valuation.value += amount;
+                continue;
+            }
+            let conversion =
+                self.rate(&amount.commodity.name, target, date)
+                    .and_then(|(rate, hops)| {
+                        let mut quantity = amount.quantity.checked_mul(rate)?;
+                        if let Some(&precision) = self.precisions.get(target) {
+                            quantity = quantity.round_dp_with_strategy(
+                                precision,
+                                RoundingStrategy::MidpointAwayFromZero,
+                            );
+                        }
+                        let to = Amount::new(quantity, target_commodity.clone());
+                        valuation.value.checked_add_amount(&to).ok()?;
+                        Some(Conversion {
+                            from: amount.clone(),
+                            to,
+                            rate,
+                            hops,
+                        })
+                    });
+            match conversion {
+                Some(conversion) => valuation.conversions.push(conversion),
+                None => {
+                    valuation.value += amount;
+                    valuation.unconverted.push(amount.clone());
+                }
+            }
+        }
+        valuation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"P 2018-10-01 00:00:00 USD 3.80 PLN
+P 2018-10-10 00:00:00 USD 3.90 PLN
+P 2018-10-05 00:00:00 PLN 0.25 EUR
+
+2018-10-03 Broker
+  Assets:Stocks  10 AAPL @@ 1,000.00 USD
+  Assets:Bank  -1,000.00 USD
+"#;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2018, 10, day).unwrap()
+    }
+
+    fn holdings() -> MixedAmount {
+        let ledger: Ledger = "2018-10-01 Payee\n  A  100 USD\n  B  20 EUR\n  C  1 AAPL\n  D  5 PLN\n  E  1 XYZ\n  F\n"
+            .parse()
+            .unwrap();
+        let LedgerItem::Transaction(ref transaction) = ledger.items[0] else {
+            unreachable!()
+        };
+        transaction
+            .postings
+            .iter()
+            .filter_map(|posting| posting.amount.as_ref())
+            .map(|amount| amount.amount.clone())
+            .collect()
+    }
+
+    #[test]
+    fn nearest_earlier_quotes() {
+        let ledger: Ledger = SOURCE.parse().unwrap();
+        let db = PriceDb::new(&ledger);
+
+        let (rate, quote, inverted) = db.hop("USD", "PLN", date(9)).unwrap();
+        assert_eq!((rate, inverted), (Decimal::new(380, 2), false));
+        assert_eq!(quote.source, PriceSource::Directive { item: 0 });
+        assert_eq!(
+            db.hop("USD", "PLN", date(10)).unwrap().0,
+            Decimal::new(390, 2)
+        );
+        assert!(db
+            .rate("USD", "PLN", NaiveDate::from_ymd_opt(2018, 9, 30).unwrap())
+            .is_none());
+
+        let (rate, quote, _) = db.hop("AAPL", "USD", date(3)).unwrap();
+        assert_eq!(rate, Decimal::new(100, 0));
+        assert_eq!(
+            quote.source,
+            PriceSource::Posting {
+                item: 4,
+                posting: 0
+            }
+        );
+
+        let (rate, _, inverted) = db.hop("EUR", "PLN", date(5)).unwrap();
+        assert_eq!((rate, inverted), (Decimal::new(4, 0), true));
+
+        let (rate, hops) = db.rate("AAPL", "EUR", date(20)).unwrap();
+        assert_eq!(rate, Decimal::new(975, 1));
+        let commodities = hops.iter().map(|hop| hop.to.as_str()).collect::<Vec<_>>();
+        assert_eq!(commodities, vec!["USD", "PLN", "EUR"]);
+        assert!(!hops[2].inverted);
+        assert!(db.rate("AAPL", "EUR", date(4)).is_none());
+    }
+
+    #[test]
+    fn value_in_target_commodity() {
+        let ledger: Ledger = SOURCE.parse().unwrap();
+        let db = PriceDb::new(&ledger);
+
+        let valuation = db.value(&holdings(), "PLN", date(20));
+        // 100 * 3.90 + 20 * 4 + 100 * 3.90 + 5, XYZ has no price
+        assert_eq!(valuation.value.to_string(), "865.00 PLN, 1 XYZ");
+        assert_eq!(valuation.conversions.len(), 3);
+        assert_eq!(valuation.conversions[1].from.to_string(), "20 EUR");
+        assert_eq!(valuation.conversions[2].to.to_string(), "390.00 PLN");
+        assert_eq!(valuation.unconverted.len(), 1);
+
+        // 1 AAPL is valued through its USD price.
+        let conversion = &valuation.conversions[0];
+        assert_eq!(conversion.from.to_string(), "1 AAPL");
+        assert_eq!(conversion.to.to_string(), "390.00 PLN");
+        let hops = conversion
+            .hops
+            .iter()
+            .map(|hop| (hop.from.as_str(), hop.to.as_str(), hop.rate.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            hops,
+            vec![
+                ("AAPL", "USD", "100.00".to_owned()),
+                ("USD", "PLN", "3.90".to_owned())
+            ]
+        );
+        assert_eq!(
+            conversion.hops[1].quote.source,
+            PriceSource::Directive { item: 1 }
+        );
+    }
+
+    #[test]
+    fn rounding_and_overflow() {
+        let source = format!(
+            "{}P 2018-10-05 00:00:00 XYZ 79228162514264337593543950335 PLN\nP 2018-10-05 00:00:00 ABC 1.23456 USD\n",
+            SOURCE
+        );
+        let ledger: Ledger = source.parse().unwrap();
+        let db = PriceDb::new(&ledger);
+
+        // USD has the precision of the postings, PLN of the `P` directives.
+        let amount = |quantity: i64, commodity_name: &str| {
+            Amount::new(
+                Decimal::new(quantity, 0),
+                Commodity {
+                    name: commodity_name.to_owned(),
+                    position: CommodityPosition::Right,
+                },
+            )
+        };
+        let valuation = db.value(&amount(3, "ABC").into(), "USD", date(20));
+        assert_eq!(valuation.value.to_string(), "3.70 USD");
+        assert_eq!(valuation.conversions[0].rate, Decimal::new(123456, 5));
+
+        let valuation = db.value(&holdings(), "PLN", date(20));
+        assert_eq!(valuation.value.to_string(), "865.00 PLN, 1 XYZ");
+        // The value of 1 XYZ fits, but not the sum with the others.
+        assert_eq!(valuation.unconverted.len(), 1);
+        let mut holdings = holdings();
+        holdings += &amount(2, "XYZ");
+        let valuation = db.value(&holdings, "PLN", date(20));
+        assert_eq!(valuation.value.to_string(), "865.00 PLN, 3 XYZ");
+        assert_eq!(valuation.unconverted[0].to_string(), "3 XYZ");
+    }
+}